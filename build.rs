@@ -0,0 +1,45 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generates the `Proofs` service of src/grpc.rs.
+#[cfg(feature = "grpc")]
+fn grpc() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::{}", input))
+            .output_type(format!("crate::grpc::{}", output))
+            .codec_path("tonic::codec::ProstCodec")
+            .build()
+    };
+
+    let service = Service::builder()
+        .name("Proofs")
+        .package("merkle")
+        .method(method(
+            "get_root",
+            "GetRoot",
+            "GetRootRequest",
+            "GetRootResponse",
+        ))
+        .method(method(
+            "get_inclusion_proof",
+            "GetInclusionProof",
+            "GetInclusionProofRequest",
+            "ProofResponse",
+        ))
+        .method(method(
+            "get_consistency_proof",
+            "GetConsistencyProof",
+            "GetConsistencyProofRequest",
+            "ProofResponse",
+        ))
+        .build();
+
+    Builder::new().compile(&[service]);
+}
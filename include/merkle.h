@@ -0,0 +1,124 @@
+#ifndef MERKLE_H
+#define MERKLE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define MERKLE_OK 0
+
+/*
+ A required pointer is null, or a count or position is out of range.
+ */
+#define MERKLE_ERR_INVALID_ARGUMENT -1
+
+/*
+ Proof bytes could not be decoded.
+ */
+#define MERKLE_ERR_INVALID_ENCODING -2
+
+/*
+ A proof decoded but does not prove the value against the root.
+ */
+#define MERKLE_ERR_INVALID_PROOF -3
+
+/*
+ Length of a root, leaf or node hash.
+ */
+#define MERKLE_HASH_LEN 32
+
+/*
+ A tree of SHA-256 hashes, owned by the caller until `merkle_tree_free`.
+ */
+typedef struct MerkleTree MerkleTree;
+
+/*
+ Bytes allocated by this library, released with `merkle_buffer_free`.
+ */
+typedef struct MerkleBuffer {
+  uint8_t *data;
+  size_t len;
+} MerkleBuffer;
+
+/*
+ Builds a tree over `count` values, the `i`th being `lens[i]` bytes at
+ `values[i]`. Returns null if `count` is 0 or a pointer is null.
+
+ # Safety
+
+ `values` and `lens` must point to `count` elements each, and every value
+ must be readable for its length.
+ */
+struct MerkleTree *merkle_tree_new(const uint8_t *const *values, const size_t *lens, size_t count);
+
+/*
+ Releases a tree made by `merkle_tree_new`. Does nothing for null.
+
+ # Safety
+
+ `tree` must be null or a tree not freed already.
+ */
+void merkle_tree_free(struct MerkleTree *tree);
+
+/*
+ Returns the number of leaves, or 0 for null.
+
+ # Safety
+
+ `tree` must be null or a live tree.
+ */
+size_t merkle_tree_count_leaves(const struct MerkleTree *tree);
+
+/*
+ Copies the root into the `MERKLE_HASH_LEN` bytes at `out`.
+
+ # Safety
+
+ `tree` must be null or a live tree, and `out` null or writable for
+ `MERKLE_HASH_LEN` bytes.
+ */
+int32_t merkle_tree_root(const struct MerkleTree *tree, uint8_t *out);
+
+/*
+ Stores the inclusion proof of the leaf at `position` in `out`, encoded as
+ `merkle_proof_verify` reads it.
+
+ # Safety
+
+ `tree` must be null or a live tree, and `out` null or writable.
+ */
+int32_t merkle_tree_gen_proof(const struct MerkleTree *tree,
+                              size_t position,
+                              struct MerkleBuffer *out);
+
+/*
+ Checks that `value` is the leaf proven by `proof` in the tree with `root`.
+ Returns `MERKLE_OK` if it is and `MERKLE_ERR_INVALID_PROOF` if not.
+
+ # Safety
+
+ Each pointer must be readable for its length, or may be null if the
+ length is 0.
+ */
+int32_t merkle_proof_verify(const uint8_t *proof,
+                            size_t proof_len,
+                            const uint8_t *root,
+                            size_t root_len,
+                            const uint8_t *value,
+                            size_t value_len);
+
+/*
+ Releases the bytes of a buffer filled by this library and empties it.
+
+ # Safety
+
+ `buffer` must be null or point to a buffer filled by this library and not
+ freed already.
+ */
+void merkle_buffer_free(struct MerkleBuffer *buffer);
+
+#endif  /* MERKLE_H */
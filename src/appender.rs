@@ -0,0 +1,184 @@
+use digest::Digest;
+
+use crate::{hash_internal_node, hash_leaf, height, AsBytes, Error, Hash, MerkleTree, Padding};
+
+/// Computes the root of an append-only sequence while storing only the right
+/// edge of the tree.
+///
+/// `frontier[k]` holds the root of the complete subtree of `2^k` leaves that
+/// is still waiting for a right sibling, so memory is O(log n). No proofs can
+/// be generated for old leaves.
+pub struct Appender<H> {
+    hasher: H,
+    frontier: Vec<Option<Hash>>,
+    count_leaves: usize,
+    padding: Padding,
+}
+
+impl<H: Digest> Appender<H> {
+    pub fn with_hasher(hasher: H) -> Appender<H> {
+        Appender::with_padding(hasher, Padding::Duplicate)
+    }
+
+    /// Creates an appender whose root matches a tree using `padding`.
+    pub fn with_padding(hasher: H, padding: Padding) -> Appender<H> {
+        Appender {
+            hasher,
+            frontier: Vec::new(),
+            count_leaves: 0,
+            padding,
+        }
+    }
+
+    /// Resumes appending after `count_leaves` leaves whose complete right
+    /// edge subtrees are `frontier`.
+    pub(crate) fn from_frontier(
+        hasher: H,
+        frontier: Vec<Option<Hash>>,
+        count_leaves: usize,
+        padding: Padding,
+    ) -> Appender<H> {
+        Appender {
+            hasher,
+            frontier,
+            count_leaves,
+            padding,
+        }
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn append<T>(&mut self, value: &T)
+    where
+        T: AsBytes,
+    {
+        let mut node = hash_leaf(value, &mut self.hasher);
+        let mut level = 0;
+
+        while self.count_leaves >> level & 1 == 1 {
+            let left = self.frontier[level].take().unwrap();
+            node = hash_internal_node(&left, Some(&node), self.padding, &mut self.hasher);
+            level += 1;
+        }
+
+        if level == self.frontier.len() {
+            self.frontier.push(None);
+        }
+        self.frontier[level] = Some(node);
+        self.count_leaves += 1;
+    }
+
+    /// Returns the root of the leaves appended so far, the same root a
+    /// `MerkleTree` over them would have.
+    pub fn root(&mut self) -> Option<Hash> {
+        if self.count_leaves == 0 {
+            return None;
+        }
+
+        let (padding, hasher) = (self.padding, &mut self.hasher);
+        let mut right: Option<Hash> = None;
+        for level in 0..height(self.count_leaves) {
+            right = match (&self.frontier[level], right) {
+                (Some(left), Some(r)) => Some(hash_internal_node(left, Some(&r), padding, hasher)),
+                (Some(left), None) => Some(hash_internal_node(left, None, padding, hasher)),
+                (None, Some(r)) => Some(hash_internal_node(&r, None, padding, hasher)),
+                (None, None) => None,
+            };
+        }
+
+        right.or_else(|| self.frontier.last().cloned().flatten())
+    }
+}
+
+impl<H: Digest + Clone> MerkleTree<H> {
+    /// Returns an `Appender` positioned after the last leaf of this tree.
+    pub fn appender(&self) -> Appender<H> {
+        self.try_appender().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `appender`, but fails instead of panicking if the tree has
+    /// deferred changes.
+    pub fn try_appender(&self) -> Result<Appender<H>, Error> {
+        self.check_clean()?;
+
+        let count_leaves = self.count_leaves();
+        let frontier = (0..self.levels.len())
+            .map(|level| {
+                if count_leaves >> level & 1 == 1 {
+                    Some(self.levels[level][(count_leaves >> level) - 1].clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Appender {
+            hasher: self.hasher.clone(),
+            frontier,
+            count_leaves,
+            padding: self.padding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn values(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i as u8]).collect()
+    }
+
+    fn tree(count: usize, padding: Padding) -> MerkleTree<Sha256> {
+        let mut tree = MerkleTree::build_with_hasher(&values(count), Sha256::default());
+        tree.set_padding(padding);
+        tree
+    }
+
+    #[test]
+    fn root_matches_tree() {
+        for padding in [Padding::Duplicate, Padding::Empty] {
+            let mut appender = Appender::with_padding(Sha256::default(), padding);
+            assert_eq!(appender.root(), None);
+            for count in 1..=17 {
+                appender.append(&vec![count as u8 - 1]);
+                assert_eq!(appender.count_leaves(), count);
+                assert_eq!(
+                    appender.root().as_ref(),
+                    Some(tree(count, padding).root_hash()),
+                    "{} leaves, {:?}",
+                    count,
+                    padding
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn appender_resumes_after_tree() {
+        for count in 1..=9 {
+            let mut appender = tree(count, Padding::Empty).appender();
+            appender.append(&vec![count as u8]);
+            appender.append(&vec![count as u8 + 1]);
+            assert_eq!(
+                appender.root().as_ref(),
+                Some(tree(count + 2, Padding::Empty).root_hash()),
+                "{} leaves",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn try_appender_fails_on_deferred_changes() {
+        let mut tree = tree(3, Padding::Duplicate);
+        tree.defer(&[crate::Mutation::Push(vec![3])]);
+        assert!(matches!(tree.try_appender(), Err(Error::DeferredChanges)));
+        tree.flush();
+        assert_eq!(tree.try_appender().unwrap().count_leaves(), 4);
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+
+use digest::Digest;
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+use crate::codec::{Reader, Writer};
+use crate::{hash_leaf, Error, Limits, MerkleTree, Padding, Proof};
+
+/// Prefix of every hash drawing challenge positions, so they cannot be made
+/// to collide with hashes the seed is used for elsewhere.
+const AUDIT_DOMAIN: &[u8] = b"merkle-rs audit v1\0";
+
+/// Length of the seeds `AuditChallenge::random` draws.
+#[cfg(feature = "rand")]
+const SEED_LEN: usize = 32;
+
+/// Asks a prover claiming to store the leaves of a tree to produce some of
+/// them, at positions derived from a seed.
+///
+/// The positions are a function of the seed alone, so the verifier only
+/// sends the seed and a prover cannot know which leaves it will be asked for
+/// before it sees it. A prover that dropped a fraction `f` of the leaves
+/// passes a challenge of `k` positions with probability about `(1 - f)^k`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditChallenge {
+    seed: Vec<u8>,
+    count_leaves: usize,
+    k: usize,
+}
+
+/// Draws numbers below some bound from hashes of the seed and a counter.
+struct Draws<'a, H> {
+    seed: &'a [u8],
+    counter: u64,
+    hasher: H,
+}
+
+impl<H: Digest> Draws<'_, H> {
+    /// Returns a number uniformly distributed below `bound`, rejecting the
+    /// draws that would favour small numbers.
+    fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let draw = self.next_u64();
+            if draw < limit {
+                return (draw % bound) as usize;
+            }
+        }
+    }
+
+    /// Returns the first 8 bytes of the next digests, joining as many as a
+    /// short hash needs.
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        let mut filled = 0;
+        while filled < bytes.len() {
+            let mut w = Writer::default();
+            w.bytes(self.seed);
+            w.u64(self.counter);
+            self.counter += 1;
+
+            self.hasher.update(AUDIT_DOMAIN);
+            self.hasher.update(w.into_bytes());
+            let digest = self.hasher.finalize_reset();
+            if digest.is_empty() {
+                break;
+            }
+            let n = digest.len().min(bytes.len() - filled);
+            bytes[filled..filled + n].copy_from_slice(&digest[..n]);
+            filled += n;
+        }
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl AuditChallenge {
+    /// Challenges `k` distinct leaves of a tree of `count_leaves` leaves,
+    /// picked from `seed`.
+    pub fn new(seed: &[u8], count_leaves: usize, k: usize) -> AuditChallenge {
+        AuditChallenge::try_new(seed, count_leaves, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `count_leaves` is zero or `k` is larger than it.
+    pub fn try_new(seed: &[u8], count_leaves: usize, k: usize) -> Result<AuditChallenge, Error> {
+        if count_leaves == 0 {
+            return Err(Error::InvalidSize(count_leaves));
+        }
+        if k > count_leaves {
+            return Err(Error::InvalidSize(k));
+        }
+
+        Ok(AuditChallenge {
+            seed: seed.to_vec(),
+            count_leaves,
+            k,
+        })
+    }
+
+    /// Like `new`, with a seed drawn from `rng`.
+    #[cfg(feature = "rand")]
+    pub fn random<R>(rng: &mut R, count_leaves: usize, k: usize) -> AuditChallenge
+    where
+        R: Rng + ?Sized,
+    {
+        let mut seed = vec![0u8; SEED_LEN];
+        rng.fill(&mut seed[..]);
+        AuditChallenge::new(&seed, count_leaves, k)
+    }
+
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the challenged positions in the order they are answered,
+    /// drawn with `H` as a partial Fisher-Yates shuffle of all positions.
+    ///
+    /// Any output size works: a hash shorter than 8 bytes draws each number
+    /// from several digests.
+    pub fn positions<H: Digest>(&self) -> Vec<usize> {
+        let mut draws = Draws {
+            seed: &self.seed,
+            counter: 0,
+            hasher: H::new(),
+        };
+        // Only the slots moved by the shuffle so far, the others still
+        // hold their own position.
+        let mut moved: HashMap<usize, usize> = HashMap::new();
+        (0..self.k)
+            .map(|i| {
+                let j = i + draws.below(self.count_leaves - i);
+                let at_j = moved.get(&j).copied().unwrap_or(j);
+                let at_i = moved.get(&i).copied().unwrap_or(i);
+                moved.insert(j, at_i);
+                at_j
+            })
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(&self.seed);
+        w.usize(self.count_leaves);
+        w.usize(self.k);
+        w.into_bytes()
+    }
+
+    /// Decodes a challenge written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<AuditChallenge, Error> {
+        AuditChallenge::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on challenges going past `limits`.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<AuditChallenge, Error> {
+        let mut r = Reader::new(bytes);
+        let seed = r.bytes()?.to_vec();
+        let count_leaves = r.usize()?;
+        limits.check_leaves(count_leaves)?;
+        let k = r.usize()?;
+        r.finish()?;
+        if count_leaves == 0 || k > count_leaves {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(AuditChallenge {
+            seed,
+            count_leaves,
+            k,
+        })
+    }
+
+    /// Checks that `response` answers every challenged position, in order,
+    /// with a leaf of the tree with `root_hash`.
+    ///
+    /// Fails with `Error::InvalidPosition` at the first answer missing or
+    /// for another position, and with `Error::InvalidChunk` at the first
+    /// leaf that does not verify.
+    pub fn verify_with_hasher<H>(
+        &self,
+        root_hash: &[u8],
+        response: &AuditResponse,
+        hasher: H,
+    ) -> Result<(), Error>
+    where
+        H: Digest,
+    {
+        self.verify_with_padding(root_hash, response, Padding::Duplicate, hasher)
+    }
+
+    /// Like `verify_with_hasher`, for a tree using `padding`.
+    pub fn verify_with_padding<H>(
+        &self,
+        root_hash: &[u8],
+        response: &AuditResponse,
+        padding: Padding,
+        mut hasher: H,
+    ) -> Result<(), Error>
+    where
+        H: Digest,
+    {
+        let positions = self.positions::<H>();
+        for (i, &position) in positions.iter().enumerate() {
+            let (value, proof) = response
+                .answers
+                .get(i)
+                .ok_or(Error::InvalidPosition(position))?;
+            if proof.position() != position {
+                return Err(Error::InvalidPosition(position));
+            }
+            if proof.count_leaves() != self.count_leaves {
+                return Err(Error::InvalidChunk(position));
+            }
+
+            let leaf = hash_leaf(value, &mut hasher);
+            if !proof.validate_leaf(root_hash, leaf, padding, &mut hasher) {
+                return Err(Error::InvalidChunk(position));
+            }
+        }
+
+        match response.answers.get(positions.len()) {
+            Some((_, proof)) => Err(Error::InvalidPosition(proof.position())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The leaves a prover produced for an `AuditChallenge`, each with its
+/// inclusion proof, in the order of the challenged positions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditResponse {
+    answers: Vec<(Vec<u8>, Proof)>,
+}
+
+impl AuditResponse {
+    pub fn new(answers: Vec<(Vec<u8>, Proof)>) -> AuditResponse {
+        AuditResponse { answers }
+    }
+
+    pub fn answers(&self) -> &[(Vec<u8>, Proof)] {
+        &self.answers
+    }
+
+    /// Encodes every answer into a single message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.usize(self.answers.len());
+        for (value, proof) in &self.answers {
+            w.bytes(value);
+            w.bytes(&proof.to_bytes());
+        }
+        w.into_bytes()
+    }
+
+    /// Decodes a response written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<AuditResponse, Error> {
+        AuditResponse::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on responses or proofs going past
+    /// `limits`. A response counts as many leaves as it answers.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<AuditResponse, Error> {
+        let mut r = Reader::new(bytes);
+        let len = r.usize()?;
+        limits.check_leaves(len)?;
+
+        let mut answers = Vec::new();
+        for _ in 0..len {
+            let value = r.bytes()?.to_vec();
+            let proof = Proof::from_bytes_with_limits(r.bytes()?, limits)?;
+            answers.push((value, proof));
+        }
+        r.finish()?;
+
+        Ok(AuditResponse { answers })
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Answers `challenge` with the value of every challenged leaf, read
+    /// with `value`, and its proof. A tree built with
+    /// `build_retaining_values` can read them back with `MerkleTree::value`.
+    ///
+    /// Fails with `Error::InvalidSize` if the challenge is for a tree of
+    /// another size, or `Error::DeferredChanges` if the tree has deferred
+    /// changes.
+    pub fn respond<F>(
+        &self,
+        challenge: &AuditChallenge,
+        mut value: F,
+    ) -> Result<AuditResponse, Error>
+    where
+        F: FnMut(usize) -> Vec<u8>,
+    {
+        if challenge.count_leaves != self.count_leaves() {
+            return Err(Error::InvalidSize(challenge.count_leaves));
+        }
+        self.check_clean()?;
+
+        let answers = challenge
+            .positions::<H>()
+            .into_iter()
+            .map(|position| Ok((value(position), self.try_gen_proof(position)?)))
+            .collect::<Result<_, Error>>()?;
+        Ok(AuditResponse { answers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        let values: Vec<Vec<u8>> = (0..20).map(|i| vec![i]).collect();
+        MerkleTree::build_retaining_values(&values, Sha256::default())
+    }
+
+    fn answer(tree: &MerkleTree<Sha256>, challenge: &AuditChallenge) -> AuditResponse {
+        tree.respond(challenge, |i| tree.value(i).unwrap().to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn positions_are_distinct_and_seeded() {
+        let challenge = AuditChallenge::new(b"seed", 20, 8);
+        let positions = challenge.positions::<Sha256>();
+        assert_eq!(positions, challenge.positions::<Sha256>());
+        assert_ne!(
+            positions,
+            AuditChallenge::new(b"other", 20, 8).positions::<Sha256>()
+        );
+
+        let mut sorted = positions.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 8);
+        assert!(sorted.iter().all(|&p| p < 20));
+
+        let mut all = AuditChallenge::new(b"seed", 20, 20).positions::<Sha256>();
+        all.sort_unstable();
+        assert_eq!(all, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn honest_response_verifies() {
+        let tree = tree();
+        let challenge = AuditChallenge::new(b"seed", 20, 5);
+        let response = answer(&tree, &challenge);
+        assert_eq!(
+            challenge.verify_with_hasher(tree.root_hash(), &response, Sha256::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn wrong_answers_are_caught() {
+        let tree = tree();
+        let challenge = AuditChallenge::new(b"seed", 20, 5);
+        let positions = challenge.positions::<Sha256>();
+        let response = answer(&tree, &challenge);
+        let verify = |answers: Vec<(Vec<u8>, Proof)>| {
+            challenge.verify_with_hasher(
+                tree.root_hash(),
+                &AuditResponse::new(answers),
+                Sha256::default(),
+            )
+        };
+
+        let mut forged = response.answers().to_vec();
+        forged[1].0 = vec![0xff];
+        assert_eq!(verify(forged), Err(Error::InvalidChunk(positions[1])));
+
+        let mut swapped = response.answers().to_vec();
+        swapped.swap(0, 1);
+        assert_eq!(verify(swapped), Err(Error::InvalidPosition(positions[0])));
+
+        let mut short = response.answers().to_vec();
+        short.pop();
+        assert_eq!(verify(short), Err(Error::InvalidPosition(positions[4])));
+
+        let mut long = response.answers().to_vec();
+        long.push((vec![0], tree.gen_proof(0)));
+        assert_eq!(verify(long), Err(Error::InvalidPosition(0)));
+    }
+
+    #[test]
+    fn challenge_for_another_size_is_refused() {
+        let tree = tree();
+        let challenge = AuditChallenge::new(b"seed", 21, 5);
+        assert_eq!(
+            tree.respond(&challenge, |_| Vec::new()),
+            Err(Error::InvalidSize(21))
+        );
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let tree = tree();
+        let challenge = AuditChallenge::new(b"seed", 20, 3);
+        let response = answer(&tree, &challenge);
+
+        assert_eq!(
+            AuditChallenge::from_bytes(&challenge.to_bytes()),
+            Ok(challenge.clone())
+        );
+        assert_eq!(
+            AuditResponse::from_bytes(&response.to_bytes()),
+            Ok(response)
+        );
+        let oversized = AuditChallenge {
+            k: 21,
+            ..challenge.clone()
+        };
+        assert_eq!(
+            AuditChallenge::from_bytes(&oversized.to_bytes()),
+            Err(Error::InvalidEncoding)
+        );
+        let empty = AuditChallenge {
+            count_leaves: 0,
+            k: 0,
+            ..challenge
+        };
+        assert_eq!(
+            AuditChallenge::from_bytes(&empty.to_bytes()),
+            Err(Error::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn try_new_fails_on_bad_sizes() {
+        assert_eq!(
+            AuditChallenge::try_new(b"seed", 0, 0),
+            Err(Error::InvalidSize(0))
+        );
+        assert_eq!(
+            AuditChallenge::try_new(b"seed", 20, 21),
+            Err(Error::InvalidSize(21))
+        );
+        assert_eq!(
+            AuditChallenge::try_new(b"seed", 20, 20).map(|c| c.k()),
+            Ok(20)
+        );
+    }
+
+    /// A 4 byte hash, too short to draw a position from one digest.
+    #[derive(Clone, Default)]
+    struct Short(Sha256);
+
+    impl digest::Update for Short {
+        fn update(&mut self, data: impl AsRef<[u8]>) {
+            Digest::update(&mut self.0, data);
+        }
+    }
+
+    impl digest::FixedOutputDirty for Short {
+        type OutputSize = digest::consts::U4;
+
+        fn finalize_into_dirty(&mut self, out: &mut digest::Output<Short>) {
+            out.copy_from_slice(&self.0.finalize_reset()[..4]);
+        }
+    }
+
+    impl digest::Reset for Short {
+        fn reset(&mut self) {
+            Digest::reset(&mut self.0);
+        }
+    }
+
+    #[test]
+    fn short_hashes_draw_positions() {
+        let challenge = AuditChallenge::new(b"seed", 20, 20);
+        let mut all = challenge.positions::<Short>();
+        all.sort_unstable();
+        assert_eq!(all, (0..20).collect::<Vec<_>>());
+        assert_ne!(
+            challenge.positions::<Short>(),
+            AuditChallenge::new(b"other", 20, 20).positions::<Short>()
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! Deduplicating directory backups kept in a content-addressed store.
+//!
+//! Every file is split into fixed-size chunks, the leaves of a tree whose
+//! root identifies the file, and the files of a directory are the leaves of
+//! a tree whose root identifies the snapshot. Blobs are stored under the hash
+//! that verifies them: a chunk under its leaf hash, the leaf hashes of a file
+//! under its root, and the file list under the snapshot root. Chunks already
+//! in the store from earlier snapshots are not written again, and a restore
+//! from a root checks everything it reads against it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+use crate::{hash_leaf, hashes_equal, Error, Hash, MerkleTree};
+
+/// Blobs addressed by the hash that verifies them.
+pub trait ContentStore {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    fn put(&mut self, key: &[u8], blob: &[u8]) -> io::Result<()>;
+
+    fn contains(&self, key: &[u8]) -> io::Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+}
+
+impl ContentStore for HashMap<Hash, Vec<u8>> {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(HashMap::get(self, key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], blob: &[u8]) -> io::Result<()> {
+        self.insert(key.to_vec(), blob.to_vec());
+        Ok(())
+    }
+
+    fn contains(&self, key: &[u8]) -> io::Result<bool> {
+        Ok(self.contains_key(key))
+    }
+}
+
+/// Keeps every blob in a file of a directory, named by the key in hex.
+#[derive(Clone, Debug)]
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    /// Uses `dir`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<DirStore> {
+        fs::create_dir_all(&dir)?;
+        Ok(DirStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, key: &[u8]) -> PathBuf {
+        let name: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(name)
+    }
+}
+
+impl ContentStore for DirStore {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(blob) => Ok(Some(blob)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&mut self, key: &[u8], blob: &[u8]) -> io::Result<()> {
+        // Written aside and renamed, so a crash never leaves a partial blob
+        // under a key that claims to verify it.
+        let path = self.path(key);
+        let partial = path.with_extension("partial");
+        fs::write(&partial, blob)?;
+        fs::rename(partial, path)
+    }
+
+    fn contains(&self, key: &[u8]) -> io::Result<bool> {
+        Ok(self.path(key).is_file())
+    }
+}
+
+/// What `backup` did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// Root of the snapshot, the key to restore it from.
+    pub root: Hash,
+    pub count_files: usize,
+    pub count_chunks: usize,
+    /// Number of chunks not in the store before, the others being shared
+    /// with earlier snapshots or other files.
+    pub new_chunks: usize,
+    /// Total length of the new chunks.
+    pub new_bytes: u64,
+}
+
+/// A file of a snapshot.
+struct Entry {
+    name: String,
+    len: u64,
+    root: Hash,
+}
+
+fn invalid_data(e: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Lists the regular files below `dir` as `/`-separated relative names,
+/// sorted. Symbolic links are not followed.
+fn scan(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if kind.is_dir() {
+            scan(&entry.path(), &format!("{}/", name), files)?;
+        } else if kind.is_file() {
+            files.push((name, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+/// Encodes the leaf of the snapshot tree for `entry`.
+fn entry_leaf(entry: &Entry) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(entry.name.as_bytes());
+    w.u64(entry.len);
+    w.bytes(&entry.root);
+    w.into_bytes()
+}
+
+fn encode_hashes(hashes: &[Hash]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.usize(hashes.len());
+    for hash in hashes {
+        w.bytes(hash);
+    }
+    w.into_bytes()
+}
+
+fn decode_hashes(bytes: &[u8]) -> Result<Vec<Hash>, Error> {
+    let mut r = Reader::new(bytes);
+    let len = r.usize()?;
+    let mut hashes = Vec::new();
+    for _ in 0..len {
+        hashes.push(r.bytes()?.to_vec());
+    }
+    r.finish()?;
+    Ok(hashes)
+}
+
+/// Stores a snapshot of the files below `dir`, split into chunks of
+/// `chunk_size` bytes, and returns its root. Empty files are a single empty
+/// chunk; a directory without files cannot be snapshotted, nor can files be
+/// split into chunks of zero bytes.
+pub fn backup<H, S>(dir: &Path, store: &mut S, chunk_size: usize) -> io::Result<Report>
+where
+    H: Digest,
+    S: ContentStore,
+{
+    if chunk_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "chunk size must be positive",
+        ));
+    }
+
+    let mut files = Vec::new();
+    scan(dir, "", &mut files)?;
+    files.sort();
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "directory has no files",
+        ));
+    }
+
+    let mut hasher = H::new();
+    let mut report = Report {
+        root: Vec::new(),
+        count_files: files.len(),
+        count_chunks: 0,
+        new_chunks: 0,
+        new_bytes: 0,
+    };
+    let mut entries = Vec::with_capacity(files.len());
+    for (name, path) in files {
+        let content = fs::read(path)?;
+        let chunks: Vec<&[u8]> = if content.is_empty() {
+            vec![&[]]
+        } else {
+            content.chunks(chunk_size).collect()
+        };
+
+        let mut leaves = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let leaf = hash_leaf(&chunk.to_vec(), &mut hasher);
+            if !store.contains(&leaf)? {
+                store.put(&leaf, chunk)?;
+                report.new_chunks += 1;
+                report.new_bytes += chunk.len() as u64;
+            }
+            leaves.push(leaf);
+        }
+        report.count_chunks += leaves.len();
+
+        let blob = encode_hashes(&leaves);
+        let root = MerkleTree::build_from_leaves(leaves, H::new())
+            .root_hash()
+            .clone();
+        store.put(&root, &blob)?;
+        entries.push(Entry {
+            name,
+            len: content.len() as u64,
+            root,
+        });
+    }
+
+    let leaves: Vec<Vec<u8>> = entries.iter().map(entry_leaf).collect();
+    let root = MerkleTree::build_with_hasher(&leaves, H::new())
+        .root_hash()
+        .clone();
+    let mut w = Writer::default();
+    w.usize(leaves.len());
+    for leaf in &leaves {
+        w.bytes(leaf);
+    }
+    store.put(&root, &w.into_bytes())?;
+
+    report.root = root;
+    Ok(report)
+}
+
+/// Reads the blob under `key`, failing if it is missing.
+fn fetch<S: ContentStore>(store: &S, key: &[u8]) -> io::Result<Vec<u8>> {
+    store
+        .get(key)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "blob missing from the store"))
+}
+
+/// Reads the file list of the snapshot with `root`, checked against it.
+fn read_entries<H, S>(store: &S, root: &[u8]) -> io::Result<Vec<Entry>>
+where
+    H: Digest,
+    S: ContentStore,
+{
+    let blob = fetch(store, root)?;
+    let mut r = Reader::new(&blob);
+    let len = r.usize().map_err(invalid_data)?;
+    let mut leaves = Vec::new();
+    for _ in 0..len {
+        leaves.push(r.bytes().map_err(invalid_data)?.to_vec());
+    }
+    r.finish().map_err(invalid_data)?;
+    if leaves.is_empty() {
+        return Err(invalid_data(Error::InvalidEncoding));
+    }
+
+    let tree = MerkleTree::build_with_hasher(&leaves, H::new());
+    if !hashes_equal(tree.root_hash(), root) {
+        return Err(invalid_data(Error::RootMismatch {
+            expected: root.to_vec(),
+            actual: tree.root_hash().clone(),
+        }));
+    }
+
+    leaves
+        .iter()
+        .map(|leaf| {
+            let mut r = Reader::new(leaf);
+            let name =
+                String::from_utf8(r.bytes()?.to_vec()).map_err(|_| Error::InvalidEncoding)?;
+            let len = r.u64()?;
+            let root = r.bytes()?.to_vec();
+            r.finish()?;
+            Ok(Entry { name, len, root })
+        })
+        .collect::<Result<_, Error>>()
+        .map_err(invalid_data)
+}
+
+/// Returns where the file `name` of a snapshot goes below `dir`, refusing
+/// names that would land outside of it.
+fn target(dir: &Path, name: &str) -> io::Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(invalid_data(Error::InvalidEncoding));
+    }
+    Ok(dir.join(relative))
+}
+
+/// Writes the files of the snapshot with `root` below `dir`, checking every
+/// blob read from `store` against the root first.
+///
+/// Fails with an `io::ErrorKind::InvalidData` error wrapping
+/// `Error::RootMismatch` or `Error::InvalidChunk` if the store returns
+/// anything the root does not vouch for. Files are only written once all
+/// their chunks verified.
+pub fn restore<H, S>(store: &S, root: &[u8], dir: &Path) -> io::Result<()>
+where
+    H: Digest,
+    S: ContentStore,
+{
+    let mut hasher = H::new();
+    for entry in read_entries::<H, S>(store, root)? {
+        let path = target(dir, &entry.name)?;
+        let leaves = decode_hashes(&fetch(store, &entry.root)?).map_err(invalid_data)?;
+        if leaves.is_empty() {
+            return Err(invalid_data(Error::InvalidEncoding));
+        }
+        let tree = MerkleTree::build_from_leaves(leaves, H::new());
+        if !hashes_equal(tree.root_hash(), &entry.root) {
+            return Err(invalid_data(Error::RootMismatch {
+                expected: entry.root.clone(),
+                actual: tree.root_hash().clone(),
+            }));
+        }
+
+        let mut content = Vec::new();
+        for (position, leaf) in tree.leaves().enumerate() {
+            let chunk = fetch(store, leaf)?;
+            if !hashes_equal(&hash_leaf(&chunk, &mut hasher), leaf) {
+                return Err(invalid_data(Error::InvalidChunk(position)));
+            }
+            content.extend_from_slice(&chunk);
+        }
+        if content.len() as u64 != entry.len {
+            return Err(invalid_data(Error::InvalidEncoding));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    /// A directory of its own below the temporary directory, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir =
+                std::env::temp_dir().join(format!("merkle-backup-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        fn write(&self, name: &str, content: &[u8]) {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn source(name: &str) -> Scratch {
+        let scratch = Scratch::new(name);
+        scratch.write("a.txt", b"abcdefgh");
+        scratch.write("sub/b.txt", b"abcdxy");
+        scratch.write("sub/empty", b"");
+        scratch
+    }
+
+    fn leaf_of(chunk: &[u8]) -> Hash {
+        hash_leaf(&chunk.to_vec(), &mut Sha256::default())
+    }
+
+    #[test]
+    fn backup_restores_and_deduplicates() {
+        let src = source("restore-src");
+        let mut store: HashMap<Hash, Vec<u8>> = HashMap::new();
+
+        let report = backup::<Sha256, _>(&src.0, &mut store, 4).unwrap();
+        assert_eq!(
+            (report.count_files, report.count_chunks, report.new_chunks),
+            (3, 5, 4)
+        );
+        assert_eq!(report.new_bytes, 10);
+        let again = backup::<Sha256, _>(&src.0, &mut store, 4).unwrap();
+        assert_eq!((again.root, again.new_chunks), (report.root.clone(), 0));
+
+        let dst = Scratch::new("restore-dst");
+        restore::<Sha256, _>(&store, &report.root, &dst.0).unwrap();
+        assert_eq!(fs::read(dst.0.join("a.txt")).unwrap(), b"abcdefgh");
+        assert_eq!(fs::read(dst.0.join("sub/b.txt")).unwrap(), b"abcdxy");
+        assert_eq!(fs::read(dst.0.join("sub/empty")).unwrap(), b"");
+    }
+
+    #[test]
+    fn dir_store_round_trips() {
+        let src = source("dir-src");
+        let blobs = Scratch::new("dir-blobs");
+        let mut store = DirStore::open(blobs.0.join("store")).unwrap();
+        let report = backup::<Sha256, _>(&src.0, &mut store, 4).unwrap();
+        assert!(store.contains(&report.root).unwrap());
+        assert_eq!(store.get(b"missing").unwrap(), None);
+
+        let dst = Scratch::new("dir-dst");
+        restore::<Sha256, _>(&store, &report.root, &dst.0).unwrap();
+        assert_eq!(fs::read(dst.0.join("sub/b.txt")).unwrap(), b"abcdxy");
+    }
+
+    #[test]
+    fn tampered_store_fails_to_restore() {
+        let src = source("tamper-src");
+        let mut store: HashMap<Hash, Vec<u8>> = HashMap::new();
+        let report = backup::<Sha256, _>(&src.0, &mut store, 4).unwrap();
+
+        let leaf = leaf_of(b"efgh");
+        store.insert(leaf, b"efgX".to_vec());
+        let dst = Scratch::new("tamper-dst");
+        let e = restore::<Sha256, _>(&store, &report.root, &dst.0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(!dst.0.join("a.txt").exists());
+
+        let e = restore::<Sha256, _>(&store, &[0; 32], &dst.0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn names_outside_the_directory_are_refused() {
+        let dir = Path::new("/restore");
+        assert_eq!(target(dir, "a/b").unwrap(), dir.join("a/b"));
+        for name in ["", "../a", "/etc/passwd", "a/../../b"] {
+            assert!(target(dir, name).is_err(), "{}", name);
+        }
+    }
+    #[test]
+    fn zero_chunk_size_is_invalid_input() {
+        let src = source("zero-chunk-src");
+        let mut store: HashMap<Hash, Vec<u8>> = HashMap::new();
+        let error = backup::<Sha256, _>(&src.0, &mut store, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Standard base64 with padding, as RFC 4648 describes it, the encoding of
+//! checkpoint notes and of the `merkle` tool's base64 output.
+
+use crate::Error;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` with the standard alphabet and padding.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Decodes what `encode` writes. Fails with `Error::InvalidEncoding` on
+/// anything else, including whitespace and missing padding.
+pub fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for (k, chunk) in s.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && k + 1 < s.len() / 4) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let mut n = 0u32;
+        for (i, &c) in chunk[..4 - padding].iter().enumerate() {
+            let v = ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or(Error::InvalidEncoding)?;
+            n |= (v as u32) << (18 - 6 * i);
+        }
+        for i in 0..3 - padding {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for &(plain, encoded) in &vectors {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded), Ok(plain.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for s in &["Zg", "Zg=", "Z===", "Zg==Zm8=", " Zg==", "Zg-="] {
+            assert_eq!(decode(s), Err(Error::InvalidEncoding), "{:?}", s);
+        }
+    }
+}
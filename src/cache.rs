@@ -0,0 +1,200 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use digest::Digest;
+
+use crate::{Error, Hash, MerkleTree, Proof, Snapshot};
+
+/// Counters of a `ProofCache`, as reported by `ProofCache::metrics`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    /// Number of proofs returned from the cache.
+    pub hits: u64,
+    /// Number of proofs that had to be generated.
+    pub misses: u64,
+    /// Number of times the cache was emptied because the root changed.
+    pub invalidations: u64,
+    /// Number of proofs currently held.
+    pub len: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    root: Hash,
+    proofs: HashMap<usize, Proof>,
+    /// Cached positions, oldest first.
+    order: VecDeque<usize>,
+    metrics: CacheMetrics,
+}
+
+/// Keeps the proofs of recently requested leaves, for servers asked for the
+/// same few leaves over and over.
+///
+/// Proofs are keyed by the root they lead to and the position of their leaf.
+/// The cache holds proofs for one root at a time: the first request against
+/// a tree whose root moved empties it, so a mutated tree never gets a stale
+/// proof. The cache can be shared between threads; to drop proofs as soon as
+/// the tree changes rather than on the next request, call `clear` from a hook
+/// registered with `MerkleTree::on_root_change`.
+#[derive(Debug)]
+pub struct ProofCache {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+impl ProofCache {
+    /// Creates a cache holding up to `capacity` proofs, evicting the oldest
+    /// one first.
+    pub fn new(capacity: usize) -> ProofCache {
+        ProofCache::try_new(capacity).expect("capacity must be positive")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `capacity` is zero.
+    pub fn try_new(capacity: usize) -> Result<ProofCache, Error> {
+        if capacity == 0 {
+            return Err(Error::InvalidSize(capacity));
+        }
+
+        Ok(ProofCache {
+            capacity,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        let state = self.state.lock().unwrap();
+        CacheMetrics {
+            len: state.proofs.len(),
+            ..state.metrics
+        }
+    }
+
+    /// Drops every cached proof. Counters are kept.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.proofs.clear();
+        state.order.clear();
+    }
+
+    /// Like `MerkleTree::try_gen_proof`, but returns the cached proof if the
+    /// leaf at `position` was requested before under the same root.
+    pub fn gen_proof<H: Digest>(
+        &self,
+        tree: &MerkleTree<H>,
+        position: usize,
+    ) -> Result<Proof, Error> {
+        tree.check_clean()?;
+        self.get_or_insert(tree.root_hash(), position, || tree.try_gen_proof(position))
+    }
+
+    /// Like `gen_proof`, for a snapshot of a tree.
+    pub fn gen_snapshot_proof(&self, snapshot: &Snapshot, position: usize) -> Result<Proof, Error> {
+        self.get_or_insert(snapshot.root_hash(), position, || {
+            snapshot.try_gen_proof(position)
+        })
+    }
+
+    fn get_or_insert<F>(&self, root: &[u8], position: usize, generate: F) -> Result<Proof, Error>
+    where
+        F: FnOnce() -> Result<Proof, Error>,
+    {
+        let mut state = self.state.lock().unwrap();
+        if state.root != root {
+            if !state.proofs.is_empty() {
+                state.metrics.invalidations += 1;
+            }
+            state.root = root.to_vec();
+            state.proofs.clear();
+            state.order.clear();
+        }
+
+        if let Some(proof) = state.proofs.get(&position) {
+            let proof = proof.clone();
+            state.metrics.hits += 1;
+            return Ok(proof);
+        }
+
+        // Generating under the lock keeps concurrent requests for one leaf
+        // from all missing; proofs are cheap next to the round trip they save.
+        let proof = generate()?;
+        state.metrics.misses += 1;
+        if state.order.len() == self.capacity {
+            if let Some(oldest) = state.order.pop_front() {
+                state.proofs.remove(&oldest);
+            }
+        }
+        state.order.push_back(position);
+        state.proofs.insert(position, proof.clone());
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c", "d"], Sha256::default())
+    }
+
+    #[test]
+    fn repeated_requests_hit() {
+        let tree = tree();
+        let cache = ProofCache::new(2);
+        assert_eq!(cache.gen_proof(&tree, 1), Ok(tree.gen_proof(1)));
+        assert_eq!(cache.gen_proof(&tree, 1), Ok(tree.gen_proof(1)));
+        assert_eq!(
+            cache.gen_snapshot_proof(&tree.snapshot(), 1),
+            Ok(tree.gen_proof(1))
+        );
+        assert_eq!(
+            cache.metrics(),
+            CacheMetrics {
+                hits: 2,
+                misses: 1,
+                invalidations: 0,
+                len: 1
+            }
+        );
+    }
+
+    #[test]
+    fn zero_capacity_is_refused() {
+        assert!(matches!(ProofCache::try_new(0), Err(Error::InvalidSize(0))));
+        assert_eq!(ProofCache::try_new(1).map(|c| c.capacity()).ok(), Some(1));
+    }
+
+    #[test]
+    fn oldest_proof_is_evicted() {
+        let tree = tree();
+        let cache = ProofCache::new(2);
+        for position in [0, 1, 2, 1, 0] {
+            cache.gen_proof(&tree, position).unwrap();
+        }
+        let metrics = cache.metrics();
+        assert_eq!((metrics.hits, metrics.misses, metrics.len), (1, 4, 2));
+    }
+
+    #[test]
+    fn moved_root_invalidates() {
+        let mut tree = tree();
+        let cache = ProofCache::new(4);
+        cache.gen_proof(&tree, 0).unwrap();
+        tree.push(&"e");
+
+        assert_eq!(cache.gen_proof(&tree, 0), Ok(tree.gen_proof(0)));
+        let metrics = cache.metrics();
+        assert_eq!((metrics.misses, metrics.invalidations), (2, 1));
+
+        cache.clear();
+        assert_eq!(cache.metrics().len, 0);
+        assert_eq!(cache.gen_proof(&tree, 5), Err(Error::InvalidPosition(5)));
+    }
+}
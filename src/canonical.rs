@@ -0,0 +1,373 @@
+use crate::{AsBytes, Error};
+
+/// Rewrites leaf values into a canonical form before they are hashed, so
+/// values that mean the same, such as JSON documents differing only in
+/// whitespace or key order, always get the same leaf hash.
+///
+/// Trees and verifiers must agree on the canonicalizer: wrap values in
+/// `Canonical` both when building the tree and when validating proofs.
+pub trait Canonicalize {
+    /// Returns the canonical form of `value`, or `Error::InvalidEncoding` if
+    /// it is not something this canonicalizer accepts.
+    fn canonicalize(&self, value: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> Canonicalize for F
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>, Error>,
+{
+    fn canonicalize(&self, value: &[u8]) -> Result<Vec<u8>, Error> {
+        self(value)
+    }
+}
+
+/// A leaf value in canonical form, hashed like any other value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Canonical(Vec<u8>);
+
+impl Canonical {
+    pub fn new<C, T>(canonicalizer: &C, value: &T) -> Result<Canonical, Error>
+    where
+        C: Canonicalize + ?Sized,
+        T: AsBytes,
+    {
+        canonicalizer.canonicalize(value.as_bytes()).map(Canonical)
+    }
+
+    /// Canonicalizes every value, failing on the first one rejected.
+    pub fn all<C, T>(canonicalizer: &C, values: &[T]) -> Result<Vec<Canonical>, Error>
+    where
+        C: Canonicalize + ?Sized,
+        T: AsBytes,
+    {
+        values
+            .iter()
+            .map(|value| Canonical::new(canonicalizer, value))
+            .collect()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl AsBytes for Canonical {
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Canonicalizes JSON documents with the JSON Canonicalization Scheme of
+/// RFC 8785: no insignificant whitespace, object members sorted by the
+/// UTF-16 code units of their names and numbers written the way ECMAScript
+/// prints doubles.
+///
+/// Input that is not UTF-8 JSON, holds a number outside the range of a
+/// double or an object naming a member twice is rejected.
+#[cfg(feature = "serde_json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+#[cfg(feature = "serde_json")]
+impl Canonicalize for Json {
+    fn canonicalize(&self, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let json::Strict(value) =
+            serde_json::from_slice(value).map_err(|_| Error::InvalidEncoding)?;
+        let mut out = String::new();
+        json::write_value(&value, &mut out)?;
+        Ok(out.into_bytes())
+    }
+}
+
+#[cfg(feature = "serde_json")]
+mod json {
+    use std::fmt::{self, Write};
+
+    use serde::de::{Deserialize, Deserializer, Error as _, MapAccess, SeqAccess, Visitor};
+    use serde_json::{Map, Number, Value};
+
+    use crate::Error;
+
+    /// A JSON value parsed like `serde_json::Value` does, except that an
+    /// object naming a member twice fails instead of keeping the last one.
+    pub(super) struct Strict(pub(super) Value);
+
+    impl<'de> Deserialize<'de> for Strict {
+        fn deserialize<D>(deserializer: D) -> Result<Strict, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(StrictVisitor).map(Strict)
+        }
+    }
+
+    struct StrictVisitor;
+
+    impl<'de> Visitor<'de> for StrictVisitor {
+        type Value = Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a JSON value")
+        }
+
+        fn visit_unit<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+            Ok(Value::Bool(b))
+        }
+
+        fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_f64<E>(self, n: f64) -> Result<Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Number::from_f64(n)
+                .map(Value::Number)
+                .ok_or_else(|| E::custom("number is not finite"))
+        }
+
+        fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+            Ok(Value::String(s.to_string()))
+        }
+
+        fn visit_string<E>(self, s: String) -> Result<Value, E> {
+            Ok(Value::String(s))
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::new();
+            while let Some(Strict(value)) = seq.next_element()? {
+                values.push(value);
+            }
+            Ok(Value::Array(values))
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut members = Map::new();
+            while let Some(name) = map.next_key::<String>()? {
+                if members.contains_key(&name) {
+                    return Err(A::Error::custom("duplicate member name"));
+                }
+                let Strict(value) = map.next_value()?;
+                members.insert(name, value);
+            }
+            Ok(Value::Object(members))
+        }
+    }
+
+    pub(super) fn write_value(value: &Value, out: &mut String) -> Result<(), Error> {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => {
+                let n = n.as_f64().ok_or(Error::InvalidEncoding)?;
+                if !n.is_finite() {
+                    return Err(Error::InvalidEncoding);
+                }
+                write_number(n, out);
+            }
+            Value::String(s) => write_string(s, out),
+            Value::Array(values) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_value(value, out)?;
+                }
+                out.push(']');
+            }
+            Value::Object(members) => {
+                let mut members: Vec<_> = members.iter().collect();
+                members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+                out.push('{');
+                for (i, (name, value)) in members.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_string(name, out);
+                    out.push(':');
+                    write_value(value, out)?;
+                }
+                out.push('}');
+            }
+        }
+        Ok(())
+    }
+
+    /// Escapes only what JSON requires, using the short escapes where
+    /// there is one.
+    fn write_string(s: &str, out: &mut String) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\u{8}' => out.push_str("\\b"),
+                '\u{c}' => out.push_str("\\f"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+
+    /// Writes a finite double as ECMAScript's `Number.prototype.toString`
+    /// does, from the shortest digits that round-trip.
+    fn write_number(n: f64, out: &mut String) {
+        if n == 0.0 {
+            out.push('0');
+            return;
+        }
+        if n < 0.0 {
+            out.push('-');
+        }
+
+        // `{:e}` gives the shortest round-tripping digits as `d.ddde<exp>`.
+        let scientific = format!("{:e}", n.abs());
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+        let k = digits.len() as i32;
+        // Position of the decimal point relative to the first digit.
+        let point = exponent.parse::<i32>().unwrap() + 1;
+
+        if k <= point && point <= 21 {
+            out.push_str(&digits);
+            out.extend((k..point).map(|_| '0'));
+        } else if 0 < point && point <= 21 {
+            out.push_str(&digits[..point as usize]);
+            out.push('.');
+            out.push_str(&digits[point as usize..]);
+        } else if -6 < point && point <= 0 {
+            out.push_str("0.");
+            out.extend((point..0).map(|_| '0'));
+            out.push_str(&digits);
+        } else {
+            out.push_str(&digits[..1]);
+            if k > 1 {
+                out.push('.');
+                out.push_str(&digits[1..]);
+            }
+            let exponent = point - 1;
+            write!(
+                out,
+                "e{}{}",
+                if exponent < 0 { '-' } else { '+' },
+                exponent.abs()
+            )
+            .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::MerkleTree;
+
+    fn upper(value: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(value.to_ascii_uppercase())
+    }
+
+    #[test]
+    fn closures_canonicalize() {
+        let values = Canonical::all(&upper, &["a", "B"]).unwrap();
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        let expected = MerkleTree::build_with_hasher(&["A", "B"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+
+        let reject = |_: &[u8]| Err(Error::InvalidEncoding);
+        assert_eq!(Canonical::all(&reject, &["a"]), Err(Error::InvalidEncoding));
+    }
+
+    #[cfg(feature = "serde_json")]
+    fn jcs(json: &str) -> String {
+        String::from_utf8(Json.canonicalize(json.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_drops_whitespace_and_sorts_members() {
+        assert_eq!(
+            jcs(r#"{ "b": [1, true, null], "a": { "d": "x", "c": "y" } }"#),
+            r#"{"a":{"c":"y","d":"x"},"b":[1,true,null]}"#
+        );
+        // Sorted by UTF-16 code units, which puts the surrogate pair of
+        // U+1F600 before U+FB33.
+        assert_eq!(
+            jcs("{\"\u{fb33}\":1,\"\u{1f600}\":2,\"\u{20ac}\":3,\"\u{80}\":4}"),
+            "{\"\u{80}\":4,\"\u{20ac}\":3,\"\u{1f600}\":2,\"\u{fb33}\":1}"
+        );
+        assert_eq!(jcs(r#""\u0001\n\"\\é/""#), "\"\\u0001\\n\\\"\\\\\u{e9}/\"");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_numbers_print_like_ecmascript() {
+        for (input, expected) in [
+            ("0", "0"),
+            ("-0.0", "0"),
+            ("1.0", "1"),
+            ("-4.5", "-4.5"),
+            ("0.000001", "0.000001"),
+            ("1e-7", "1e-7"),
+            ("123456789012345680000", "123456789012345680000"),
+            ("1e21", "1e+21"),
+            ("333333333.33333329", "333333333.3333333"),
+            ("5e-324", "5e-324"),
+            ("1.7976931348623157e308", "1.7976931348623157e+308"),
+        ] {
+            assert_eq!(jcs(input), expected, "{}", input);
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_rejects_invalid_input() {
+        assert_eq!(Json.canonicalize(b"{"), Err(Error::InvalidEncoding));
+        assert_eq!(Json.canonicalize(b"1e400"), Err(Error::InvalidEncoding));
+        assert_eq!(Json.canonicalize(b"\xff"), Err(Error::InvalidEncoding));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_rejects_duplicate_member_names() {
+        assert_eq!(
+            Json.canonicalize(br#"{"a":1,"a":2}"#),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            Json.canonicalize(br#"[{"b":{"a":1,"c":2,"a":1}}]"#),
+            Err(Error::InvalidEncoding)
+        );
+        // Names are compared after unescaping.
+        assert_eq!(
+            Json.canonicalize(br#"{"a":1,"\u0061":2}"#),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            jcs(r#"{"a":{"a":1},"b":{"a":2}}"#),
+            r#"{"a":{"a":1},"b":{"a":2}}"#
+        );
+    }
+}
@@ -0,0 +1,542 @@
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::{base64, Error, Hash, RootSigner, RootVerifier};
+
+/// Starts every signature line of a note.
+const SIGNATURE_PREFIX: &str = "\u{2014} ";
+
+/// Signature type byte of keys signing cosignatures, as opposed to the
+/// plain note signatures of the log itself.
+const COSIGNATURE_TYPE: u8 = 0x04;
+
+/// What a log commits to at one point: its name, size and root, in the
+/// checkpoint format transparency-log witnesses exchange.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    origin: String,
+    count_leaves: usize,
+    root: Hash,
+    extensions: Vec<String>,
+}
+
+impl Checkpoint {
+    /// `origin` names the log, conventionally by a URL without scheme.
+    pub fn new(origin: &str, count_leaves: usize, root: &[u8]) -> Checkpoint {
+        Checkpoint::try_new(origin, count_leaves, root)
+            .expect("origin must be a single non-empty line and root must not be empty")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidEncoding` instead of
+    /// panicking if `origin` is not a single non-empty line or `root` is
+    /// empty.
+    pub fn try_new(origin: &str, count_leaves: usize, root: &[u8]) -> Result<Checkpoint, Error> {
+        if !is_line(origin) || root.is_empty() {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(Checkpoint {
+            origin: origin.to_string(),
+            count_leaves,
+            root: root.to_vec(),
+            extensions: Vec::new(),
+        })
+    }
+
+    /// Adds an extension line after the root, for data the log wants
+    /// witnesses to sign along.
+    pub fn with_extension(self, line: &str) -> Checkpoint {
+        self.try_with_extension(line)
+            .expect("extension must be a single non-empty line")
+    }
+
+    /// Like `with_extension`, but fails with `Error::InvalidEncoding`
+    /// instead of panicking if `line` is not a single non-empty line.
+    pub fn try_with_extension(mut self, line: &str) -> Result<Checkpoint, Error> {
+        if !is_line(line) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        self.extensions.push(line.to_string());
+        Ok(self)
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Returns the signed text: the origin, the size in decimal and the root
+    /// in base64 on lines of their own, then any extension lines.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "{}", self.origin).unwrap();
+        writeln!(text, "{}", self.count_leaves).unwrap();
+        writeln!(text, "{}", base64::encode(&self.root)).unwrap();
+        for line in &self.extensions {
+            writeln!(text, "{}", line).unwrap();
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Checkpoint, Error> {
+        let lines: Vec<&str> = text
+            .strip_suffix('\n')
+            .ok_or(Error::InvalidEncoding)?
+            .split('\n')
+            .collect();
+        if lines.len() < 3 || lines.iter().any(|line| line.is_empty()) {
+            return Err(Error::InvalidEncoding);
+        }
+        let digits = lines[1];
+        if digits.is_empty()
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+            || (digits.len() > 1 && digits.starts_with('0'))
+        {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(Checkpoint {
+            origin: lines[0].to_string(),
+            count_leaves: digits.parse().map_err(|_| Error::InvalidEncoding)?,
+            root: base64::decode(lines[2])?,
+            extensions: lines[3..].iter().map(|line| line.to_string()).collect(),
+        })
+    }
+}
+
+/// Returns true if `s` fits on one line of a checkpoint.
+fn is_line(s: &str) -> bool {
+    !s.is_empty() && !s.contains('\n')
+}
+
+/// One signature line of a note.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoteSignature {
+    name: String,
+    key_id: [u8; 4],
+    signature: Vec<u8>,
+}
+
+impl NoteSignature {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key_id(&self) -> [u8; 4] {
+        self.key_id
+    }
+
+    /// The signature bytes after the key id. For cosignatures they start with
+    /// the 8 byte big-endian timestamp.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+/// A key that checkpoint signatures are checked against.
+pub struct NoteKey<'a> {
+    name: &'a str,
+    key_id: [u8; 4],
+    cosigns: bool,
+    verifier: &'a dyn RootVerifier,
+}
+
+impl<'a> NoteKey<'a> {
+    /// Returns the id signature lines name a key by: the first 4 bytes of the
+    /// SHA-256 of `name`, a newline, and `key`, the signature type byte
+    /// followed by the public key, such as `0x01` and 32 bytes for Ed25519.
+    pub fn key_id(name: &str, key: &[u8]) -> [u8; 4] {
+        let hash = Sha256::new()
+            .chain(name.as_bytes())
+            .chain(b"\n")
+            .chain(key)
+            .finalize();
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Like `key_id`, for the key of a witness signing cosignatures with an
+    /// Ed25519 `public_key`.
+    pub fn cosignature_key_id(name: &str, public_key: &[u8]) -> [u8; 4] {
+        NoteKey::key_id(name, &[&[COSIGNATURE_TYPE], public_key].concat())
+    }
+
+    /// The key of a log, signing the checkpoint text itself.
+    pub fn log(name: &'a str, key_id: [u8; 4], verifier: &'a dyn RootVerifier) -> NoteKey<'a> {
+        NoteKey {
+            name,
+            key_id,
+            cosigns: false,
+            verifier,
+        }
+    }
+
+    /// The key of a witness, signing timestamped cosignatures.
+    pub fn witness(name: &'a str, key_id: [u8; 4], verifier: &'a dyn RootVerifier) -> NoteKey<'a> {
+        NoteKey {
+            name,
+            key_id,
+            cosigns: true,
+            verifier,
+        }
+    }
+
+    fn verifies(&self, text: &str, signature: &NoteSignature) -> bool {
+        if signature.name != self.name || signature.key_id != self.key_id {
+            return false;
+        }
+        if !self.cosigns {
+            return self
+                .verifier
+                .verify_message(text.as_bytes(), &signature.signature);
+        }
+
+        if signature.signature.len() < 8 {
+            return false;
+        }
+        let (time, rest) = signature.signature.split_at(8);
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(time);
+        let message = cosigned_message(text, u64::from_be_bytes(timestamp));
+        self.verifier.verify_message(message.as_bytes(), rest)
+    }
+}
+
+/// Fails if `name` cannot name a key on a signature line.
+fn check_key_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains(char::is_whitespace) || name.contains('+') {
+        Err(Error::InvalidEncoding)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns what a witness signs to cosign `text` at `timestamp`.
+fn cosigned_message(text: &str, timestamp: u64) -> String {
+    format!("cosignature/v1\ntime {}\n{}", timestamp, text)
+}
+
+/// A checkpoint with the signatures of its log and any witnesses, in the
+/// signed note format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignedCheckpoint {
+    checkpoint: Checkpoint,
+    signatures: Vec<NoteSignature>,
+}
+
+impl SignedCheckpoint {
+    pub fn new(checkpoint: Checkpoint) -> SignedCheckpoint {
+        SignedCheckpoint {
+            checkpoint,
+            signatures: Vec::new(),
+        }
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    pub fn signatures(&self) -> &[NoteSignature] {
+        &self.signatures
+    }
+
+    /// Adds the signature of the key `name` with `key_id` over the
+    /// checkpoint text.
+    pub fn sign<S>(&mut self, name: &str, key_id: [u8; 4], signer: &S)
+    where
+        S: RootSigner + ?Sized,
+    {
+        self.try_sign(name, key_id, signer)
+            .expect("key name must be non-empty without spaces or +")
+    }
+
+    /// Like `sign`, but fails with `Error::InvalidEncoding` instead of
+    /// panicking if `name` is empty or holds spaces or `+`.
+    pub fn try_sign<S>(&mut self, name: &str, key_id: [u8; 4], signer: &S) -> Result<(), Error>
+    where
+        S: RootSigner + ?Sized,
+    {
+        check_key_name(name)?;
+        let signature = signer.sign_message(self.checkpoint.to_text().as_bytes());
+        self.push(name, key_id, signature);
+        Ok(())
+    }
+
+    /// Adds the cosignature of the witness `name` with `key_id`, vouching at
+    /// `timestamp`, in seconds since the Unix epoch, that it saw this
+    /// checkpoint.
+    pub fn cosign<S>(&mut self, name: &str, key_id: [u8; 4], timestamp: u64, signer: &S)
+    where
+        S: RootSigner + ?Sized,
+    {
+        self.try_cosign(name, key_id, timestamp, signer)
+            .expect("key name must be non-empty without spaces or +")
+    }
+
+    /// Like `cosign`, but fails with `Error::InvalidEncoding` instead of
+    /// panicking if `name` is empty or holds spaces or `+`.
+    pub fn try_cosign<S>(
+        &mut self,
+        name: &str,
+        key_id: [u8; 4],
+        timestamp: u64,
+        signer: &S,
+    ) -> Result<(), Error>
+    where
+        S: RootSigner + ?Sized,
+    {
+        check_key_name(name)?;
+        let message = cosigned_message(&self.checkpoint.to_text(), timestamp);
+        let signature = [
+            &timestamp.to_be_bytes()[..],
+            &signer.sign_message(message.as_bytes()),
+        ]
+        .concat();
+        self.push(name, key_id, signature);
+        Ok(())
+    }
+
+    fn push(&mut self, name: &str, key_id: [u8; 4], signature: Vec<u8>) {
+        self.signatures.push(NoteSignature {
+            name: name.to_string(),
+            key_id,
+            signature,
+        });
+    }
+
+    /// Returns true if one of the signatures is a valid one by `key`.
+    pub fn verify(&self, key: &NoteKey) -> bool {
+        let text = self.checkpoint.to_text();
+        self.signatures.iter().any(|s| key.verifies(&text, s))
+    }
+
+    /// Returns how many of `witnesses` signed the checkpoint of `log`.
+    ///
+    /// Keys are told apart by name and key id, so a witness listed twice
+    /// counts once and one sharing the name and id of `log` not at all.
+    pub fn count_witnesses(&self, log: &NoteKey, witnesses: &[NoteKey]) -> usize {
+        let text = self.checkpoint.to_text();
+        let mut counted = vec![(log.name, log.key_id)];
+        for witness in witnesses {
+            let key = (witness.name, witness.key_id);
+            if !counted.contains(&key) && self.signatures.iter().any(|s| witness.verifies(&text, s))
+            {
+                counted.push(key);
+            }
+        }
+        counted.len() - 1
+    }
+
+    /// Returns true if `log` signed the checkpoint and at least `threshold`
+    /// of `witnesses` cosigned it.
+    pub fn verify_witnessed(&self, log: &NoteKey, witnesses: &[NoteKey], threshold: usize) -> bool {
+        self.verify(log) && self.count_witnesses(log, witnesses) >= threshold
+    }
+
+    /// Returns the note: the checkpoint text, a blank line and one line per
+    /// signature.
+    pub fn to_text(&self) -> String {
+        let mut text = self.checkpoint.to_text();
+        text.push('\n');
+        for s in &self.signatures {
+            let blob = [&s.key_id[..], &s.signature].concat();
+            writeln!(
+                text,
+                "{}{} {}",
+                SIGNATURE_PREFIX,
+                s.name,
+                base64::encode(&blob)
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<SignedCheckpoint, Error> {
+        let split = text.find("\n\n").ok_or(Error::InvalidEncoding)?;
+        let checkpoint = Checkpoint::parse(&text[..split + 1])?;
+
+        let mut signatures = Vec::new();
+        let lines = text[split + 2..]
+            .strip_suffix('\n')
+            .ok_or(Error::InvalidEncoding)?;
+        for line in lines.split('\n') {
+            let line = line
+                .strip_prefix(SIGNATURE_PREFIX)
+                .ok_or(Error::InvalidEncoding)?;
+            let (name, blob) = line.split_once(' ').ok_or(Error::InvalidEncoding)?;
+            let blob = base64::decode(blob)?;
+            if name.is_empty() || blob.len() < 5 {
+                return Err(Error::InvalidEncoding);
+            }
+            signatures.push(NoteSignature {
+                name: name.to_string(),
+                key_id: [blob[0], blob[1], blob[2], blob[3]],
+                signature: blob[4..].to_vec(),
+            });
+        }
+
+        Ok(SignedCheckpoint {
+            checkpoint,
+            signatures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stand-in key pair whose signature is the hash of the key byte and
+    /// the message.
+    struct Toy(u8);
+
+    impl RootSigner for Toy {
+        fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+            Sha256::new()
+                .chain([self.0])
+                .chain(message)
+                .finalize()
+                .to_vec()
+        }
+    }
+
+    impl RootVerifier for Toy {
+        fn verify_message(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign_message(message) == signature
+        }
+    }
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint::new("example.com/log", 3, &[0xfb; 32]).with_extension("extra")
+    }
+
+    #[test]
+    fn checkpoint_text_round_trips() {
+        let text = checkpoint().to_text();
+        assert_eq!(
+            text,
+            format!(
+                "example.com/log\n3\n{}\nextra\n",
+                base64::encode(&[0xfb; 32])
+            )
+        );
+        assert_eq!(Checkpoint::parse(&text), Ok(checkpoint()));
+    }
+
+    #[test]
+    fn malformed_checkpoints_are_rejected() {
+        let root = base64::encode(&[0xfb; 32]);
+        for text in [
+            format!("example.com/log\n3\n{}", root),
+            format!("example.com/log\n03\n{}\n", root),
+            format!("example.com/log\n+3\n{}\n", root),
+            format!("example.com/log\n\n{}\n", root),
+            "example.com/log\n3\n".to_string(),
+        ] {
+            assert_eq!(Checkpoint::parse(&text), Err(Error::InvalidEncoding));
+        }
+    }
+
+    #[test]
+    fn signed_checkpoint_round_trips_and_verifies() {
+        let log_id = NoteKey::key_id("log", &[1, 2]);
+        let witness_id = NoteKey::cosignature_key_id("witness", &[3]);
+        let mut signed = SignedCheckpoint::new(checkpoint());
+        signed.sign("log", log_id, &Toy(1));
+        signed.cosign("witness", witness_id, 1_700_000_000, &Toy(2));
+
+        let parsed = SignedCheckpoint::parse(&signed.to_text()).unwrap();
+        assert_eq!(parsed, signed);
+        assert_eq!(
+            &parsed.signatures()[1].signature()[..8],
+            &1_700_000_000u64.to_be_bytes()
+        );
+
+        let log = NoteKey::log("log", log_id, &Toy(1));
+        let witness = NoteKey::witness("witness", witness_id, &Toy(2));
+        assert!(parsed.verify(&log));
+        assert!(parsed.verify_witnessed(&log, &[witness], 1));
+    }
+
+    #[test]
+    fn wrong_keys_do_not_verify() {
+        let log_id = NoteKey::key_id("log", &[1, 2]);
+        let mut signed = SignedCheckpoint::new(checkpoint());
+        signed.sign("log", log_id, &Toy(1));
+
+        assert!(!signed.verify(&NoteKey::log("log", log_id, &Toy(9))));
+        assert!(!signed.verify(&NoteKey::log("other", log_id, &Toy(1))));
+        // A log signature is not a cosignature of the same key.
+        let witness = NoteKey::witness("log", log_id, &Toy(1));
+        assert!(!signed.verify_witnessed(&NoteKey::log("log", log_id, &Toy(1)), &[witness], 1));
+    }
+
+    #[test]
+    fn witnesses_count_once_and_never_as_the_log() {
+        let log_id = NoteKey::key_id("log", &[1, 2]);
+        let witness_id = NoteKey::cosignature_key_id("witness", &[3]);
+        let mut signed = SignedCheckpoint::new(checkpoint());
+        signed.sign("log", log_id, &Toy(1));
+        signed.cosign("witness", witness_id, 1_700_000_000, &Toy(2));
+        signed.cosign("log", log_id, 1_700_000_000, &Toy(1));
+
+        let log = NoteKey::log("log", log_id, &Toy(1));
+        let witness = NoteKey::witness("witness", witness_id, &Toy(2));
+        let again = NoteKey::witness("witness", witness_id, &Toy(2));
+        let as_log = NoteKey::witness("log", log_id, &Toy(1));
+        assert_eq!(signed.count_witnesses(&log, &[witness, again, as_log]), 1);
+
+        let witness = NoteKey::witness("witness", witness_id, &Toy(2));
+        let again = NoteKey::witness("witness", witness_id, &Toy(2));
+        assert!(!signed.verify_witnessed(&log, &[witness, again], 2));
+    }
+
+    #[test]
+    fn try_forms_fail_on_malformed_input() {
+        assert_eq!(
+            Checkpoint::try_new("", 3, &[1]),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            Checkpoint::try_new("a\nb", 3, &[1]),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            Checkpoint::try_new("log", 3, &[]),
+            Err(Error::InvalidEncoding)
+        );
+        for line in ["", "a\nb"] {
+            assert_eq!(
+                checkpoint().try_with_extension(line),
+                Err(Error::InvalidEncoding)
+            );
+        }
+
+        let mut signed = SignedCheckpoint::new(checkpoint());
+        for name in ["", "a b", "a+b"] {
+            assert_eq!(
+                signed.try_sign(name, [0; 4], &Toy(1)),
+                Err(Error::InvalidEncoding)
+            );
+            assert_eq!(
+                signed.try_cosign(name, [0; 4], 0, &Toy(1)),
+                Err(Error::InvalidEncoding)
+            );
+        }
+        assert!(signed.signatures().is_empty());
+        assert_eq!(signed.try_sign("log", [0; 4], &Toy(1)), Ok(()));
+    }
+}
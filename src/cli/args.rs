@@ -0,0 +1,106 @@
+use std::str::FromStr;
+
+use super::Failure;
+
+/// Positional arguments and `--name value` options of a command.
+pub struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Splits `args`, rejecting options not listed in `known`.
+    pub fn parse(args: Vec<String>, known: &[&str]) -> Result<Args, Failure> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    positional.push(arg);
+                    continue;
+                }
+            };
+
+            let (name, value) = match name.find('=') {
+                Some(at) => (name[..at].to_string(), name[at + 1..].to_string()),
+                None => match args.next() {
+                    Some(value) => (name.to_string(), value),
+                    None => return Err(Failure::usage(format!("missing value for --{}", name))),
+                },
+            };
+            if !known.contains(&name.as_str()) {
+                return Err(Failure::usage(format!("unknown option --{}", name)));
+            }
+            options.push((name, value));
+        }
+
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    /// Returns the last value given for option `name`.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the value of option `name` parsed as a `T`.
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>, Failure> {
+        match self.option(name) {
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|_| Failure::usage(format!("invalid value `{}` for --{}", v, name))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::args;
+    use super::*;
+
+    #[test]
+    fn splits_positional_arguments_and_options() {
+        let parsed = Args::parse(
+            args(&["a", "--size", "3", "b", "--size=4", "--name=x=y"]),
+            &["size", "name"],
+        )
+        .unwrap();
+
+        assert_eq!(parsed.positional(), ["a", "b"]);
+        assert_eq!(parsed.option("size"), Some("4"));
+        assert_eq!(parsed.option("name"), Some("x=y"));
+        assert_eq!(parsed.parsed::<usize>("size").unwrap(), Some(4));
+        assert!(parsed.parsed::<usize>("name").is_err());
+        assert_eq!(parsed.option("other"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_options_and_missing_values() {
+        assert_eq!(
+            Args::parse(args(&["--size", "3"]), &[]).err().unwrap().code,
+            2
+        );
+        assert_eq!(
+            Args::parse(args(&["--size"]), &["size"])
+                .err()
+                .unwrap()
+                .code,
+            2
+        );
+    }
+}
@@ -0,0 +1,8 @@
+use super::Failure;
+
+pub use merkle_rs::base64::encode;
+
+/// Decodes a base64 argument, ignoring surrounding whitespace.
+pub fn decode(s: &str) -> Result<Vec<u8>, Failure> {
+    merkle_rs::base64::decode(s.trim()).map_err(|_| Failure::error("not a base64 string"))
+}
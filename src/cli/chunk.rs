@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::Path;
+
+use digest::Digest;
+use serde_json::json;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output::{self, Format};
+use super::scheme::{self, Command, Scheme};
+use super::{hex, note, proof, Failure};
+
+struct Chunk {
+    scheme: Scheme,
+    file: String,
+    size: usize,
+    out: String,
+    format: Format,
+}
+
+/// Parses a byte count such as `4096`, `64KiB` or `1MiB`.
+fn parse_size(s: &str) -> Option<usize> {
+    let units = [
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("B", 1),
+    ];
+    let (digits, unit) = units
+        .iter()
+        .find_map(|&(suffix, unit)| s.strip_suffix(suffix).map(|d| (d, unit)))
+        .unwrap_or((s, 1));
+    digits.trim().parse::<usize>().ok()?.checked_mul(unit)
+}
+
+impl Command for Chunk {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let manifest = Manifest::from_inputs::<H>(
+            &self.scheme,
+            std::slice::from_ref(&self.file),
+            Some(self.size),
+        )?;
+        let tree = manifest.tree::<H>()?;
+        let root = tree.root_hash();
+
+        fs::create_dir_all(&self.out)
+            .map_err(|e| Failure::error(format!("{}: {}", self.out, e)))?;
+        let write = |name: &str, bytes: &[u8]| {
+            let path = Path::new(&self.out).join(name);
+            fs::write(&path, bytes)
+                .map_err(|e| Failure::error(format!("{}: {}", path.display(), e)))
+        };
+
+        for (n, (name, leaf)) in manifest.leaves.iter().enumerate() {
+            let proof = tree.gen_proof(n);
+            let output = self.format.encode(&proof.to_bytes(), || {
+                let mut value = proof::describe(&proof, &self.scheme, root, name, leaf);
+                value["offset"] = json!(n * self.size);
+                value
+            });
+            write(&format!("{:08}.proof", n), &output)?;
+        }
+
+        let output = self.format.encode(root, || {
+            json!({
+                "algorithm": self.scheme.algorithm,
+                "profile": self.scheme.profile,
+                "padding": scheme::padding_name(self.scheme.padding),
+                "count_leaves": tree.count_leaves(),
+                "chunk_size": self.size,
+                "root": hex::encode(root),
+            })
+        });
+        write("root", &output)?;
+
+        note(format_args!(
+            "wrote {} chunk proofs to {}",
+            tree.count_leaves(),
+            self.out
+        ));
+        output::print(&output)
+    }
+}
+
+/// `merkle chunk <file> --size <bytes> --out <dir>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &["algorithm", "profile", "padding", "size", "out", "format"],
+    )?;
+    let file = match args.positional() {
+        [file] => file.clone(),
+        _ => return Err(Failure::usage("give one file to chunk")),
+    };
+    let size = args
+        .option("size")
+        .ok_or_else(|| Failure::usage("missing --size"))?;
+    let size = parse_size(size)
+        .filter(|&s| s > 0)
+        .ok_or_else(|| Failure::usage(format!("invalid value `{}` for --size", size)))?;
+    let out = args
+        .option("out")
+        .ok_or_else(|| Failure::usage("missing --out"))?
+        .to_string();
+    let scheme = Scheme::from_args(&args)?;
+    let format = Format::from_args(&args, Format::Json)?;
+
+    scheme::dispatch(
+        &scheme.clone(),
+        Chunk {
+            scheme,
+            file,
+            size,
+            out,
+            format,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::{MerkleTree, Proof};
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64KiB"), Some(64 << 10));
+        assert_eq!(parse_size("1 MiB"), Some(1 << 20));
+        assert_eq!(parse_size("3B"), Some(3));
+        assert_eq!(parse_size("1kb"), None);
+        assert_eq!(parse_size(&format!("{}GiB", usize::MAX)), None);
+    }
+
+    #[test]
+    fn saves_a_proof_of_every_chunk() {
+        let scratch = Scratch::new("chunk");
+        let file = scratch.write("file", b"abcdefghij");
+        let out = scratch.path("out");
+        run(args(&[
+            &file, "--size", "4B", "--out", &out, "--format", "binary",
+        ]))
+        .unwrap();
+
+        let chunks = ["abcd", "efgh", "ij"];
+        let tree = MerkleTree::build_with_hasher(&chunks, Sha256::default());
+        assert_eq!(&output::decode(&scratch.read("out/root")), tree.root_hash());
+        for (n, chunk) in chunks.iter().enumerate() {
+            let bytes = output::decode(&scratch.read(&format!("out/{:08}.proof", n)));
+            let proof = Proof::from_bytes(&bytes).unwrap();
+            assert!(proof.validate_with_hasher(tree.root_hash(), chunk, Sha256::default()));
+        }
+    }
+
+    #[test]
+    fn rejects_zero_sizes() {
+        let scratch = Scratch::new("chunk-zero");
+        let file = scratch.write("file", b"abc");
+        let out = scratch.path("out");
+        let result = run(args(&[&file, "--size", "0", "--out", &out]));
+        assert_eq!(result.unwrap_err().code, 2);
+    }
+}
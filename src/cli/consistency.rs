@@ -0,0 +1,143 @@
+use digest::Digest;
+use serde_json::json;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output::{self, Format};
+use super::scheme::{self, Command};
+use super::{hex, note, Failure};
+
+struct Consistency {
+    old: Manifest,
+    new: Manifest,
+    args: Args,
+}
+
+impl Command for Consistency {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let (old, new) = (self.old.tree::<H>()?, self.new.tree::<H>()?);
+        if old.count_leaves() > new.count_leaves() {
+            return Err(Failure::invalid(format!(
+                "old snapshot has {} leaves, more than the {} of the new one",
+                old.count_leaves(),
+                new.count_leaves()
+            )));
+        }
+
+        let proof = new.consistency_proof(old.count_leaves());
+        let format = Format::from_args(&self.args, Format::Json)?;
+        let output = format.encode(&proof.to_bytes(), || {
+            json!({
+                "algorithm": self.new.scheme.algorithm,
+                "profile": self.new.scheme.profile,
+                "padding": scheme::padding_name(self.new.scheme.padding),
+                "old_count_leaves": proof.old_count_leaves(),
+                "count_leaves": proof.count_leaves(),
+                "old_root": hex::encode(old.root_hash()),
+                "new_root": hex::encode(new.root_hash()),
+                "hashes": proof.hashes().iter().map(|h| hex::encode(h)).collect::<Vec<_>>(),
+            })
+        });
+        output::emit(&self.args, &output)?;
+
+        if proof.verify_with_padding(old.root_hash(), new.root_hash(), new.padding(), H::new()) {
+            note(format_args!(
+                "ok: the {} leaves of the new snapshot extend the {} of the old one",
+                proof.count_leaves(),
+                proof.old_count_leaves()
+            ));
+            Ok(())
+        } else {
+            Err(Failure::invalid(
+                "new snapshot does not extend the old one, leaves were changed or removed",
+            ))
+        }
+    }
+}
+
+/// `merkle consistency --old <manifest> --new <manifest>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(args, &["old", "new", "format", "out"])?;
+    let read = |name| match args.option(name) {
+        Some(path) => Manifest::read(path),
+        None => Err(Failure::usage(format!("missing --{}", name))),
+    };
+    let (old, new) = (read("old")?, read("new")?);
+    if old.scheme != new.scheme {
+        return Err(Failure::error(format!(
+            "snapshots were made with {} and {}",
+            old.scheme, new.scheme
+        )));
+    }
+
+    let scheme = new.scheme.clone();
+    scheme::dispatch(&scheme, Consistency { old, new, args })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::{ConsistencyProof, MerkleTree, Padding};
+    use sha2::Sha256;
+
+    use super::super::scheme::Scheme;
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    fn save(scratch: &Scratch, name: &str, values: &[&str]) -> String {
+        let manifest = Manifest {
+            scheme: Scheme {
+                algorithm: "sha256".to_string(),
+                profile: "merkle".to_string(),
+                padding: Padding::Duplicate,
+            },
+            leaves: values
+                .iter()
+                .map(|v| {
+                    (
+                        v.to_string(),
+                        merkle_rs::leaf_hash_with_hasher(v, Sha256::default()),
+                    )
+                })
+                .collect(),
+        };
+        let path = scratch.path(name);
+        manifest.write(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn proves_an_appended_tree() {
+        let scratch = Scratch::new("consistency");
+        let old = save(&scratch, "old", &["a", "b", "c"]);
+        let new = save(&scratch, "new", &["a", "b", "c", "d", "e"]);
+        let out = scratch.path("proof");
+
+        run(args(&[
+            "--old", &old, "--new", &new, "--format", "binary", "--out", &out,
+        ]))
+        .unwrap();
+        let proof = ConsistencyProof::from_bytes(&scratch.read("proof")).unwrap();
+        let old_tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let new_tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        assert!(proof.verify_with_hasher(
+            old_tree.root_hash(),
+            new_tree.root_hash(),
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    fn fails_on_changed_or_shrunk_trees() {
+        let scratch = Scratch::new("consistency-invalid");
+        let old = save(&scratch, "old", &["a", "b", "c"]);
+        let changed = save(&scratch, "changed", &["a", "x", "c", "d"]);
+        let shrunk = save(&scratch, "shrunk", &["a", "b"]);
+        let out = scratch.path("proof");
+
+        let result = run(args(&["--old", &old, "--new", &changed, "--out", &out]));
+        assert_eq!(result.unwrap_err().code, 1);
+        let result = run(args(&["--old", &old, "--new", &shrunk, "--out", &out]));
+        assert_eq!(result.unwrap_err().code, 1);
+        assert_eq!(run(args(&["--old", &old])).unwrap_err().code, 2);
+    }
+}
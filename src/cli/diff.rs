@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use digest::Digest;
+use merkle_rs::Hash;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::scheme::{self, Command, Scheme};
+use super::Failure;
+
+struct Diff {
+    scheme: Scheme,
+    old: String,
+    new: String,
+}
+
+/// Hashes the directory at `path`, or reads it as a manifest if it is a file.
+fn load<H: Digest>(scheme: &Scheme, path: &str) -> Result<Manifest, Failure> {
+    if Path::new(path).is_dir() {
+        return Manifest::from_dir::<H>(scheme, path);
+    }
+
+    let manifest = Manifest::read(path)?;
+    if manifest.scheme != *scheme {
+        return Err(Failure::usage(format!(
+            "{} was made with {}",
+            path, manifest.scheme
+        )));
+    }
+    Ok(manifest)
+}
+
+/// Returns whether each position of `old` may differ from the same position
+/// of `new`. Positions in subtrees both trees share hold the same files.
+fn maybe_changed<H: Digest>(old: &Manifest, new: &Manifest) -> Result<Vec<bool>, Failure> {
+    let mut changed = vec![true; old.leaves.len().max(new.leaves.len())];
+    if old.leaves.is_empty() || new.leaves.is_empty() {
+        return Ok(changed);
+    }
+
+    let (old_tree, new_tree) = (old.tree::<H>()?, new.tree::<H>()?);
+    for (c, (a, b)) in changed.iter_mut().zip(old.leaves.iter().zip(&new.leaves)) {
+        *c = a.0 != b.0;
+    }
+    for range in old_tree.diff(&new_tree) {
+        for c in &mut changed[range] {
+            *c = true;
+        }
+    }
+
+    Ok(changed)
+}
+
+impl Command for Diff {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let old = load::<H>(&self.scheme, &self.old)?;
+        let new = load::<H>(&self.scheme, &self.new)?;
+        let changed = maybe_changed::<H>(&old, &new)?;
+
+        // Names are unique, so a file that moved is a candidate on both sides.
+        let candidates = |m: &Manifest| -> BTreeMap<String, Hash> {
+            m.leaves
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| changed[*i])
+                .map(|(_, leaf)| leaf.clone())
+                .collect()
+        };
+        let (old_files, mut new_files) = (candidates(&old), candidates(&new));
+
+        let mut report = BTreeMap::new();
+        for (name, hash) in old_files {
+            match new_files.remove(&name) {
+                Some(new_hash) if new_hash != hash => report.insert(name, 'M'),
+                Some(_) => None,
+                None => report.insert(name, '-'),
+            };
+        }
+        for name in new_files.into_keys() {
+            report.insert(name, '+');
+        }
+
+        for (name, change) in &report {
+            println!("{} {}", change, name);
+        }
+        if report.is_empty() {
+            Ok(())
+        } else {
+            Err(Failure::invalid(format!("{} files differ", report.len())))
+        }
+    }
+}
+
+/// `merkle diff <old> <new>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(args, &["algorithm", "profile", "padding"])?;
+    let (old, new) = match args.positional() {
+        [old, new] => (old.clone(), new.clone()),
+        _ => return Err(Failure::usage("give two directories or manifests")),
+    };
+    let scheme = Scheme::from_args(&args)?;
+
+    scheme::dispatch(&scheme.clone(), Diff { scheme, old, new })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::Padding;
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    fn scheme() -> Scheme {
+        Scheme {
+            algorithm: "sha256".to_string(),
+            profile: "merkle".to_string(),
+            padding: Padding::Duplicate,
+        }
+    }
+
+    #[test]
+    fn counts_the_files_that_differ() {
+        let scratch = Scratch::new("diff");
+        for (name, contents) in [("a", "a"), ("b", "b"), ("c", "c")] {
+            scratch.write(&format!("old/{}", name), contents.as_bytes());
+        }
+        for (name, contents) in [("a", "a"), ("c", "changed"), ("d", "d")] {
+            scratch.write(&format!("new/{}", name), contents.as_bytes());
+        }
+        let (old, new) = (scratch.path("old"), scratch.path("new"));
+
+        let failure = run(args(&[&old, &new])).unwrap_err();
+        assert_eq!(
+            (failure.code, failure.message.as_str()),
+            (1, "3 files differ")
+        );
+        run(args(&[&old, &old])).unwrap();
+    }
+
+    #[test]
+    fn shared_subtrees_are_unchanged() {
+        let scratch = Scratch::new("diff-shared");
+        for name in ["a", "b", "c", "d"] {
+            scratch.write(&format!("old/{}", name), name.as_bytes());
+            scratch.write(&format!("new/{}", name), name.as_bytes());
+        }
+        scratch.write("new/d", b"changed");
+        let old = Manifest::from_dir::<Sha256>(&scheme(), &scratch.path("old")).unwrap();
+        let new = Manifest::from_dir::<Sha256>(&scheme(), &scratch.path("new")).unwrap();
+
+        assert_eq!(
+            maybe_changed::<Sha256>(&old, &new).unwrap(),
+            vec![false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn compares_a_manifest_with_a_directory() {
+        let scratch = Scratch::new("diff-manifest");
+        scratch.write("dir/a", b"a");
+        let manifest = scratch.path("manifest");
+        Manifest::from_dir::<Sha256>(&scheme(), &scratch.path("dir"))
+            .unwrap()
+            .write(&manifest)
+            .unwrap();
+
+        run(args(&[&manifest, &scratch.path("dir")])).unwrap();
+        let failure = run(args(&[
+            &manifest,
+            &scratch.path("dir"),
+            "--algorithm",
+            "sha512",
+        ]));
+        assert_eq!(failure.unwrap_err().code, 2);
+    }
+}
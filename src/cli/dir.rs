@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::Failure;
+
+/// A regular file found below a directory.
+pub struct File {
+    /// Path relative to the directory, with `/` between components.
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+}
+
+impl File {
+    /// Returns true if `other` looks unchanged since this was read.
+    pub fn same_stamp(&self, other: &File) -> bool {
+        self.modified == other.modified && self.len == other.len
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, Failure> {
+        fs::read(&self.path).map_err(|e| Failure::error(format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+/// Lists the files below `dir`, sorted by name. Symbolic links are not
+/// followed.
+pub fn scan(dir: &str) -> Result<Vec<File>, Failure> {
+    let mut files = Vec::new();
+    walk(Path::new(dir), "", &mut files)?;
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+fn walk(dir: &Path, prefix: &str, files: &mut Vec<File>) -> Result<(), Failure> {
+    let error = |e: std::io::Error| Failure::error(format!("{}: {}", dir.display(), e));
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        let metadata = entry.metadata().map_err(error)?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+        if metadata.is_dir() {
+            walk(&entry.path(), &format!("{}/", name), files)?;
+        } else if metadata.is_file() {
+            files.push(File {
+                name,
+                path: entry.path(),
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            });
+        }
+    }
+
+    Ok(())
+}
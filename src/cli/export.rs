@@ -0,0 +1,46 @@
+use digest::Digest;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output;
+use super::scheme::{self, Command};
+use super::snapshot::{self, Encoding};
+use super::Failure;
+
+struct Export {
+    manifest: Manifest,
+    encoding: Encoding,
+    args: Args,
+}
+
+impl Command for Export {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let tree = self.manifest.tree::<H>()?;
+        let output = snapshot::encode(&self.manifest, tree.root_hash(), self.encoding)?;
+        output::emit(&self.args, &output)
+    }
+}
+
+/// `merkle export <manifest> --to <encoding>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(args, &["to", "out"])?;
+    let manifest = match args.positional() {
+        [path] => Manifest::read(path)?,
+        _ => return Err(Failure::usage("give one manifest to export")),
+    };
+    let encoding = match args.option("to") {
+        Some(name) => Encoding::parse(name)
+            .ok_or_else(|| Failure::usage(format!("unknown snapshot encoding `{}`", name)))?,
+        None => return Err(Failure::usage("missing --to")),
+    };
+
+    let scheme = manifest.scheme.clone();
+    scheme::dispatch(
+        &scheme,
+        Export {
+            manifest,
+            encoding,
+            args,
+        },
+    )
+}
@@ -0,0 +1,9 @@
+use super::Failure;
+
+pub use merkle_rs::hex::encode;
+
+/// Decodes a hex argument, ignoring surrounding whitespace.
+pub fn decode(s: &str) -> Result<Vec<u8>, Failure> {
+    let s = s.trim();
+    merkle_rs::hex::decode(s).map_err(|_| Failure::error(format!("`{}` is not a hex string", s)))
+}
@@ -0,0 +1,134 @@
+use std::fs;
+
+use digest::Digest;
+use merkle_rs::Hash;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output;
+use super::scheme::{self, Command, Scheme};
+use super::snapshot::{self, Encoding};
+use super::{hex, note, Failure};
+
+struct Import {
+    manifest: Manifest,
+    root: Option<Hash>,
+    args: Args,
+}
+
+impl Command for Import {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let tree = self.manifest.tree::<H>()?;
+        match &self.root {
+            Some(root) if root != tree.root_hash() => {
+                return Err(Failure::invalid(format!(
+                    "leaves hash to root {}, not to the saved {}",
+                    hex::encode(tree.root_hash()),
+                    hex::encode(root)
+                )))
+            }
+            Some(_) => note("ok: leaves match the saved root"),
+            None => {}
+        }
+
+        output::emit(&self.args, self.manifest.to_text()?.as_bytes())
+    }
+}
+
+/// `merkle import <file> [--from <encoding>]`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(args, &["from", "out", "algorithm", "profile", "padding"])?;
+    let path = match args.positional() {
+        [path] => path,
+        _ => return Err(Failure::usage("give one snapshot to import")),
+    };
+    let bytes = fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+    let encoding = match args.option("from") {
+        Some(name) => Encoding::parse(name)
+            .ok_or_else(|| Failure::usage(format!("unknown snapshot encoding `{}`", name)))?,
+        None => Encoding::detect(&bytes),
+    };
+
+    let (manifest, root) = snapshot::decode(&bytes, path, encoding, &Scheme::from_args(&args)?)?;
+    if encoding != Encoding::Leaves {
+        manifest.scheme.check_args(&args, path)?;
+    }
+
+    let scheme = manifest.scheme.clone();
+    scheme::dispatch(
+        &scheme,
+        Import {
+            manifest,
+            root,
+            args,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::Padding;
+
+    use super::super::export;
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    fn save(scratch: &Scratch) -> String {
+        let files: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|name| scratch.write(name, name.as_bytes()))
+            .collect();
+        let scheme = Scheme {
+            algorithm: "sha256".to_string(),
+            profile: "merkle".to_string(),
+            padding: Padding::Duplicate,
+        };
+        let manifest = Manifest::from_inputs::<sha2::Sha256>(&scheme, &files, None).unwrap();
+        let path = scratch.path("manifest");
+        manifest.write(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn exported_snapshots_import_back() {
+        let scratch = Scratch::new("import");
+        let manifest = save(&scratch);
+        for encoding in ["binary", "json", "leaves", "manifest"] {
+            let exported = scratch.path(encoding);
+            export::run(args(&[&manifest, "--to", encoding, "--out", &exported])).unwrap();
+
+            let imported = scratch.path("imported");
+            run(args(&[&exported, "--out", &imported])).unwrap();
+            let leaves = |path: &str| Manifest::read(path).unwrap().leaves;
+            let hashes = |path: &str| -> Vec<Hash> {
+                leaves(path).into_iter().map(|(_, leaf)| leaf).collect()
+            };
+            assert_eq!(hashes(&imported), hashes(&manifest), "{}", encoding);
+            if encoding != "leaves" {
+                assert_eq!(leaves(&imported), leaves(&manifest), "{}", encoding);
+            }
+        }
+    }
+
+    #[test]
+    fn fails_on_a_root_the_leaves_do_not_hash_to() {
+        let scratch = Scratch::new("import-root");
+        let manifest = save(&scratch);
+        let exported = scratch.path("json");
+        export::run(args(&[&manifest, "--to", "json", "--out", &exported])).unwrap();
+
+        let text = String::from_utf8(scratch.read("json")).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        value["leaves"][0]["hash"] = value["leaves"][1]["hash"].clone();
+        let forged = scratch.write("forged", value.to_string().as_bytes());
+
+        let out = scratch.path("imported");
+        assert_eq!(run(args(&[&forged, "--out", &out])).unwrap_err().code, 1);
+        assert_eq!(
+            run(args(&[&exported, "--algorithm", "sha512", "--out", &out]))
+                .unwrap_err()
+                .code,
+            2
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use super::Failure;
+
+/// Calls `f` with the name and contents of every leaf of `paths` in order.
+///
+/// Each file is one leaf named after its path and `-` stands for the lines of
+/// stdin, one leaf per line. With a `chunk_size`, every input is cut into
+/// leaves of that many bytes instead, the last one of each input possibly
+/// shorter. Lines and chunks are named `<path>#<n>`.
+pub fn for_each_leaf<F>(
+    paths: &[String],
+    chunk_size: Option<usize>,
+    mut f: F,
+) -> Result<(), Failure>
+where
+    F: FnMut(String, Vec<u8>),
+{
+    if paths.is_empty() {
+        return Err(Failure::usage("no inputs given"));
+    }
+    if chunk_size == Some(0) {
+        return Err(Failure::usage("chunk size must be positive"));
+    }
+
+    for path in paths {
+        let mut reader: Box<dyn BufRead> = if path == "-" {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            let file = File::open(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+            Box::new(BufReader::new(file))
+        };
+
+        match chunk_size {
+            Some(size) => {
+                for n in 0.. {
+                    let mut chunk = Vec::with_capacity(size);
+                    (&mut reader).take(size as u64).read_to_end(&mut chunk)?;
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    f(format!("{}#{}", path, n), chunk);
+                }
+            }
+            None if path == "-" => {
+                for (n, line) in reader.split(b'\n').enumerate() {
+                    let mut line = line?;
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    f(format!("{}#{}", path, n), line);
+                }
+            }
+            None => {
+                let mut contents = Vec::new();
+                reader.read_to_end(&mut contents)?;
+                f(path.clone(), contents);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::Scratch;
+    use super::*;
+
+    fn leaves(paths: &[String], chunk_size: Option<usize>) -> Vec<(String, Vec<u8>)> {
+        let mut leaves = Vec::new();
+        for_each_leaf(paths, chunk_size, |name, leaf| leaves.push((name, leaf))).unwrap();
+        leaves
+    }
+
+    #[test]
+    fn files_are_leaves_or_cut_into_chunks() {
+        let scratch = Scratch::new("input");
+        let a = scratch.write("a", b"hello");
+        let b = scratch.write("b", b"");
+        let paths = vec![a.clone(), b.clone()];
+
+        assert_eq!(
+            leaves(&paths, None),
+            vec![(a.clone(), b"hello".to_vec()), (b, Vec::new())]
+        );
+        assert_eq!(
+            leaves(&paths, Some(2)),
+            vec![
+                (format!("{}#0", a), b"he".to_vec()),
+                (format!("{}#1", a), b"ll".to_vec()),
+                (format!("{}#2", a), b"o".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_inputs_and_empty_chunks() {
+        assert!(for_each_leaf(&[], None, |_, _| ()).is_err());
+        assert!(for_each_leaf(&["a".to_string()], Some(0), |_, _| ()).is_err());
+        assert!(for_each_leaf(&["/nonexistent/merkle".to_string()], None, |_, _| ()).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use digest::Digest;
+use merkle_rs::{Hash, MerkleTree};
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::scheme::{self, Command, Scheme};
+use super::snapshot::{self, Encoding};
+use super::{hex, Failure};
+
+const HELP: &str = "commands:
+    root                         print the root
+    node <level> <index>         print a node, the leaves below it and its children
+    leaf <position>              print the name and hash of a leaf
+    path <position>              print the nodes from a leaf up to the root and
+                                 the siblings they are hashed with
+    tree [<level> <index>] [<depth>]
+                                 draw a subtree, the whole tree 3 levels deep
+                                 by default
+    find <hash>                  print the first position of a leaf hash
+    help                         print this
+    quit                         leave
+
+levels count up from the leaves at 0.";
+
+/// Hex digits of a hash shown where many of them are listed.
+const SHORT: usize = 16;
+
+struct Inspect {
+    manifest: Manifest,
+}
+
+/// The nodes of a tree by level, the leaves first.
+struct Explorer<'a> {
+    manifest: &'a Manifest,
+    levels: Vec<&'a [Hash]>,
+}
+
+fn short(hash: &[u8]) -> String {
+    let mut text = hex::encode(hash);
+    if text.len() > SHORT {
+        text.truncate(SHORT);
+        text.push('…');
+    }
+    text
+}
+
+fn number(word: Option<&&str>, what: &str) -> Result<usize, String> {
+    match word {
+        Some(word) => word
+            .parse()
+            .map_err(|_| format!("`{}` is not a valid {}", word, what)),
+        None => Err(format!("missing {}", what)),
+    }
+}
+
+impl Explorer<'_> {
+    fn count_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    fn node(&self, level: usize, index: usize) -> Result<&Hash, String> {
+        self.levels
+            .get(level)
+            .and_then(|nodes| nodes.get(index))
+            .ok_or_else(|| format!("there is no node {} at level {}", index, level))
+    }
+
+    fn leaf(&self, position: usize) -> Result<&Hash, String> {
+        self.node(0, position)
+            .map_err(|_| format!("there is no leaf {} of {}", position, self.count_leaves()))
+    }
+
+    /// Describes the node at `index` of `level` on one line.
+    fn describe(&self, level: usize, index: usize) -> String {
+        let start = index << level;
+        let end = ((index + 1) << level).min(self.count_leaves());
+        let hash = short(&self.levels[level][index]);
+        match level {
+            0 => format!("0:{} {} {}", index, hash, self.manifest.leaves[index].0),
+            _ if end - start == 1 => format!("{}:{} {} leaf {}", level, index, hash, start),
+            _ => format!("{}:{} {} leaves {}..{}", level, index, hash, start, end),
+        }
+    }
+
+    fn tree(&self, level: usize, index: usize, depth: usize, prefix: &str, out: &mut String) {
+        if depth == 0 || level == 0 {
+            return;
+        }
+
+        let below = &self.levels[level - 1];
+        let children: Vec<usize> = [2 * index, 2 * index + 1]
+            .iter()
+            .copied()
+            .filter(|&child| child < below.len())
+            .collect();
+        for (n, &child) in children.iter().enumerate() {
+            let last = n + 1 == children.len();
+            out.push_str(prefix);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(&self.describe(level - 1, child));
+            if children.len() == 1 {
+                out.push_str(" (paired with itself)");
+            }
+            out.push('\n');
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.tree(level - 1, child, depth - 1, &prefix, out);
+        }
+    }
+
+    /// Runs one command line and returns what to print.
+    fn run(&self, words: &[&str]) -> Result<String, String> {
+        let top = self.levels.len() - 1;
+        match words {
+            ["root"] => Ok(format!(
+                "{} over {} leaves",
+                hex::encode(self.levels[top][0].as_slice()),
+                self.count_leaves()
+            )),
+            ["node", rest @ ..] => {
+                let level = number(rest.first(), "level")?;
+                let index = number(rest.get(1), "index")?;
+                let hash = self.node(level, index)?;
+                let mut out = format!("{}\n{}\n", self.describe(level, index), hex::encode(hash));
+                if level > 0 {
+                    self.tree(level, index, 1, "", &mut out);
+                }
+                Ok(out.trim_end().to_string())
+            }
+            ["leaf", rest @ ..] => {
+                let position = number(rest.first(), "position")?;
+                let hash = self.leaf(position)?;
+                Ok(format!(
+                    "{} {}",
+                    hex::encode(hash),
+                    self.manifest.leaves[position].0
+                ))
+            }
+            ["path", rest @ ..] => {
+                let position = number(rest.first(), "position")?;
+                self.leaf(position)?;
+                let mut lines = Vec::new();
+                for level in 0..top {
+                    let index = position >> level;
+                    let nodes = self.levels[level];
+                    let (sibling, side) = if index % 2 == 1 {
+                        (index - 1, "left")
+                    } else {
+                        (index + 1, "right")
+                    };
+                    let sibling = match nodes.get(sibling) {
+                        Some(hash) => {
+                            format!("sibling {} {} on the {}", sibling, short(hash), side)
+                        }
+                        None => "paired with itself".to_string(),
+                    };
+                    lines.push(format!("{}  {}", self.describe(level, index), sibling));
+                }
+                lines.push(self.describe(top, 0));
+                Ok(lines.join("\n"))
+            }
+            ["tree", rest @ ..] => {
+                let (level, index, depth) = match rest {
+                    [] => (top, 0, 3),
+                    [depth] => (top, 0, number(Some(depth), "depth")?),
+                    [level, index, depth @ ..] => (
+                        number(Some(level), "level")?,
+                        number(Some(index), "index")?,
+                        match depth {
+                            [] => 3,
+                            [depth] => number(Some(depth), "depth")?,
+                            _ => return Err("tree takes at most 3 numbers".to_string()),
+                        },
+                    ),
+                };
+                self.node(level, index)?;
+                let mut out = self.describe(level, index) + "\n";
+                self.tree(level, index, depth, "", &mut out);
+                Ok(out.trim_end().to_string())
+            }
+            ["find", leaf] => {
+                let leaf =
+                    hex::decode(leaf).map_err(|_| format!("`{}` is not a hex hash", leaf))?;
+                self.levels[0]
+                    .iter()
+                    .position(|l| *l == leaf)
+                    .map(|position| self.describe(0, position))
+                    .ok_or_else(|| "no leaf has that hash".to_string())
+            }
+            ["help"] => Ok(HELP.to_string()),
+            [other, ..] => Err(format!("unknown command `{}`, try help", other)),
+            [] => Ok(String::new()),
+        }
+    }
+}
+
+impl Command for Inspect {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let tree: MerkleTree<H> = self.manifest.tree()?;
+        let mut levels: Vec<&[Hash]> = tree.levels().collect();
+        levels.reverse();
+        let explorer = Explorer {
+            manifest: &self.manifest,
+            levels,
+        };
+
+        // Prompts are only shown to people, not to scripts feeding commands.
+        let interactive = io::stdin().is_terminal();
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        if interactive {
+            writeln!(
+                stdout,
+                "{}\ntype help for commands",
+                explorer.run(&["root"]).unwrap()
+            )?;
+        }
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            if interactive {
+                write!(stdout, "> ")?;
+                stdout.flush()?;
+            }
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["quit"] | ["exit"] = words.as_slice() {
+                return Ok(());
+            }
+
+            match explorer.run(&words) {
+                Ok(out) if out.is_empty() => {}
+                Ok(out) => writeln!(stdout, "{}", out)?,
+                Err(message) => writeln!(stdout, "error: {}", message)?,
+            }
+        }
+    }
+}
+
+/// `merkle inspect <snapshot>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(args, &["algorithm", "profile", "padding"])?;
+    let path = match args.positional() {
+        [path] => path,
+        _ => return Err(Failure::usage("give one snapshot to inspect")),
+    };
+    let bytes = fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+    let encoding = Encoding::detect(&bytes);
+    let (manifest, _) = snapshot::decode(&bytes, path, encoding, &Scheme::from_args(&args)?)?;
+    if encoding != Encoding::Leaves {
+        manifest.scheme.check_args(&args, path)?;
+    }
+
+    let scheme = manifest.scheme.clone();
+    scheme::dispatch(&scheme, Inspect { manifest })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::{leaf_hash_with_hasher, Padding};
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            scheme: Scheme {
+                algorithm: "sha256".to_string(),
+                profile: "merkle".to_string(),
+                padding: Padding::Duplicate,
+            },
+            leaves: ["a", "b", "c"]
+                .iter()
+                .map(|v| (v.to_string(), leaf_hash_with_hasher(v, Sha256::default())))
+                .collect(),
+        }
+    }
+
+    fn inspect(line: &str) -> Result<String, String> {
+        let manifest = manifest();
+        let tree = manifest.tree::<Sha256>().unwrap();
+        let mut levels: Vec<&[Hash]> = tree.levels().collect();
+        levels.reverse();
+        let explorer = Explorer {
+            manifest: &manifest,
+            levels,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        explorer.run(&words)
+    }
+
+    #[test]
+    fn answers_commands() {
+        let tree = manifest().tree::<Sha256>().unwrap();
+        assert_eq!(
+            inspect("root"),
+            Ok(format!("{} over 3 leaves", hex::encode(tree.root_hash())))
+        );
+        let leaf = hex::encode(tree.leaf(2).unwrap());
+        assert_eq!(inspect("leaf 2"), Ok(format!("{} c", leaf)));
+        assert_eq!(
+            inspect(&format!("find {}", leaf)),
+            Ok(format!("0:2 {}… c", &leaf[..SHORT]))
+        );
+
+        let path = inspect("path 2").unwrap();
+        let lines: Vec<&str> = path.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("paired with itself"));
+        assert!(lines[1].contains("sibling 0") && lines[1].ends_with("on the left"));
+        assert!(lines[2].starts_with("2:0 "));
+
+        let drawn = inspect("tree").unwrap();
+        assert_eq!(drawn.lines().count(), 6);
+        assert!(inspect("node 1 1")
+            .unwrap()
+            .contains("(paired with itself)"));
+        assert_eq!(inspect(""), Ok(String::new()));
+    }
+
+    #[test]
+    fn reports_bad_commands() {
+        assert!(inspect("leaf 3").is_err());
+        assert!(inspect("node 2 1").is_err());
+        assert!(inspect("node x 0").is_err());
+        assert!(inspect("find zz").is_err());
+        assert!(inspect("tree 1 0 2 3").is_err());
+        assert!(inspect("frobnicate").is_err());
+    }
+}
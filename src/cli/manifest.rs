@@ -0,0 +1,258 @@
+use std::fs;
+
+use digest::Digest;
+use merkle_rs::{leaf_hash_with_hasher, Hash, MerkleTree, Padding};
+
+use super::args::Args;
+use super::scheme::{self, Scheme};
+use super::{dir, hex, input, Failure};
+
+pub const HEADER: &str = "merkle-manifest 1";
+
+/// The named leaf hashes of a tree, saved as text so a tree can be rebuilt
+/// without its inputs.
+///
+/// The first line is `merkle-manifest 1 <algorithm> <profile> <padding>`,
+/// then every leaf takes one line with its hex hash and its name. Manifests
+/// saved without a padding duplicate odd nodes.
+pub struct Manifest {
+    pub scheme: Scheme,
+    pub leaves: Vec<(String, Hash)>,
+}
+
+impl Manifest {
+    /// Hashes the leaves of `paths` as `input::for_each_leaf` reads them.
+    pub fn from_inputs<H: Digest>(
+        scheme: &Scheme,
+        paths: &[String],
+        chunk_size: Option<usize>,
+    ) -> Result<Manifest, Failure> {
+        let mut leaves = Vec::new();
+        input::for_each_leaf(paths, chunk_size, |name, leaf| {
+            leaves.push((name, leaf_hash_with_hasher(&leaf, H::new())));
+        })?;
+
+        Ok(Manifest {
+            scheme: scheme.clone(),
+            leaves,
+        })
+    }
+
+    /// Hashes every file below `dir`, in name order.
+    pub fn from_dir<H: Digest>(scheme: &Scheme, dir: &str) -> Result<Manifest, Failure> {
+        let mut leaves = Vec::new();
+        for file in dir::scan(dir)? {
+            leaves.push((
+                file.name.clone(),
+                leaf_hash_with_hasher(&file.read()?, H::new()),
+            ));
+        }
+
+        Ok(Manifest {
+            scheme: scheme.clone(),
+            leaves,
+        })
+    }
+
+    pub fn read(path: &str) -> Result<Manifest, Failure> {
+        let text =
+            fs::read_to_string(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+        Manifest::parse(&text, path)
+    }
+
+    /// Reads a manifest from `text`, naming it `path` in errors.
+    pub fn parse(text: &str, path: &str) -> Result<Manifest, Failure> {
+        let invalid = |line: usize| Failure::error(format!("{}:{}: invalid manifest", path, line));
+        let mut lines = text.lines();
+
+        let scheme = match lines
+            .next()
+            .and_then(|header| header.strip_prefix(HEADER))
+            .map(|rest| rest.split(' ').collect::<Vec<_>>())
+            .as_deref()
+        {
+            Some(["", algorithm, profile]) => Scheme {
+                algorithm: algorithm.to_string(),
+                profile: profile.to_string(),
+                padding: Padding::Duplicate,
+            },
+            Some(["", algorithm, profile, padding]) => Scheme {
+                algorithm: algorithm.to_string(),
+                profile: profile.to_string(),
+                padding: scheme::parse_padding(padding).map_err(|_| invalid(1))?,
+            },
+            _ => return Err(invalid(1)),
+        };
+
+        let mut leaves = Vec::new();
+        for (n, line) in lines.enumerate() {
+            let (leaf, name) = line.split_at(line.find(' ').ok_or_else(|| invalid(n + 2))?);
+            let leaf = hex::decode(leaf).map_err(|_| invalid(n + 2))?;
+            leaves.push((name[1..].to_string(), leaf));
+        }
+
+        Ok(Manifest { scheme, leaves })
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Failure> {
+        fs::write(path, self.to_text()?).map_err(|e| Failure::error(format!("{}: {}", path, e)))
+    }
+
+    pub fn to_text(&self) -> Result<String, Failure> {
+        let mut text = format!("{} {}\n", HEADER, self.scheme);
+        for (name, leaf) in &self.leaves {
+            if name.contains('\n') {
+                return Err(Failure::error(format!("cannot save leaf name {:?}", name)));
+            }
+            text.push_str(&format!("{} {}\n", hex::encode(leaf), name));
+        }
+
+        Ok(text)
+    }
+
+    /// Builds the tree over the leaves with the padding of the scheme, or
+    /// fails if there are none or the padding rejects their count.
+    pub fn tree<H: Digest>(&self) -> Result<MerkleTree<H>, Failure> {
+        if self.leaves.is_empty() {
+            return Err(Failure::error("inputs hold no leaves"));
+        }
+
+        let leaves = self.leaves.iter().map(|(_, leaf)| leaf.clone()).collect();
+        let mut tree = MerkleTree::build_from_leaves(leaves, H::new());
+        tree.try_set_padding(self.scheme.padding)
+            .map_err(Failure::error)?;
+        Ok(tree)
+    }
+}
+
+/// Where a command takes its tree from: a manifest given with `--manifest`,
+/// or the inputs on its command line.
+pub struct Source {
+    scheme: Scheme,
+    manifest: Option<Manifest>,
+}
+
+impl Source {
+    pub fn from_args(args: &Args) -> Result<Source, Failure> {
+        let manifest = match args.option("manifest") {
+            Some(path) => Manifest::read(path)?,
+            None => {
+                return Ok(Source {
+                    scheme: Scheme::from_args(args)?,
+                    manifest: None,
+                })
+            }
+        };
+
+        if !args.positional().is_empty() {
+            return Err(Failure::usage("give either a manifest or inputs"));
+        }
+        manifest.scheme.check_args(args, "manifest")?;
+
+        Ok(Source {
+            scheme: manifest.scheme.clone(),
+            manifest: Some(manifest),
+        })
+    }
+
+    pub fn scheme(&self) -> &Scheme {
+        &self.scheme
+    }
+
+    /// Returns the manifest, hashing the inputs in `args` if none was given.
+    pub fn into_manifest<H: Digest>(self, args: &Args) -> Result<Manifest, Failure> {
+        match self.manifest {
+            Some(manifest) => Ok(manifest),
+            None => Manifest::from_inputs::<H>(
+                &self.scheme,
+                args.positional(),
+                args.parsed("chunk-size")?,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::super::tests::Scratch;
+    use super::*;
+
+    fn scheme() -> Scheme {
+        Scheme {
+            algorithm: "sha256".to_string(),
+            profile: "merkle".to_string(),
+            padding: Padding::Duplicate,
+        }
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let scratch = Scratch::new("manifest");
+        let paths = vec![scratch.write("a", b"a"), scratch.write("b", b"b")];
+        let manifest = Manifest::from_inputs::<Sha256>(&scheme(), &paths, None).unwrap();
+
+        let path = scratch.path("manifest");
+        manifest.write(&path).unwrap();
+        let read = Manifest::read(&path).unwrap();
+        assert_eq!(read.scheme, scheme());
+        assert_eq!(read.leaves, manifest.leaves);
+        assert_eq!(read.leaves[1].0, paths[1]);
+
+        let expected = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        assert_eq!(
+            read.tree::<Sha256>().unwrap().root_hash(),
+            expected.root_hash()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_manifests() {
+        assert!(Manifest::parse("merkle-manifest 2 sha256 merkle\n", "m").is_err());
+        assert!(Manifest::parse(&format!("{} sha256 merkle\nzz a\n", HEADER), "m").is_err());
+        assert!(Manifest::parse(&format!("{} sha256 merkle\n00\n", HEADER), "m").is_err());
+        assert!(Manifest::parse(&format!("{} sha256 merkle zero\n", HEADER), "m").is_err());
+
+        let manifest = Manifest {
+            scheme: scheme(),
+            leaves: vec![("a\nb".to_string(), vec![0])],
+        };
+        assert!(manifest.to_text().is_err());
+    }
+
+    #[test]
+    fn padding_is_saved_and_used() {
+        let mut manifest = Manifest {
+            scheme: scheme(),
+            leaves: ["a", "b", "c"]
+                .iter()
+                .map(|v| (v.to_string(), leaf_hash_with_hasher(v, Sha256::new())))
+                .collect(),
+        };
+        let text = manifest.to_text().unwrap();
+        assert!(text.starts_with("merkle-manifest 1 sha256 merkle duplicate\n"));
+
+        manifest.scheme.padding = Padding::Empty;
+        let text = manifest.to_text().unwrap();
+        assert!(text.starts_with("merkle-manifest 1 sha256 merkle empty\n"));
+        let read = Manifest::parse(&text, "m").unwrap();
+        assert_eq!(read.scheme.padding, Padding::Empty);
+
+        let mut expected = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        expected.set_padding(Padding::Empty);
+        assert_eq!(
+            read.tree::<Sha256>().unwrap().root_hash(),
+            expected.root_hash()
+        );
+
+        let old = text.replacen(" empty\n", "\n", 1);
+        assert_eq!(
+            Manifest::parse(&old, "m").unwrap().scheme.padding,
+            Padding::Duplicate
+        );
+
+        manifest.scheme.padding = Padding::Reject;
+        assert!(manifest.tree::<Sha256>().is_err());
+    }
+}
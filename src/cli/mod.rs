@@ -0,0 +1,242 @@
+mod args;
+mod base64;
+mod chunk;
+mod consistency;
+mod diff;
+mod dir;
+mod export;
+mod hex;
+mod import;
+mod input;
+mod inspect;
+mod manifest;
+mod output;
+mod proof;
+mod prove;
+mod root;
+mod scheme;
+mod serve;
+mod snapshot;
+mod verify;
+mod watch;
+
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const USAGE: &str = "usage: merkle <command> [options]
+
+commands:
+    root <inputs>          print the root of the inputs
+        --save <file>      also save their manifest
+        --format <name>    hex by default
+        --out <file>       write the root to file instead of stdout
+    prove <tree>           print the inclusion proof of a leaf
+        --index <n>        of the leaf at position n
+        --leaf <file>      of the first leaf holding the contents of file
+        --format <name>    json by default
+        --out <file>       write the proof to file instead of stdout
+    verify                 check a proof, exiting with 0 if it holds and 1 if not
+        --root <root>      against this root, in hex or base64
+        --proof <file>     as printed by prove in any format
+        --leaf <file>      for a leaf holding the contents of file
+        --value <text>     for a leaf holding text
+    watch <dir>            print the root of the files below dir as they change
+        --manifest <file>  keep their manifest up to date in file
+        --interval <secs>  between scans, 1 by default
+        --format <name>    hex by default
+    diff <old> <new>       list files added (+), removed (-) or modified (M)
+                           between two directories or manifests, exiting
+                           with 1 if there are any
+    consistency            prove that a saved tree only appended to another
+        --old <manifest>   the earlier tree
+        --new <manifest>   the later tree
+        --format <name>    json by default
+        --out <file>       write the proof to file instead of stdout
+    chunk <file>           print the root of the chunks of file and save the
+                           proof of every chunk
+        --size <bytes>     of the chunks, such as 4096, 64KiB or 1MiB
+        --out <dir>        to save the proofs and the root in
+        --format <name>    json by default
+    serve                  answer HTTP requests for the root and proofs of a
+                           saved tree: GET /root, /proof/<index>,
+                           /proof/leaf/<hash> and /consistency/<count>, in
+                           json unless ?format=<name> is given
+        --snapshot <file>  the saved tree, in any snapshot encoding
+        --listen <addr>    127.0.0.1:8080 by default, :<port> for every
+                           interface
+    export <manifest>      convert a saved tree for other machines and tools
+        --to <encoding>    manifest, binary, json or leaves (one hex leaf
+                           hash per line)
+        --out <file>       write it to file instead of stdout
+    import <file>          convert a tree back into a manifest, checking it
+                           against the root saved with it
+        --from <encoding>  guessed from the file by default; leaf lists are
+                           hashed with --algorithm and --profile
+        --out <file>       write it to file instead of stdout
+    inspect <snapshot>     explore a saved tree in any encoding, reading
+                           commands such as `node 2 1` or `path 5` from stdin;
+                           type help for the list
+
+inputs are files, one leaf each, or `-` for the lines of stdin. a <tree> is
+either inputs or `--manifest <file>`.
+
+options:
+    --algorithm <name>     sha224, sha256 (default), sha384, sha512, sha3,
+                           blake3 or double-sha256
+    --profile <name>       merkle (default), plain, bitcoin or rfc6962
+    --padding <name>       what the last node of an odd level is paired with:
+                           duplicate (default), empty, reject (for a power
+                           of 2 leaves), promote (carried up unpaired, as
+                           rfc6962 does) or constant:<hex>; saved manifests
+                           and proofs remember all three
+    --chunk-size <bytes>   split inputs into chunks instead of files or lines
+    --format <name>        hex, base64, json or binary
+    --quiet, -q            only print results and errors";
+
+/// Why a command did not succeed, and the exit status to report it with.
+#[derive(Debug)]
+pub struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl Failure {
+    /// The command line could not be understood.
+    pub fn usage<M: fmt::Display>(message: M) -> Failure {
+        Failure {
+            code: 2,
+            message: format!("{}\n\n{}", message, USAGE),
+        }
+    }
+
+    /// The command ran and found what it checked to be wrong.
+    pub fn invalid<M: fmt::Display>(message: M) -> Failure {
+        Failure {
+            code: 1,
+            message: message.to_string(),
+        }
+    }
+
+    /// The command could not complete.
+    pub fn error<M: fmt::Display>(message: M) -> Failure {
+        Failure {
+            code: 2,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::error(e)
+    }
+}
+
+/// Set by `--quiet` to only print results and errors.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a diagnostic for people reading along, unless `--quiet` was given.
+pub fn note<M: fmt::Display>(message: M) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Runs the command in `args` and returns the process exit status.
+pub fn run(mut args: Vec<String>) -> i32 {
+    let quiet = args.len();
+    args.retain(|arg| arg != "--quiet" && arg != "-q");
+    QUIET.store(args.len() < quiet, Ordering::Relaxed);
+
+    let mut args = args.into_iter();
+    let command = args.next();
+    let args: Vec<String> = args.collect();
+
+    let result = match command.as_deref() {
+        Some("root") => root::run(args),
+        Some("prove") => prove::run(args),
+        Some("verify") => verify::run(args),
+        Some("watch") => watch::run(args),
+        Some("diff") => diff::run(args),
+        Some("consistency") => consistency::run(args),
+        Some("chunk") => chunk::run(args),
+        Some("serve") => serve::run(args),
+        Some("export") => export::run(args),
+        Some("import") => import::run(args),
+        Some("inspect") => inspect::run(args),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(Failure::usage(format!("unknown command `{}`", other))),
+        None => Err(Failure::usage("missing command")),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(failure) => {
+            // Under --quiet the status alone tells that a check failed.
+            if failure.code != 1 || !QUIET.load(Ordering::Relaxed) {
+                eprintln!("merkle: {}", failure.message);
+            }
+            failure.code
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    /// A directory of files for one test, removed once it is dropped.
+    pub struct Scratch {
+        dir: PathBuf,
+    }
+
+    impl Scratch {
+        pub fn new(name: &str) -> Scratch {
+            let dir =
+                std::env::temp_dir().join(format!("merkle-cli-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch { dir }
+        }
+
+        pub fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_string_lossy().into_owned()
+        }
+
+        /// Writes `contents` to the file `name`, creating its directories.
+        pub fn write(&self, name: &str, contents: &[u8]) -> String {
+            let path = self.dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+
+        pub fn read(&self, name: &str) -> Vec<u8> {
+            fs::read(Path::new(&self.path(name))).unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    pub fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn unknown_or_missing_command_is_a_usage_error() {
+        assert_eq!(run(args(&["frobnicate"])), 2);
+        assert_eq!(run(args(&[])), 2);
+        assert_eq!(run(args(&["root", "--bogus", "x"])), 2);
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use super::args::Args;
+use super::{base64, hex, Failure};
+
+/// How roots and proofs are written out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Hex,
+    Base64,
+    Json,
+    Binary,
+}
+
+impl Format {
+    /// Reads `--format`, falling back to `default`.
+    pub fn from_args(args: &Args, default: Format) -> Result<Format, Failure> {
+        match args.option("format") {
+            None => Ok(default),
+            Some(name) => Format::parse(name)
+                .ok_or_else(|| Failure::usage(format!("unknown format `{}`", name))),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "hex" => Some(Format::Hex),
+            "base64" => Some(Format::Base64),
+            "json" => Some(Format::Json),
+            "binary" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+
+    /// Encodes `bytes`, or the object `json` describes them with for the JSON
+    /// format. Text formats end with a newline.
+    pub fn encode<F>(self, bytes: &[u8], json: F) -> Vec<u8>
+    where
+        F: FnOnce() -> Value,
+    {
+        let text = match self {
+            Format::Hex => hex::encode(bytes),
+            Format::Base64 => base64::encode(bytes),
+            Format::Json => serde_json::to_string_pretty(&json()).unwrap(),
+            Format::Binary => return bytes.to_vec(),
+        };
+        (text + "\n").into_bytes()
+    }
+}
+
+/// Writes `output` to the file given with `--out`, or to stdout.
+pub fn emit(args: &Args, output: &[u8]) -> Result<(), Failure> {
+    match args.option("out") {
+        Some(path) => {
+            fs::write(path, output).map_err(|e| Failure::error(format!("{}: {}", path, e)))
+        }
+        None => print(output),
+    }
+}
+
+pub fn print(output: &[u8]) -> Result<(), Failure> {
+    let mut stdout = io::stdout();
+    stdout.write_all(output)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Decodes `bytes` written in a format other than JSON, trying hex, then
+/// base64, then taking them as they are.
+pub fn decode(bytes: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => hex::decode(text)
+            .or_else(|_| base64::decode(text))
+            .unwrap_or_else(|_| bytes.to_vec()),
+        Err(_) => bytes.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn text_formats_decode_back() {
+        let bytes = [0u8, 1, 0xfe, 0xff];
+        for format in [Format::Hex, Format::Base64, Format::Binary] {
+            let encoded = format.encode(&bytes, || unreachable!());
+            assert_eq!(decode(&encoded), bytes, "{:?}", format);
+        }
+        assert_eq!(Format::Hex.encode(&bytes, || unreachable!()), b"0001feff\n");
+    }
+
+    #[test]
+    fn json_format_encodes_the_description() {
+        let encoded = Format::Json.encode(&[1], || json!({ "root": "01" }));
+        let value: Value = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(value, json!({ "root": "01" }));
+        assert_eq!(encoded.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!(Format::parse("base64"), Some(Format::Base64));
+        assert_eq!(Format::parse("yaml"), None);
+    }
+}
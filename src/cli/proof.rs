@@ -0,0 +1,114 @@
+use merkle_rs::{Padding, Proof};
+use serde_json::{json, Value};
+
+use super::output;
+use super::scheme::{self, Scheme};
+use super::{hex, Failure};
+
+/// Describes `proof` like `Proof::to_json`, along with what it was made for.
+pub fn describe(proof: &Proof, scheme: &Scheme, root: &[u8], name: &str, leaf: &[u8]) -> Value {
+    let mut value = proof.to_json();
+    value["algorithm"] = json!(scheme.algorithm);
+    value["profile"] = json!(scheme.profile);
+    value["padding"] = json!(scheme::padding_name(scheme.padding));
+    value["name"] = json!(name);
+    value["root"] = json!(hex::encode(root));
+    value["leaf"] = json!(hex::encode(leaf));
+    value
+}
+
+/// Reads a proof saved in any output format, and the scheme named in it if
+/// it was saved as JSON.
+///
+/// Binary proofs start with their position, whose low byte may well be `{`,
+/// so only input that parses as JSON is read as JSON.
+pub fn parse(bytes: &[u8]) -> Result<(Proof, Option<Scheme>), Failure> {
+    let json = if bytes.trim_ascii_start().starts_with(b"{") {
+        serde_json::from_slice::<Value>(bytes).ok()
+    } else {
+        None
+    };
+    match json {
+        Some(value) => {
+            let padding = match value["padding"].as_str() {
+                Some(name) => scheme::parse_padding(name)
+                    .map_err(|_| Failure::error(format!("invalid proof: padding `{}`", name)))?,
+                None => Padding::Duplicate,
+            };
+            let scheme = match (value["algorithm"].as_str(), value["profile"].as_str()) {
+                (Some(algorithm), Some(profile)) => Some(Scheme {
+                    algorithm: algorithm.to_string(),
+                    profile: profile.to_string(),
+                    padding,
+                }),
+                _ => None,
+            };
+            let proof = Proof::from_json(&value)
+                .map_err(|e| Failure::error(format!("invalid proof: {}", e)))?;
+            Ok((proof, scheme))
+        }
+        None => {
+            let proof = Proof::from_bytes(&output::decode(bytes))
+                .map_err(|e| Failure::error(format!("invalid proof: {}", e)))?;
+            Ok((proof, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::MerkleTree;
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn proof() -> Proof {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default()).gen_proof(2)
+    }
+
+    #[test]
+    fn described_proof_parses_back_with_its_scheme() {
+        let scheme = Scheme {
+            algorithm: "sha256".to_string(),
+            profile: "merkle".to_string(),
+            padding: Padding::Duplicate,
+        };
+        let value = describe(&proof(), &scheme, &[1, 2], "c", &[3]);
+        assert_eq!(value["root"], "0102");
+        assert_eq!(value["leaf"], "03");
+
+        let parsed = parse(value.to_string().as_bytes()).unwrap();
+        assert_eq!(parsed, (proof(), Some(scheme)));
+    }
+
+    #[test]
+    fn encoded_proofs_parse_without_a_scheme() {
+        let bytes = proof().to_bytes();
+        assert_eq!(parse(&bytes).unwrap(), (proof(), None));
+        assert_eq!(
+            parse(hex::encode(&bytes).as_bytes()).unwrap(),
+            (proof(), None)
+        );
+        assert_eq!(
+            parse(proof().to_json().to_string().as_bytes()).unwrap(),
+            (proof(), None)
+        );
+    }
+
+    #[test]
+    fn binary_proof_starting_with_a_brace_is_not_json() {
+        let values: Vec<Vec<u8>> = (0..200u8).map(|i| vec![i]).collect();
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        let proof = tree.gen_proof(123);
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes[0], b'{');
+        assert_eq!(parse(&bytes).unwrap(), (proof, None));
+    }
+
+    #[test]
+    fn malformed_proofs_fail() {
+        assert!(parse(b"{ not json").is_err());
+        assert!(parse(b"{}").is_err());
+        assert!(parse(b"zz").is_err());
+    }
+}
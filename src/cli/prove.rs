@@ -0,0 +1,127 @@
+use std::fs;
+
+use digest::Digest;
+use merkle_rs::leaf_hash_with_hasher;
+
+use super::args::Args;
+use super::manifest::Source;
+use super::output::{self, Format};
+use super::proof;
+use super::scheme::{self, Command};
+use super::Failure;
+
+struct Prove {
+    args: Args,
+    source: Source,
+}
+
+impl Command for Prove {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let scheme = self.source.scheme().clone();
+        let manifest = self.source.into_manifest::<H>(&self.args)?;
+        let tree = manifest.tree::<H>()?;
+
+        let position = match (
+            self.args.parsed::<usize>("index")?,
+            self.args.option("leaf"),
+        ) {
+            (Some(index), None) if index < tree.count_leaves() => index,
+            (Some(index), None) => {
+                return Err(Failure::error(format!(
+                    "index {} does not relate to any of the {} leaves",
+                    index,
+                    tree.count_leaves()
+                )))
+            }
+            (None, Some(path)) => {
+                let contents =
+                    fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+                tree.index_of(&leaf_hash_with_hasher(&contents, H::new()))
+                    .ok_or_else(|| Failure::error(format!("{} is not a leaf of the tree", path)))?
+            }
+            _ => return Err(Failure::usage("give one of --index or --leaf")),
+        };
+
+        let proof = tree.gen_proof(position);
+        let format = Format::from_args(&self.args, Format::Json)?;
+        let (name, leaf) = &manifest.leaves[position];
+        let output = format.encode(&proof.to_bytes(), || {
+            proof::describe(&proof, &scheme, tree.root_hash(), name, leaf)
+        });
+        output::emit(&self.args, &output)
+    }
+}
+
+/// `merkle prove (--index <n> | --leaf <file>) (--manifest <file> | <paths>...)`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &[
+            "algorithm",
+            "profile",
+            "padding",
+            "chunk-size",
+            "manifest",
+            "index",
+            "leaf",
+            "format",
+            "out",
+        ],
+    )?;
+    let source = Source::from_args(&args)?;
+    let scheme = source.scheme().clone();
+    scheme::dispatch(&scheme, Prove { args, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::MerkleTree;
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    fn files(scratch: &Scratch) -> Vec<String> {
+        ["a", "b", "c"]
+            .iter()
+            .map(|name| scratch.write(name, name.as_bytes()))
+            .collect()
+    }
+
+    fn prove(scratch: &Scratch, options: &[&str]) -> Result<serde_json::Value, Failure> {
+        let out = scratch.path("proof");
+        let mut command = files(scratch);
+        command.extend(args(options));
+        command.extend(args(&["--out", &out]));
+        run(command)?;
+        Ok(serde_json::from_slice(&scratch.read("proof")).unwrap())
+    }
+
+    #[test]
+    fn proves_a_leaf_by_index_or_contents() {
+        let scratch = Scratch::new("prove");
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+
+        let by_index = prove(&scratch, &["--index", "2"]).unwrap();
+        assert_eq!(
+            merkle_rs::Proof::from_json(&by_index).unwrap(),
+            tree.gen_proof(2)
+        );
+        assert_eq!(
+            by_index["root"],
+            super::super::hex::encode(tree.root_hash())
+        );
+
+        let leaf = scratch.path("c");
+        assert_eq!(prove(&scratch, &["--leaf", &leaf]).unwrap(), by_index);
+    }
+
+    #[test]
+    fn fails_on_unknown_leaf() {
+        let scratch = Scratch::new("prove-unknown");
+        assert_eq!(prove(&scratch, &["--index", "3"]).unwrap_err().code, 2);
+        let other = scratch.write("other", b"z");
+        assert_eq!(prove(&scratch, &["--leaf", &other]).unwrap_err().code, 2);
+        assert_eq!(prove(&scratch, &[]).unwrap_err().code, 2);
+    }
+}
@@ -0,0 +1,188 @@
+use digest::Digest;
+use merkle_rs::{leaf_hash_with_hasher, Appender, Error, Padding};
+use serde_json::json;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output::{self, Format};
+use super::scheme::{self, Command, Scheme};
+use super::{hex, input, Failure};
+
+struct Root {
+    scheme: Scheme,
+    args: Args,
+}
+
+impl Command for Root {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let chunk_size = self.args.parsed("chunk-size")?;
+        let save = self.args.option("save");
+        let padding = self.scheme.padding;
+        let mut appender = Appender::with_padding(H::new(), padding);
+        let mut leaves = Vec::new();
+        input::for_each_leaf(self.args.positional(), chunk_size, |name, leaf| {
+            if save.is_some() {
+                leaves.push((name, leaf_hash_with_hasher(&leaf, H::new())));
+            }
+            appender.append(&leaf)
+        })?;
+
+        let count_leaves = appender.count_leaves();
+        if padding == Padding::Reject && (count_leaves < 2 || !count_leaves.is_power_of_two()) {
+            return Err(Failure::error(Error::PaddingRejected(count_leaves)));
+        }
+        let root = appender
+            .root()
+            .ok_or_else(|| Failure::error("inputs hold no leaves"))?;
+        if let Some(path) = save {
+            let manifest = Manifest {
+                scheme: self.scheme.clone(),
+                leaves,
+            };
+            manifest.write(path)?;
+        }
+
+        let format = Format::from_args(&self.args, Format::Hex)?;
+        let output = format.encode(&root, || {
+            json!({
+                "algorithm": self.scheme.algorithm,
+                "profile": self.scheme.profile,
+                "padding": scheme::padding_name(self.scheme.padding),
+                "count_leaves": appender.count_leaves(),
+                "root": hex::encode(&root),
+            })
+        });
+        output::emit(&self.args, &output)
+    }
+}
+
+/// `merkle root <paths>... | -`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &[
+            "algorithm",
+            "profile",
+            "padding",
+            "chunk-size",
+            "save",
+            "format",
+            "out",
+        ],
+    )?;
+    let scheme = Scheme::from_args(&args)?;
+    scheme::dispatch(&scheme.clone(), Root { scheme, args })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::MerkleTree;
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    #[test]
+    fn prints_the_root_of_the_files() {
+        let scratch = Scratch::new("root");
+        let files: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|name| scratch.write(name, name.as_bytes()))
+            .collect();
+        let out = scratch.path("root");
+
+        let mut command = files.clone();
+        command.extend(args(&["--out", &out]));
+        run(command).unwrap();
+
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        assert_eq!(
+            scratch.read("root"),
+            format!("{}\n", hex::encode(tree.root_hash())).into_bytes()
+        );
+    }
+
+    #[test]
+    fn fails_without_leaves() {
+        let scratch = Scratch::new("root-empty");
+        let empty = scratch.write("empty", b"");
+        assert!(run(args(&[&empty, "--chunk-size", "4"])).is_err());
+    }
+
+    #[test]
+    fn describes_the_root_in_json() {
+        let scratch = Scratch::new("root-json");
+        let file = scratch.write("a", b"abcdef");
+        let out = scratch.path("root");
+        run(args(&[
+            &file,
+            "--chunk-size",
+            "4",
+            "--format",
+            "json",
+            "--out",
+            &out,
+        ]))
+        .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&scratch.read("root")).unwrap();
+        let tree = MerkleTree::build_with_hasher(&["abcd", "ef"], Sha256::default());
+        assert_eq!(value["count_leaves"], 2);
+        assert_eq!(value["algorithm"], "sha256");
+        assert_eq!(value["root"], hex::encode(tree.root_hash()));
+    }
+
+    #[test]
+    fn hashes_with_the_given_padding() {
+        let scratch = Scratch::new("root-padding");
+        let files: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|name| scratch.write(name, name.as_bytes()))
+            .collect();
+        let out = scratch.path("root");
+        let manifest = scratch.path("manifest");
+
+        let mut command = files.clone();
+        command.extend(args(&[
+            "--padding",
+            "empty",
+            "--save",
+            &manifest,
+            "--out",
+            &out,
+        ]));
+        run(command).unwrap();
+
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        tree.set_padding(Padding::Empty);
+        assert_eq!(
+            scratch.read("root"),
+            format!("{}\n", hex::encode(tree.root_hash())).into_bytes()
+        );
+        let saved = Manifest::read(&manifest).unwrap();
+        assert_eq!(saved.scheme.padding, Padding::Empty);
+
+        let mut command = files.clone();
+        command.extend(args(&["--padding", "reject", "--out", &out]));
+        assert!(run(command).is_err());
+    }
+
+    #[test]
+    fn rfc6962_profile_matches_certificate_transparency() {
+        let scratch = Scratch::new("root-rfc6962");
+        let files: Vec<String> = [&b""[..], b"\x00", b"\x10"]
+            .iter()
+            .enumerate()
+            .map(|(i, leaf)| scratch.write(&i.to_string(), leaf))
+            .collect();
+        let out = scratch.path("root");
+
+        let mut command = files.clone();
+        command.extend(args(&["--profile", "rfc6962", "--out", &out]));
+        run(command).unwrap();
+        assert_eq!(
+            scratch.read("root"),
+            b"aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77\n"
+        );
+    }
+}
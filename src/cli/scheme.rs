@@ -0,0 +1,335 @@
+// digest 0.9 takes outputs as generic-array 0.14, which newer 0.14 releases
+// deprecate in favour of 1.x.
+#[allow(deprecated)]
+use digest::generic_array::GenericArray;
+use digest::{Digest, FixedOutput, Reset, Update};
+use merkle_rs::{Padding, Sentinel};
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+use sha3::Sha3_256;
+
+use super::args::Args;
+use super::{hex, Failure};
+
+const DEFAULT_ALGORITHM: &str = "sha256";
+const DEFAULT_PROFILE: &str = "merkle";
+
+/// A command that works with any of the supported hash functions.
+pub trait Command {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure>;
+}
+
+/// The hash function, the profile and the padding a tree is hashed with.
+///
+/// The `merkle` profile prefixes leaves and internal nodes with a byte
+/// telling them apart, `plain` hashes them without one and `bitcoin` is
+/// `plain` over double SHA-256. The padding says what the last node of a
+/// level with an odd count is paired with, itself by default, which
+/// `bitcoin` requires. `rfc6962` is `merkle` carrying that node up unpaired,
+/// the trees of Certificate Transparency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scheme {
+    pub algorithm: String,
+    pub profile: String,
+    pub padding: Padding,
+}
+
+impl Scheme {
+    /// Reads `--algorithm`, `--profile` and `--padding`, defaulting to
+    /// SHA-256, the `merkle` profile and duplicating odd nodes.
+    pub fn from_args(args: &Args) -> Result<Scheme, Failure> {
+        let profile = args.option("profile").unwrap_or(DEFAULT_PROFILE);
+        let algorithm = match (args.option("algorithm"), profile) {
+            (Some(algorithm), "bitcoin") if algorithm != "double-sha256" => {
+                return Err(Failure::usage(
+                    "the bitcoin profile hashes with double-sha256",
+                ))
+            }
+            (Some(algorithm), _) => algorithm,
+            (None, "bitcoin") => "double-sha256",
+            (None, _) => DEFAULT_ALGORITHM,
+        };
+        let padding = match (args.option("padding"), profile) {
+            (Some(name), _) => parse_padding(name)?,
+            (None, "rfc6962") => Padding::Promote,
+            (None, _) => Padding::Duplicate,
+        };
+        match (profile, padding) {
+            ("bitcoin", padding) if padding != Padding::Duplicate => {
+                return Err(Failure::usage(
+                    "the bitcoin profile pairs odd nodes with themselves",
+                ))
+            }
+            ("rfc6962", padding) if padding != Padding::Promote => {
+                return Err(Failure::usage(
+                    "the rfc6962 profile carries odd nodes up unpaired",
+                ))
+            }
+            _ => {}
+        }
+
+        Ok(Scheme {
+            algorithm: algorithm.to_string(),
+            profile: profile.to_string(),
+            padding,
+        })
+    }
+
+    /// Fails if `args` ask for another scheme than this one, which `what` was
+    /// saved with.
+    pub fn check_args(&self, args: &Args, what: &str) -> Result<(), Failure> {
+        let conflicts = args
+            .option("algorithm")
+            .is_some_and(|a| a != self.algorithm)
+            || args.option("profile").is_some_and(|p| p != self.profile)
+            || args
+                .option("padding")
+                .is_some_and(|p| parse_padding(p).map_or(true, |p| p != self.padding));
+        if conflicts {
+            return Err(Failure::usage(format!("{} was made with {}", what, self)));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.algorithm,
+            self.profile,
+            padding_name(self.padding)
+        )
+    }
+}
+
+/// Reads a padding named as `padding_name` names it.
+pub fn parse_padding(name: &str) -> Result<Padding, Failure> {
+    match name {
+        "duplicate" => Ok(Padding::Duplicate),
+        "empty" => Ok(Padding::Empty),
+        "reject" => Ok(Padding::Reject),
+        "promote" => Ok(Padding::Promote),
+        _ => {
+            let hash = name
+                .strip_prefix("constant:")
+                .ok_or_else(|| Failure::usage(format!("unknown padding `{}`", name)))?;
+            let hash = hex::decode(hash)?;
+            match Sentinel::try_new(&hash) {
+                Ok(sentinel) if !hash.is_empty() => Ok(Padding::Constant(sentinel)),
+                _ => Err(Failure::usage("padding constants hold 1 to 64 bytes")),
+            }
+        }
+    }
+}
+
+/// Names `padding` as the `--padding` option takes it: `duplicate`,
+/// `empty`, `reject`, `promote` or `constant:<hex>`.
+pub fn padding_name(padding: Padding) -> String {
+    match padding {
+        Padding::Duplicate => "duplicate".to_string(),
+        Padding::Empty => "empty".to_string(),
+        Padding::Constant(sentinel) => format!("constant:{}", hex::encode(sentinel.as_bytes())),
+        Padding::Reject => "reject".to_string(),
+        Padding::Promote => "promote".to_string(),
+    }
+}
+
+/// Runs `command` with the hasher `scheme` stands for.
+pub fn dispatch<C: Command>(scheme: &Scheme, command: C) -> Result<(), Failure> {
+    match scheme.algorithm.as_str() {
+        "sha224" => with_profile::<Sha224, C>(scheme, command),
+        "sha256" => with_profile::<Sha256, C>(scheme, command),
+        "sha384" => with_profile::<Sha384, C>(scheme, command),
+        "sha512" => with_profile::<Sha512, C>(scheme, command),
+        "sha3" => with_profile::<Sha3_256, C>(scheme, command),
+        "blake3" => with_profile::<blake3::Hasher, C>(scheme, command),
+        "double-sha256" => with_profile::<DoubleSha256, C>(scheme, command),
+        other => Err(Failure::usage(format!("unknown algorithm `{}`", other))),
+    }
+}
+
+fn with_profile<D, C>(scheme: &Scheme, command: C) -> Result<(), Failure>
+where
+    D: Update + FixedOutput + Reset + Clone + Default + Send,
+    C: Command,
+{
+    match scheme.profile.as_str() {
+        "merkle" | "rfc6962" => command.run::<D>(),
+        "plain" | "bitcoin" => command.run::<Plain<D>>(),
+        other => Err(Failure::usage(format!("unknown profile `{}`", other))),
+    }
+}
+
+/// Drops the first byte fed to `D` after every reset, which is the prefix
+/// telling leaves and internal nodes apart.
+#[derive(Clone, Default)]
+pub struct Plain<D> {
+    inner: D,
+    started: bool,
+}
+
+impl<D: Update> Update for Plain<D> {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        let mut data = data.as_ref();
+        if !self.started && !data.is_empty() {
+            self.started = true;
+            data = &data[1..];
+        }
+        self.inner.update(data);
+    }
+}
+
+#[allow(deprecated)]
+impl<D: FixedOutput> FixedOutput for Plain<D> {
+    type OutputSize = D::OutputSize;
+
+    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        self.inner.finalize_into(out);
+    }
+
+    fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        self.inner.finalize_into_reset(out);
+        self.started = false;
+    }
+}
+
+impl<D: Reset> Reset for Plain<D> {
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.started = false;
+    }
+}
+
+/// SHA-256 applied twice, as Bitcoin hashes its trees.
+#[derive(Clone, Default)]
+pub struct DoubleSha256 {
+    inner: Sha256,
+}
+
+impl Update for DoubleSha256 {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        Update::update(&mut self.inner, data);
+    }
+}
+
+#[allow(deprecated)]
+impl FixedOutput for DoubleSha256 {
+    type OutputSize = <Sha256 as FixedOutput>::OutputSize;
+
+    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        *out = Sha256::digest(&self.inner.finalize_fixed());
+    }
+
+    fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        *out = Sha256::digest(&self.inner.finalize_fixed_reset());
+    }
+}
+
+impl Reset for DoubleSha256 {
+    fn reset(&mut self) {
+        Reset::reset(&mut self.inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::MerkleTree;
+
+    use super::super::tests::args;
+    use super::*;
+
+    fn parse(options: &[&str]) -> Args {
+        Args::parse(args(options), &["algorithm", "profile", "padding"]).unwrap()
+    }
+
+    fn scheme(options: &[&str]) -> Result<Scheme, Failure> {
+        Scheme::from_args(&parse(options))
+    }
+
+    #[test]
+    fn reads_algorithm_and_profile() {
+        let default = scheme(&[]).unwrap();
+        assert_eq!(default.to_string(), "sha256 merkle duplicate");
+        let bitcoin = scheme(&["--profile", "bitcoin"]).unwrap();
+        assert_eq!(bitcoin.to_string(), "double-sha256 bitcoin duplicate");
+        assert!(scheme(&["--profile", "bitcoin", "--algorithm", "sha256"]).is_err());
+
+        let sha512 = parse(&["--algorithm", "sha512"]);
+        assert!(default.check_args(&sha512, "manifest").is_err());
+        assert!(default.check_args(&parse(&[]), "manifest").is_ok());
+    }
+
+    #[test]
+    fn reads_padding() {
+        for name in ["duplicate", "empty", "reject", "promote", "constant:00ff"] {
+            let scheme = scheme(&["--padding", name]).unwrap();
+            assert_eq!(padding_name(scheme.padding), name);
+            assert!(scheme
+                .check_args(&parse(&["--padding", name]), "manifest")
+                .is_ok());
+            assert_eq!(parse_padding(name).unwrap(), scheme.padding);
+        }
+        assert_eq!(
+            scheme(&["--padding", "constant:00ff"]).unwrap().padding,
+            Padding::Constant(Sentinel::new(&[0, 0xff]))
+        );
+
+        for name in [
+            "zero",
+            "constant:",
+            "constant:zz",
+            &format!("constant:{}", "00".repeat(65)),
+        ] {
+            assert!(scheme(&["--padding", name]).is_err(), "{}", name);
+        }
+        assert!(scheme(&["--profile", "bitcoin", "--padding", "empty"]).is_err());
+        assert!(scheme(&["--profile", "rfc6962", "--padding", "duplicate"]).is_err());
+        let rfc6962 = scheme(&["--profile", "rfc6962"]).unwrap();
+        assert_eq!(rfc6962.to_string(), "sha256 rfc6962 promote");
+
+        let default = scheme(&[]).unwrap();
+        assert!(default
+            .check_args(&parse(&["--padding", "empty"]), "manifest")
+            .is_err());
+        assert!(default
+            .check_args(&parse(&["--padding", "zero"]), "manifest")
+            .is_err());
+    }
+
+    #[test]
+    fn plain_profile_hashes_without_prefixes() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b"], Plain::<Sha256>::default());
+        let mut node = Sha256::digest(b"a").to_vec();
+        node.extend_from_slice(&Sha256::digest(b"b"));
+        assert_eq!(tree.root_hash(), &Sha256::digest(&node).to_vec());
+    }
+
+    #[test]
+    fn double_sha256_hashes_twice() {
+        let mut hasher = DoubleSha256::default();
+        Update::update(&mut hasher, b"abc");
+        let digest = hasher.finalize_fixed();
+        assert_eq!(digest, Sha256::digest(&Sha256::digest(b"abc")));
+    }
+
+    #[test]
+    fn rejects_unknown_schemes() {
+        struct Nothing;
+        impl Command for Nothing {
+            fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+                Ok(())
+            }
+        }
+
+        for (algorithm, profile) in [("md5", "merkle"), ("sha256", "x")] {
+            let scheme = Scheme {
+                algorithm: algorithm.to_string(),
+                profile: profile.to_string(),
+                padding: Padding::Duplicate,
+            };
+            assert_eq!(dispatch(&scheme, Nothing).unwrap_err().code, 2);
+        }
+        assert!(dispatch(&scheme(&["--algorithm", "blake3"]).unwrap(), Nothing).is_ok());
+    }
+}
@@ -0,0 +1,473 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use digest::Digest;
+use merkle_rs::{Hash, MerkleTree};
+use serde_json::json;
+
+use super::args::Args;
+use super::manifest::Manifest;
+use super::output::Format;
+use super::scheme::{self, Command, Scheme};
+use super::snapshot::{self, Encoding};
+use super::{hex, note, proof, Failure};
+
+/// Most bytes read of one request, request line and headers included.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// How long a client may take to send its request or read the answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most connections answered at once. Clients beyond that are turned away
+/// until one finishes.
+const MAX_CONNECTIONS: usize = 64;
+
+struct Serve {
+    manifest: Manifest,
+    /// Root saved along with the snapshot, if its encoding has one.
+    root: Option<Hash>,
+    listen: String,
+}
+
+/// What a request is answered with: a status line, a content type and a body.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: &'static str, message: String) -> Response {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: (message + "\n").into_bytes(),
+        }
+    }
+
+    fn not_found() -> Response {
+        Response::error(
+            "404 Not Found",
+            "try /root, /proof/<index>, /proof/leaf/<hash> or /consistency/<count>".to_string(),
+        )
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Reads the request line and headers of one request and returns its method
+/// and target, or `None` for a malformed request or one longer than
+/// `MAX_REQUEST_BYTES`.
+fn read_request<R: Read>(stream: R) -> std::io::Result<Option<(String, String)>> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let request = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => Some((method.to_string(), target.to_string())),
+        _ => None,
+    };
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            // Out of bytes before the blank line: either the client closed
+            // its side early or the headers ran past the limit.
+            return Ok(request.filter(|_| reader.get_ref().limit() > 0));
+        }
+        if !header.ends_with('\n') {
+            return Ok(None);
+        }
+        if header.trim_end().is_empty() {
+            return Ok(request);
+        }
+    }
+}
+
+impl Serve {
+    /// Answers one request on `stream`. A client that goes away or stalls
+    /// mid-request only loses its own answer.
+    fn handle<H: Digest>(&self, tree: &Mutex<MerkleTree<H>>, mut stream: TcpStream) {
+        if stream.set_read_timeout(Some(TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+        {
+            return;
+        }
+
+        let response = match read_request(&stream) {
+            Ok(Some((method, target))) => self.respond(&tree.lock().unwrap(), &method, &target),
+            Ok(None) => Response::error("400 Bad Request", "malformed request".to_string()),
+            Err(_) => return,
+        };
+        let _ = response.write_to(&mut stream);
+    }
+
+    fn respond<H: Digest>(&self, tree: &MerkleTree<H>, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error("405 Method Not Allowed", "only GET is served".to_string());
+        }
+
+        let (path, query) = match target.find('?') {
+            Some(at) => (&target[..at], &target[at + 1..]),
+            None => (target, ""),
+        };
+        let format = match query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("format="))
+        {
+            None => Format::Json,
+            Some(name) => match Format::parse(name) {
+                Some(format) => format,
+                None => {
+                    return Response::error("400 Bad Request", format!("unknown format `{}`", name))
+                }
+            },
+        };
+
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let result = match segments.as_slice() {
+            ["root"] => Ok(self.root(tree, format)),
+            ["proof", "leaf", leaf] => match hex::decode(leaf) {
+                Ok(leaf) => tree
+                    .index_of(&leaf)
+                    .map(|position| self.proof(tree, position, format))
+                    .ok_or_else(|| {
+                        Response::error("404 Not Found", "no leaf has that hash".to_string())
+                    }),
+                Err(_) => Err(Response::error(
+                    "400 Bad Request",
+                    "leaf hashes are given in hex".to_string(),
+                )),
+            },
+            ["proof", index] => match index.parse::<usize>() {
+                Ok(index) if index < tree.count_leaves() => Ok(self.proof(tree, index, format)),
+                _ => Err(Response::error(
+                    "404 Not Found",
+                    format!(
+                        "index {} does not relate to any of the {} leaves",
+                        index,
+                        tree.count_leaves()
+                    ),
+                )),
+            },
+            ["consistency", count] => match count.parse::<usize>() {
+                Ok(count) if count > 0 && count <= tree.count_leaves() => {
+                    self.consistency(tree, count, format)
+                }
+                _ => Err(Response::error(
+                    "404 Not Found",
+                    format!(
+                        "count {} is not between 1 and the {} leaves",
+                        count,
+                        tree.count_leaves()
+                    ),
+                )),
+            },
+            _ => Err(Response::not_found()),
+        };
+
+        result.unwrap_or_else(|error| error)
+    }
+
+    fn root<H: Digest>(&self, tree: &MerkleTree<H>, format: Format) -> Response {
+        let root = tree.root_hash();
+        respond(
+            format.encode(root, || {
+                json!({
+                    "algorithm": self.manifest.scheme.algorithm,
+                    "profile": self.manifest.scheme.profile,
+                    "padding": scheme::padding_name(self.manifest.scheme.padding),
+                    "count_leaves": tree.count_leaves(),
+                    "root": hex::encode(root),
+                })
+            }),
+            format,
+        )
+    }
+
+    fn proof<H: Digest>(&self, tree: &MerkleTree<H>, position: usize, format: Format) -> Response {
+        let proof = tree.gen_proof(position);
+        let (name, leaf) = &self.manifest.leaves[position];
+        respond(
+            format.encode(&proof.to_bytes(), || {
+                proof::describe(&proof, &self.manifest.scheme, tree.root_hash(), name, leaf)
+            }),
+            format,
+        )
+    }
+
+    fn consistency<H: Digest>(
+        &self,
+        tree: &MerkleTree<H>,
+        count: usize,
+        format: Format,
+    ) -> Result<Response, Response> {
+        let old = Manifest {
+            scheme: self.manifest.scheme.clone(),
+            leaves: self.manifest.leaves[..count].to_vec(),
+        };
+        let old = old
+            .tree::<H>()
+            .map_err(|failure| Response::error("404 Not Found", failure.message))?;
+        let proof = tree.consistency_proof(count);
+        Ok(respond(
+            format.encode(&proof.to_bytes(), || {
+                json!({
+                    "algorithm": self.manifest.scheme.algorithm,
+                    "profile": self.manifest.scheme.profile,
+                    "padding": scheme::padding_name(self.manifest.scheme.padding),
+                    "old_count_leaves": proof.old_count_leaves(),
+                    "count_leaves": proof.count_leaves(),
+                    "old_root": hex::encode(old.root_hash()),
+                    "new_root": hex::encode(tree.root_hash()),
+                    "hashes": proof.hashes().iter().map(|h| hex::encode(h)).collect::<Vec<_>>(),
+                })
+            }),
+            format,
+        ))
+    }
+}
+
+fn respond(body: Vec<u8>, format: Format) -> Response {
+    Response {
+        status: "200 OK",
+        content_type: match format {
+            Format::Json => "application/json",
+            Format::Binary => "application/octet-stream",
+            Format::Hex | Format::Base64 => "text/plain",
+        },
+        body,
+    }
+}
+
+impl Command for Serve {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let mut tree = self.manifest.tree::<H>()?;
+        if let Some(root) = &self.root {
+            if root != tree.root_hash() {
+                return Err(Failure::invalid(format!(
+                    "leaves hash to root {}, not to the saved {}",
+                    hex::encode(tree.root_hash()),
+                    hex::encode(root)
+                )));
+            }
+        }
+        tree.set_indexed(true);
+
+        let listener = TcpListener::bind(&self.listen)
+            .map_err(|e| Failure::error(format!("{}: {}", self.listen, e)))?;
+        note(format_args!(
+            "serving {} leaves with root {} on {}",
+            tree.count_leaves(),
+            hex::encode(tree.root_hash()),
+            listener.local_addr()?
+        ));
+
+        // Requests only read the tree, but hooks keep it from being shared
+        // between threads without a lock.
+        let tree = Mutex::new(tree);
+        let active = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        note(format_args!("merkle: {}", e));
+                        continue;
+                    }
+                };
+                if active.load(Ordering::Acquire) >= MAX_CONNECTIONS {
+                    let _ = stream.set_write_timeout(Some(TIMEOUT));
+                    let busy = "too many connections, try again later".to_string();
+                    let _ = Response::error("503 Service Unavailable", busy).write_to(&mut stream);
+                    continue;
+                }
+
+                active.fetch_add(1, Ordering::AcqRel);
+                let (serve, tree, active) = (&self, &tree, &active);
+                scope.spawn(move || {
+                    serve.handle(tree, stream);
+                    active.fetch_sub(1, Ordering::AcqRel);
+                });
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Reads the tree `--snapshot` names and where to listen.
+fn open(args: &Args) -> Result<Serve, Failure> {
+    if !args.positional().is_empty() {
+        return Err(Failure::usage("serve takes no inputs, give --snapshot"));
+    }
+    let path = match args.option("snapshot") {
+        Some(path) => path,
+        None => return Err(Failure::usage("missing --snapshot")),
+    };
+    let bytes = fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+    let encoding = Encoding::detect(&bytes);
+    let (manifest, root) = snapshot::decode(&bytes, path, encoding, &Scheme::from_args(args)?)?;
+    if encoding != Encoding::Leaves {
+        manifest.scheme.check_args(args, path)?;
+    }
+    // `:8080` listens on every interface, as in most servers.
+    let listen = match args.option("listen").unwrap_or("127.0.0.1:8080") {
+        listen if listen.starts_with(':') => format!("0.0.0.0{}", listen),
+        listen => listen.to_string(),
+    };
+
+    Ok(Serve {
+        manifest,
+        root,
+        listen,
+    })
+}
+
+/// `merkle serve --snapshot <file> [--listen <addr>]`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &["snapshot", "listen", "algorithm", "profile", "padding"],
+    )?;
+    let serve = open(&args)?;
+    let scheme = serve.manifest.scheme.clone();
+    scheme::dispatch(&scheme, serve)
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::{leaf_hash_with_hasher, ConsistencyProof, Padding, Proof};
+    use serde_json::Value;
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    fn serve() -> Serve {
+        let leaves = ["a", "b", "c"]
+            .iter()
+            .map(|v| (v.to_string(), leaf_hash_with_hasher(v, Sha256::default())))
+            .collect();
+        Serve {
+            manifest: Manifest {
+                scheme: Scheme {
+                    algorithm: "sha256".to_string(),
+                    profile: "merkle".to_string(),
+                    padding: Padding::Duplicate,
+                },
+                leaves,
+            },
+            root: None,
+            listen: String::new(),
+        }
+    }
+
+    fn get(target: &str) -> Response {
+        let serve = serve();
+        let mut tree = serve.manifest.tree::<Sha256>().unwrap();
+        tree.set_indexed(true);
+        serve.respond(&tree, "GET", target)
+    }
+
+    fn json(response: &Response) -> Value {
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(response.content_type, "application/json");
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn answers_root_and_proofs() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        assert_eq!(json(&get("/root"))["root"], hex::encode(tree.root_hash()));
+
+        let proof = Proof::from_json(&json(&get("/proof/1"))).unwrap();
+        assert_eq!(proof, tree.gen_proof(1));
+        let leaf = hex::encode(tree.leaf(2).unwrap());
+        let proof = Proof::from_json(&json(&get(&format!("/proof/leaf/{}", leaf)))).unwrap();
+        assert_eq!(proof, tree.gen_proof(2));
+
+        let response = get("/consistency/2?format=binary");
+        assert_eq!(response.content_type, "application/octet-stream");
+        let proof = ConsistencyProof::from_bytes(&response.body).unwrap();
+        assert_eq!(proof, tree.consistency_proof(2));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(get("/proof/3").status, "404 Not Found");
+        assert_eq!(get("/proof/leaf/00").status, "404 Not Found");
+        assert_eq!(get("/proof/leaf/zz").status, "400 Bad Request");
+        assert_eq!(get("/consistency/0").status, "404 Not Found");
+        assert_eq!(get("/root?format=yaml").status, "400 Bad Request");
+        assert_eq!(get("/").status, "404 Not Found");
+
+        let serve = serve();
+        let tree = serve.manifest.tree::<Sha256>().unwrap();
+        assert_eq!(
+            serve.respond(&tree, "POST", "/root").status,
+            "405 Method Not Allowed"
+        );
+    }
+
+    #[test]
+    fn reads_requests_within_the_limit() {
+        let request = b"GET /root HTTP/1.1\r\nHost: x\r\n\r\n";
+        assert_eq!(
+            read_request(&request[..]).unwrap(),
+            Some(("GET".to_string(), "/root".to_string()))
+        );
+
+        assert_eq!(read_request(&b"GET /root"[..]).unwrap(), None);
+        let endless = vec![b'a'; 2 * MAX_REQUEST_BYTES as usize];
+        assert_eq!(read_request(&endless[..]).unwrap(), None);
+        let headers = [
+            &b"GET /root HTTP/1.1\r\n"[..],
+            &b"X-Padding: 0\r\n".repeat(MAX_REQUEST_BYTES as usize / 8),
+        ]
+        .concat();
+        assert_eq!(read_request(&headers[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn serves_any_snapshot_encoding() {
+        let scratch = Scratch::new("serve-snapshot");
+        let manifest = serve().manifest;
+        let root = manifest.tree::<Sha256>().unwrap().root_hash().clone();
+        for (name, encoding) in [
+            ("tree.bin", Encoding::Binary),
+            ("tree.json", Encoding::Json),
+        ] {
+            let bytes = snapshot::encode(&manifest, &root, encoding).unwrap();
+            let path = scratch.write(name, &bytes);
+            let args = Args::parse(
+                args(&["--snapshot", &path, "--listen", ":0"]),
+                &["snapshot", "listen"],
+            )
+            .unwrap();
+
+            let opened = open(&args).unwrap();
+            assert_eq!(opened.manifest.leaves, manifest.leaves);
+            assert_eq!(opened.root, Some(root.clone()));
+            assert_eq!(opened.listen, "0.0.0.0:0");
+        }
+    }
+}
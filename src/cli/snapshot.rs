@@ -0,0 +1,299 @@
+use merkle_rs::{Hash, Padding};
+use serde_json::{json, Value};
+
+use super::manifest::{self, Manifest};
+use super::scheme::{self, Scheme};
+use super::{hex, Failure};
+
+const MAGIC: &[u8] = b"merkle-snapshot\x02";
+
+/// Binary snapshots saved before they recorded a padding, which all
+/// duplicate odd nodes.
+const MAGIC_V1: &[u8] = b"merkle-snapshot\x01";
+
+/// The ways a saved tree can be written down for other machines and tools.
+///
+/// Manifests are what the other commands read. Binary and JSON snapshots
+/// carry the same leaves along with the root, so a copy can be checked on
+/// arrival, and leaf lists hold nothing but one hex leaf hash per line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Manifest,
+    Binary,
+    Json,
+    Leaves,
+}
+
+impl Encoding {
+    pub fn parse(name: &str) -> Option<Encoding> {
+        match name {
+            "manifest" => Some(Encoding::Manifest),
+            "binary" => Some(Encoding::Binary),
+            "json" => Some(Encoding::Json),
+            "leaves" => Some(Encoding::Leaves),
+            _ => None,
+        }
+    }
+
+    /// Tells the encoding of `bytes` from how they start.
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(MAGIC) || bytes.starts_with(MAGIC_V1) {
+            Encoding::Binary
+        } else if bytes.trim_ascii_start().starts_with(b"{") {
+            Encoding::Json
+        } else if bytes.starts_with(manifest::HEADER.as_bytes()) {
+            Encoding::Manifest
+        } else {
+            Encoding::Leaves
+        }
+    }
+}
+
+/// Writes `manifest`, whose tree has `root`, in `encoding`.
+pub fn encode(manifest: &Manifest, root: &[u8], encoding: Encoding) -> Result<Vec<u8>, Failure> {
+    match encoding {
+        Encoding::Manifest => Ok(manifest.to_text()?.into_bytes()),
+        Encoding::Binary => {
+            let mut bytes = MAGIC.to_vec();
+            let mut put = |field: &[u8]| {
+                bytes.extend_from_slice(&(field.len() as u64).to_le_bytes());
+                bytes.extend_from_slice(field);
+            };
+            put(manifest.scheme.algorithm.as_bytes());
+            put(manifest.scheme.profile.as_bytes());
+            put(scheme::padding_name(manifest.scheme.padding).as_bytes());
+            put(root);
+            put(&(manifest.leaves.len() as u64).to_le_bytes());
+            for (name, leaf) in &manifest.leaves {
+                put(name.as_bytes());
+                put(leaf);
+            }
+            Ok(bytes)
+        }
+        Encoding::Json => {
+            let leaves: Vec<Value> = manifest
+                .leaves
+                .iter()
+                .map(|(name, leaf)| json!({ "name": name, "hash": hex::encode(leaf) }))
+                .collect();
+            let value = json!({
+                "algorithm": manifest.scheme.algorithm,
+                "profile": manifest.scheme.profile,
+                "padding": scheme::padding_name(manifest.scheme.padding),
+                "count_leaves": manifest.leaves.len(),
+                "root": hex::encode(root),
+                "leaves": leaves,
+            });
+            Ok((serde_json::to_string_pretty(&value).unwrap() + "\n").into_bytes())
+        }
+        Encoding::Leaves => Ok(manifest
+            .leaves
+            .iter()
+            .map(|(_, leaf)| hex::encode(leaf) + "\n")
+            .collect::<String>()
+            .into_bytes()),
+    }
+}
+
+/// Reads a tree written by `encode`, naming it `path` in errors, along with
+/// the root saved with it if any.
+///
+/// Leaf lists do not say how they were hashed, so they take `scheme`, and
+/// their leaves are named after their positions.
+pub fn decode(
+    bytes: &[u8],
+    path: &str,
+    encoding: Encoding,
+    scheme: &Scheme,
+) -> Result<(Manifest, Option<Hash>), Failure> {
+    let invalid = || Failure::error(format!("{}: invalid snapshot", path));
+    let text = || std::str::from_utf8(bytes).map_err(|_| invalid());
+
+    match encoding {
+        Encoding::Manifest => Ok((Manifest::parse(text()?, path)?, None)),
+        Encoding::Binary => {
+            let (mut rest, v1) = match bytes.strip_prefix(MAGIC) {
+                Some(rest) => (rest, false),
+                None => (bytes.strip_prefix(MAGIC_V1).ok_or_else(invalid)?, true),
+            };
+            let mut take = || -> Result<&[u8], Failure> {
+                if rest.len() < 8 {
+                    return Err(invalid());
+                }
+                let (len, tail) = rest.split_at(8);
+                let len = u64::from_le_bytes(len.try_into().unwrap());
+                if len > tail.len() as u64 {
+                    return Err(invalid());
+                }
+                let (field, tail) = tail.split_at(len as usize);
+                rest = tail;
+                Ok(field)
+            };
+            let mut string = || -> Result<String, Failure> {
+                String::from_utf8(take()?.to_vec()).map_err(|_| invalid())
+            };
+
+            let (algorithm, profile) = (string()?, string()?);
+            let padding = match v1 {
+                true => Padding::Duplicate,
+                false => scheme::parse_padding(&string()?).map_err(|_| invalid())?,
+            };
+            let scheme = Scheme {
+                algorithm,
+                profile,
+                padding,
+            };
+            let root = take()?.to_vec();
+            let count = u64::from_le_bytes(take()?.try_into().map_err(|_| invalid())?);
+            let mut leaves = Vec::new();
+            for _ in 0..count {
+                let name = String::from_utf8(take()?.to_vec()).map_err(|_| invalid())?;
+                leaves.push((name, take()?.to_vec()));
+            }
+            if !rest.is_empty() {
+                return Err(invalid());
+            }
+
+            Ok((Manifest { scheme, leaves }, Some(root)))
+        }
+        Encoding::Json => {
+            let value: Value = serde_json::from_slice(bytes)
+                .map_err(|e| Failure::error(format!("{}: invalid snapshot: {}", path, e)))?;
+            let string = |v: &Value| v.as_str().map(str::to_string).ok_or_else(invalid);
+
+            let padding = match &value["padding"] {
+                Value::Null => Padding::Duplicate,
+                name => scheme::parse_padding(&string(name)?).map_err(|_| invalid())?,
+            };
+            let scheme = Scheme {
+                algorithm: string(&value["algorithm"])?,
+                profile: string(&value["profile"])?,
+                padding,
+            };
+            let root = hex::decode(&string(&value["root"])?)?;
+            let leaves = value["leaves"]
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|leaf| {
+                    Ok((
+                        string(&leaf["name"])?,
+                        hex::decode(&string(&leaf["hash"])?)?,
+                    ))
+                })
+                .collect::<Result<_, Failure>>()?;
+
+            Ok((Manifest { scheme, leaves }, Some(root)))
+        }
+        Encoding::Leaves => {
+            let leaves = text()?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(n, line)| Ok((format!("#{}", n), hex::decode(line)?)))
+                .collect::<Result<_, Failure>>()?;
+
+            Ok((
+                Manifest {
+                    scheme: scheme.clone(),
+                    leaves,
+                },
+                None,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::Sentinel;
+
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            scheme: Scheme {
+                algorithm: "sha512".to_string(),
+                profile: "plain".to_string(),
+                padding: Padding::Constant(Sentinel::new(&[7; 4])),
+            },
+            leaves: vec![
+                ("a".to_string(), vec![1; 4]),
+                ("b/c".to_string(), vec![2; 4]),
+            ],
+        }
+    }
+
+    #[test]
+    fn every_encoding_round_trips_and_is_detected() {
+        let manifest = manifest();
+        for encoding in [Encoding::Manifest, Encoding::Binary, Encoding::Json] {
+            let bytes = encode(&manifest, &[9; 4], encoding).unwrap();
+            assert_eq!(Encoding::detect(&bytes), encoding);
+
+            let other = Scheme {
+                algorithm: "sha256".to_string(),
+                profile: "merkle".to_string(),
+                padding: Padding::Duplicate,
+            };
+            let (decoded, root) = decode(&bytes, "snapshot", encoding, &other).unwrap();
+            assert_eq!(decoded.scheme, manifest.scheme, "{:?}", encoding);
+            assert_eq!(decoded.leaves, manifest.leaves, "{:?}", encoding);
+            let expected_root = (encoding != Encoding::Manifest).then(|| vec![9; 4]);
+            assert_eq!(root, expected_root, "{:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn leaf_lists_take_the_given_scheme() {
+        let manifest = manifest();
+        let bytes = encode(&manifest, &[9; 4], Encoding::Leaves).unwrap();
+        assert_eq!(Encoding::detect(&bytes), Encoding::Leaves);
+
+        let (decoded, root) = decode(&bytes, "leaves", Encoding::Leaves, &manifest.scheme).unwrap();
+        assert_eq!(root, None);
+        assert_eq!(
+            decoded.leaves,
+            vec![
+                ("#0".to_string(), vec![1; 4]),
+                ("#1".to_string(), vec![2; 4])
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_binary_snapshot_is_invalid() {
+        let bytes = encode(&manifest(), &[9; 4], Encoding::Binary).unwrap();
+        for len in [MAGIC.len(), bytes.len() - 1] {
+            assert!(decode(&bytes[..len], "s", Encoding::Binary, &manifest().scheme).is_err());
+        }
+    }
+
+    #[test]
+    fn unknown_paddings_are_invalid_and_missing_ones_duplicate() {
+        let mut manifest = manifest();
+        manifest.scheme.padding = Padding::Empty;
+        let binary = encode(&manifest, &[9; 4], Encoding::Binary).unwrap();
+        let json = String::from_utf8(encode(&manifest, &[9; 4], Encoding::Json).unwrap()).unwrap();
+
+        let mut unknown = binary.clone();
+        let at = unknown.windows(5).position(|w| w == b"empty").unwrap();
+        unknown[at..at + 5].copy_from_slice(b"zzzzz");
+        assert!(decode(&unknown, "s", Encoding::Binary, &manifest.scheme).is_err());
+        let unknown = json.replace("\"empty\"", "\"zzzzz\"");
+        assert!(decode(unknown.as_bytes(), "s", Encoding::Json, &manifest.scheme).is_err());
+
+        let mut v1 = MAGIC_V1.to_vec();
+        v1.extend_from_slice(&binary[MAGIC.len()..at - 8]);
+        v1.extend_from_slice(&binary[at + 5..]);
+        assert_eq!(Encoding::detect(&v1), Encoding::Binary);
+        let (decoded, _) = decode(&v1, "s", Encoding::Binary, &manifest.scheme).unwrap();
+        assert_eq!(decoded.scheme.padding, Padding::Duplicate);
+        assert_eq!(decoded.leaves, manifest.leaves);
+
+        let unpadded = json.replace("\"padding\": \"empty\",", "");
+        let (decoded, _) =
+            decode(unpadded.as_bytes(), "s", Encoding::Json, &manifest.scheme).unwrap();
+        assert_eq!(decoded.scheme.padding, Padding::Duplicate);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+
+use digest::Digest;
+use merkle_rs::Proof;
+
+use super::args::Args;
+use super::scheme::{self, Command, Scheme};
+use super::{base64, hex, note, proof, Failure};
+
+struct Verify {
+    root: Vec<u8>,
+    proof: Proof,
+    leaf: Vec<u8>,
+}
+
+impl Command for Verify {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        if self
+            .proof
+            .validate_with_hasher(&self.root, &self.leaf, H::new())
+        {
+            note(format_args!(
+                "ok: leaf {} of {} is part of the tree",
+                self.proof.position(),
+                self.proof.count_leaves()
+            ));
+            Ok(())
+        } else {
+            Err(Failure::invalid(format!(
+                "leaf {} of {} does not lead to root {}",
+                self.proof.position(),
+                self.proof.count_leaves(),
+                hex::encode(&self.root)
+            )))
+        }
+    }
+}
+
+/// `merkle verify --root <root> --proof <file> (--leaf <file> | --value <text>)`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &[
+            "algorithm",
+            "profile",
+            "padding",
+            "root",
+            "proof",
+            "leaf",
+            "value",
+        ],
+    )?;
+    if !args.positional().is_empty() {
+        return Err(Failure::usage("verify takes no inputs"));
+    }
+
+    let root = args
+        .option("root")
+        .ok_or_else(|| Failure::usage("missing --root"))?;
+    let root = hex::decode(root).or_else(|_| base64::decode(root))?;
+
+    let path = args
+        .option("proof")
+        .ok_or_else(|| Failure::usage("missing --proof"))?;
+    let bytes = fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?;
+    let (proof, proof_scheme) = proof::parse(&bytes)?;
+
+    let leaf = match (args.option("leaf"), args.option("value")) {
+        (Some(path), None) => {
+            fs::read(path).map_err(|e| Failure::error(format!("{}: {}", path, e)))?
+        }
+        (None, Some(value)) => value.as_bytes().to_vec(),
+        _ => return Err(Failure::usage("give one of --leaf or --value")),
+    };
+
+    let scheme = match proof_scheme {
+        Some(scheme) => {
+            scheme.check_args(&args, "proof")?;
+            scheme
+        }
+        None => Scheme::from_args(&args)?,
+    };
+    scheme::dispatch(&scheme, Verify { root, proof, leaf })
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle_rs::MerkleTree;
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    #[test]
+    fn checks_a_saved_proof() {
+        let scratch = Scratch::new("verify");
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let root = hex::encode(tree.root_hash());
+        let proof = scratch.write("proof", &tree.gen_proof(1).to_bytes());
+        let leaf = scratch.write("leaf", b"b");
+
+        run(args(&["--root", &root, "--proof", &proof, "--leaf", &leaf])).unwrap();
+        run(args(&["--root", &root, "--proof", &proof, "--value", "b"])).unwrap();
+        let root64 = base64::encode(tree.root_hash());
+        run(args(&[
+            "--root", &root64, "--proof", &proof, "--value", "b",
+        ]))
+        .unwrap();
+
+        let failure = run(args(&["--root", &root, "--proof", &proof, "--value", "c"]));
+        assert_eq!(failure.unwrap_err().code, 1);
+    }
+
+    #[test]
+    fn rejects_incomplete_command_lines() {
+        let scratch = Scratch::new("verify-usage");
+        let tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = hex::encode(tree.root_hash());
+        let proof = scratch.write("proof", &tree.gen_proof(0).to_bytes());
+
+        for command in [
+            args(&["--proof", &proof, "--value", "a"]),
+            args(&["--root", &root, "--value", "a"]),
+            args(&["--root", &root, "--proof", &proof]),
+            args(&["input", "--root", &root, "--proof", &proof, "--value", "a"]),
+        ] {
+            assert_eq!(run(command).unwrap_err().code, 2);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use digest::Digest;
+use merkle_rs::{leaf_hash_with_hasher, Hash, MerkleTree, Mutation, Padding};
+use serde_json::json;
+
+use super::args::Args;
+use super::dir::{self, File};
+use super::manifest::Manifest;
+use super::output::{self, Format};
+use super::scheme::{self, Command, Scheme};
+use super::{hex, note, Failure};
+
+struct Watch {
+    scheme: Scheme,
+    dir: String,
+    manifest: String,
+    interval: Duration,
+    format: Format,
+}
+
+/// The tree over the watched files, in the order their leaves were added.
+struct State<H> {
+    tree: MerkleTree<H>,
+    files: Vec<File>,
+}
+
+impl<H: Digest> State<H> {
+    fn build(files: Vec<File>, padding: Padding) -> Result<Option<State<H>>, Failure> {
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let contents = files
+            .iter()
+            .map(File::read)
+            .collect::<Result<Vec<_>, _>>()?;
+        let leaves = contents
+            .iter()
+            .map(|c| leaf_hash_with_hasher(c, H::new()))
+            .collect();
+        let mut tree = MerkleTree::build_from_leaves(leaves, H::new());
+        tree.set_padding(padding);
+        Ok(Some(State { tree, files }))
+    }
+
+    /// Brings the tree in line with `scanned`. Removed files are removed from
+    /// the tree, changed ones rehashed in place and new ones appended.
+    fn update(mut self, scanned: Vec<File>) -> Result<Option<State<H>>, Failure> {
+        let mut scanned: HashMap<String, File> =
+            scanned.into_iter().map(|f| (f.name.clone(), f)).collect();
+
+        let mut mutations = Vec::new();
+        for i in (0..self.files.len()).rev() {
+            if !scanned.contains_key(&self.files[i].name) {
+                mutations.push(Mutation::Remove(i));
+                self.files.remove(i);
+            }
+        }
+        if self.files.is_empty() {
+            let mut files: Vec<File> = scanned.into_values().collect();
+            files.sort_by(|a, b| a.name.cmp(&b.name));
+            return State::build(files, self.tree.padding());
+        }
+
+        for (i, file) in self.files.iter_mut().enumerate() {
+            let current = scanned.remove(&file.name).unwrap();
+            if !file.same_stamp(&current) {
+                mutations.push(Mutation::Set(i, current.read()?));
+                *file = current;
+            }
+        }
+
+        let mut added: Vec<File> = scanned.into_values().collect();
+        added.sort_by(|a, b| a.name.cmp(&b.name));
+        for file in added {
+            mutations.push(Mutation::Push(file.read()?));
+            self.files.push(file);
+        }
+
+        self.tree.apply(&mutations);
+        Ok(Some(self))
+    }
+
+    fn manifest(&self, scheme: &Scheme) -> Manifest {
+        Manifest {
+            scheme: scheme.clone(),
+            leaves: self
+                .files
+                .iter()
+                .map(|f| f.name.clone())
+                .zip(self.tree.leaves().cloned())
+                .collect(),
+        }
+    }
+}
+
+impl Command for Watch {
+    fn run<H: Digest + Clone + Send>(self) -> Result<(), Failure> {
+        let padding = self.scheme.padding;
+        let mut state = State::<H>::build(dir::scan(&self.dir)?, padding)?;
+        let mut last_root: Option<Hash> = None;
+
+        loop {
+            let root = state.as_ref().map(|s| s.tree.root_hash().clone());
+            if root != last_root {
+                match &state {
+                    Some(s) => {
+                        s.manifest(&self.scheme).write(&self.manifest)?;
+                        let root = s.tree.root_hash();
+                        output::print(&self.format.encode(root, || {
+                            json!({
+                                "count_leaves": s.tree.count_leaves(),
+                                "root": hex::encode(root),
+                            })
+                        }))?;
+                    }
+                    None => note(format_args!("{} holds no files", self.dir)),
+                }
+                last_root = root;
+            }
+
+            thread::sleep(self.interval);
+            let scanned = dir::scan(&self.dir)?;
+            state = match state {
+                Some(s) => s.update(scanned)?,
+                None => State::build(scanned, padding)?,
+            };
+        }
+    }
+}
+
+/// `merkle watch <dir> --manifest <file>`
+pub fn run(args: Vec<String>) -> Result<(), Failure> {
+    let args = Args::parse(
+        args,
+        &[
+            "algorithm",
+            "profile",
+            "padding",
+            "manifest",
+            "interval",
+            "format",
+        ],
+    )?;
+    let dir = match args.positional() {
+        [dir] => dir.clone(),
+        _ => return Err(Failure::usage("give one directory to watch")),
+    };
+    let manifest = args
+        .option("manifest")
+        .ok_or_else(|| Failure::usage("missing --manifest"))?
+        .to_string();
+    let interval = Duration::from_secs_f64(args.parsed("interval")?.unwrap_or(1.0));
+    let scheme = Scheme::from_args(&args)?;
+    if scheme.padding == Padding::Reject {
+        return Err(Failure::usage(
+            "watch cannot keep the files to a power of 2, which --padding reject needs",
+        ));
+    }
+    let format = Format::from_args(&args, Format::Hex)?;
+
+    scheme::dispatch(
+        &scheme.clone(),
+        Watch {
+            scheme,
+            dir,
+            manifest,
+            interval,
+            format,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use sha2::Sha256;
+
+    use super::super::tests::{args, Scratch};
+    use super::*;
+
+    #[test]
+    fn update_follows_removed_changed_and_added_files() {
+        let scratch = Scratch::new("watch");
+        for name in ["a", "b", "c"] {
+            scratch.write(&format!("dir/{}", name), name.as_bytes());
+        }
+        let dir = scratch.path("dir");
+        let state = State::<Sha256>::build(dir::scan(&dir).unwrap(), Padding::Empty)
+            .unwrap()
+            .unwrap();
+
+        fs::remove_file(scratch.path("dir/b")).unwrap();
+        scratch.write("dir/a", b"longer");
+        scratch.write("dir/sub/d", b"d");
+        let state = state.update(dir::scan(&dir).unwrap()).unwrap().unwrap();
+
+        let names: Vec<&str> = state.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a", "c", "sub/d"]);
+        let mut expected = MerkleTree::build_with_hasher(&["longer", "c", "d"], Sha256::default());
+        expected.set_padding(Padding::Empty);
+        assert_eq!(state.tree.root_hash(), expected.root_hash());
+    }
+
+    #[test]
+    fn empty_directory_has_no_tree() {
+        let scratch = Scratch::new("watch-empty");
+        scratch.write("dir/a", b"a");
+        let dir = scratch.path("dir");
+        let state = State::<Sha256>::build(dir::scan(&dir).unwrap(), Padding::Duplicate).unwrap();
+
+        fs::remove_file(scratch.path("dir/a")).unwrap();
+        let state = state.unwrap().update(dir::scan(&dir).unwrap()).unwrap();
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn requires_one_directory_and_a_manifest() {
+        assert_eq!(run(args(&["--manifest", "m"])).unwrap_err().code, 2);
+        assert_eq!(run(args(&["dir"])).unwrap_err().code, 2);
+        let reject = args(&["dir", "--manifest", "m", "--padding", "reject"]);
+        assert_eq!(run(reject).unwrap_err().code, 2);
+    }
+}
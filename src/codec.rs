@@ -0,0 +1,110 @@
+use crate::Error;
+
+/// Appends fixed-width little-endian integers and length-prefixed byte strings.
+#[derive(Default)]
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    pub(crate) fn u64(&mut self, v: u64) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    /// Writes `v` as a `u64`, so encodings do not depend on the pointer width
+    /// of the writer.
+    pub(crate) fn usize(&mut self, v: usize) {
+        self.u64(v as u64);
+    }
+
+    pub(crate) fn bytes(&mut self, v: &[u8]) {
+        self.usize(v.len());
+        self.bytes.extend_from_slice(v);
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads what `Writer` wrote, failing instead of reading past the end.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if n > self.bytes.len() {
+            return Err(Error::InvalidEncoding);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a `u64` written by `Writer::usize`, failing rather than
+    /// truncating if it does not fit a `usize` of this target.
+    pub(crate) fn usize(&mut self) -> Result<usize, Error> {
+        self.u64()?.try_into().map_err(|_| Error::InvalidEncoding)
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    /// Fails if anything is left unread.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidEncoding)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usize_is_written_as_little_endian_u64() {
+        let mut w = Writer::default();
+        w.usize(0x0102);
+        w.bytes(b"ab");
+        assert_eq!(
+            w.into_bytes(),
+            [2, 1, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b']
+        );
+    }
+
+    #[test]
+    fn usize_above_u32_max_is_not_truncated() {
+        let v = u64::from(u32::MAX) + 1;
+        let mut w = Writer::default();
+        w.u64(v);
+        let bytes = w.into_bytes();
+
+        let read = Reader::new(&bytes).usize();
+        match usize::try_from(v) {
+            Ok(v) => assert_eq!(read, Ok(v)),
+            Err(_) => assert_eq!(read, Err(Error::InvalidEncoding)),
+        }
+    }
+}
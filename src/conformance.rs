@@ -0,0 +1,277 @@
+//! Checks this crate against fixtures produced by other implementations of
+//! the same tree, so interoperability can be tested continuously.
+//!
+//! A fixture is a JSON file describing leaves, the root they hash to and
+//! optionally proofs of some of them. Hashes and leaf values are lowercase
+//! or uppercase hex:
+//!
+//! ```json
+//! {
+//!   "algorithm": "sha256",
+//!   "padding": "duplicate",
+//!   "leaves": ["616263", "646566", "676869"],
+//!   "root": "…",
+//!   "proofs": [
+//!     {
+//!       "position": 2,
+//!       "count_leaves": 3,
+//!       "path": ["duplicate", { "left": "…" }]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - `algorithm` is one of `sha224`, `sha256`, `sha384` and `sha512`, and
+//!   also `sha3` (SHA3-256) and `blake3` when those features are enabled.
+//! - `padding` is `duplicate`, the default, `empty`, `reject`, `promote` or
+//!   `{ "constant": "<hash>" }`, as described on `Padding`.
+//! - Leaves are hashed as `H(0x00 || value)` and internal nodes as
+//!   `H(0x01 || left || right)`.
+//! - Every proof lists the siblings from the leaf up, each `{ "left": … }`,
+//!   `{ "right": … }`, `{ "padding": … }`, `"duplicate"`, `"empty"` or
+//!   `"promoted"`, the same JSON the `merkle` tool writes. A proof with
+//!   `"valid": false` must be rejected for the leaf at its position; other
+//!   proofs must validate and be the proof this crate generates for that
+//!   position.
+//!
+//! Other fields, such as a `description`, are ignored.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use digest::Digest;
+use serde_json::Value;
+
+use crate::{hex, Hash, MerkleTree, Padding, Proof, Sentinel};
+
+/// How a fixture disagrees with this crate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// The fixture could not be read or does not follow the schema.
+    Invalid(String),
+    /// The fixture names a hash function this build does not support.
+    UnsupportedAlgorithm(String),
+    /// The leaves hash to another root than the fixture's.
+    Root { expected: Hash, actual: Hash },
+    /// A proof expected to validate was rejected, or the other way round.
+    Proof { index: usize, expected_valid: bool },
+    /// A valid proof is not the one this crate generates for its position.
+    ProofDiffers { index: usize, actual: Proof },
+}
+
+/// A mismatch found in one fixture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub fixture: PathBuf,
+    pub mismatch: Mismatch,
+}
+
+/// The outcome of running a directory of fixtures.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Fixtures without any mismatch.
+    pub passed: Vec<PathBuf>,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Runs every `.json` file of `dir`, in name order. Fails only if `dir`
+/// cannot be listed; a fixture that cannot be read is reported as
+/// `Mismatch::Invalid`.
+pub fn run_dir(dir: &Path) -> io::Result<Report> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut report = Report::default();
+    for path in paths {
+        let mismatches = match fs::read(&path) {
+            Ok(bytes) => run_fixture(&bytes),
+            Err(e) => vec![Mismatch::Invalid(e.to_string())],
+        };
+        if mismatches.is_empty() {
+            report.passed.push(path);
+        } else {
+            report
+                .failures
+                .extend(mismatches.into_iter().map(|mismatch| Failure {
+                    fixture: path.clone(),
+                    mismatch,
+                }));
+        }
+    }
+    Ok(report)
+}
+
+/// Runs the fixture in `bytes` and returns every mismatch found, none if
+/// this crate agrees with it.
+pub fn run_fixture(bytes: &[u8]) -> Vec<Mismatch> {
+    let fixture: Value = match serde_json::from_slice(bytes) {
+        Ok(fixture) => fixture,
+        Err(e) => return vec![Mismatch::Invalid(e.to_string())],
+    };
+
+    let algorithm = fixture["algorithm"].as_str().unwrap_or_default();
+    let result = match algorithm {
+        "sha224" => run::<sha2::Sha224>(&fixture),
+        "sha256" => run::<sha2::Sha256>(&fixture),
+        "sha384" => run::<sha2::Sha384>(&fixture),
+        "sha512" => run::<sha2::Sha512>(&fixture),
+        #[cfg(feature = "sha3")]
+        "sha3" => run::<sha3::Sha3_256>(&fixture),
+        #[cfg(feature = "blake3")]
+        "blake3" => run::<blake3::Hasher>(&fixture),
+        other => return vec![Mismatch::UnsupportedAlgorithm(other.to_string())],
+    };
+    result.unwrap_or_else(|e| vec![Mismatch::Invalid(e)])
+}
+
+/// A proof of a fixture and whether it must validate.
+struct FixtureProof {
+    proof: Proof,
+    valid: bool,
+}
+
+fn run<H: Digest>(fixture: &Value) -> Result<Vec<Mismatch>, String> {
+    let padding = padding(&fixture["padding"])?;
+    let leaves = fixture["leaves"]
+        .as_array()
+        .ok_or("missing leaves")?
+        .iter()
+        .map(hex)
+        .collect::<Result<Vec<_>, _>>()?;
+    if leaves.is_empty() {
+        return Err("a tree needs at least one leaf".to_string());
+    }
+    let root = hex(&fixture["root"])?;
+    let proofs = match &fixture["proofs"] {
+        Value::Null => Vec::new(),
+        proofs => proofs
+            .as_array()
+            .ok_or("proofs must be a list")?
+            .iter()
+            .map(fixture_proof)
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut tree = MerkleTree::build_with_hasher(&leaves, H::new());
+    tree.try_set_padding(padding).map_err(|e| e.to_string())?;
+
+    let mut mismatches = Vec::new();
+    if tree.root_hash() != &root {
+        mismatches.push(Mismatch::Root {
+            expected: root.clone(),
+            actual: tree.root_hash().clone(),
+        });
+    }
+
+    for (index, FixtureProof { proof, valid }) in proofs.into_iter().enumerate() {
+        let accepted = leaves
+            .get(proof.position())
+            .is_some_and(|leaf| proof.validate_with_padding(&root, leaf, padding, H::new()));
+        if accepted != valid {
+            mismatches.push(Mismatch::Proof {
+                index,
+                expected_valid: valid,
+            });
+        } else if valid {
+            let actual = tree.gen_proof(proof.position());
+            if actual != proof {
+                mismatches.push(Mismatch::ProofDiffers { index, actual });
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+fn padding(value: &Value) -> Result<Padding, String> {
+    match value {
+        Value::Null => Ok(Padding::Duplicate),
+        Value::String(s) if s == "duplicate" => Ok(Padding::Duplicate),
+        Value::String(s) if s == "empty" => Ok(Padding::Empty),
+        Value::String(s) if s == "reject" => Ok(Padding::Reject),
+        Value::String(s) if s == "promote" => Ok(Padding::Promote),
+        Value::Object(_) if !value["constant"].is_null() => {
+            let sentinel = Sentinel::try_new(&hex(&value["constant"])?)
+                .map_err(|_| "constant padding is longer than any supported hash")?;
+            Ok(Padding::Constant(sentinel))
+        }
+        _ => Err(format!("unknown padding {}", value)),
+    }
+}
+
+fn fixture_proof(value: &Value) -> Result<FixtureProof, String> {
+    let proof = Proof::from_json(value).map_err(|e| format!("invalid proof: {}", e))?;
+    let valid = match &value["valid"] {
+        Value::Null => true,
+        valid => valid.as_bool().ok_or("valid must be true or false")?,
+    };
+    Ok(FixtureProof { proof, valid })
+}
+
+fn hex(value: &Value) -> Result<Vec<u8>, String> {
+    let s = value.as_str().ok_or("expected a hex string")?;
+    hex::decode(s).map_err(|_| format!("invalid hex {:?}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn fixture(leaves: &[&str]) -> Value {
+        let values: Vec<Vec<u8>> = leaves.iter().map(|l| hex::decode(l).unwrap()).collect();
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        json!({
+            "algorithm": "sha256",
+            "leaves": leaves,
+            "root": hex::encode(tree.root_hash()),
+            "proofs": [tree.gen_proof(values.len() - 1).to_json()],
+        })
+    }
+
+    fn run(fixture: &Value) -> Vec<Mismatch> {
+        run_fixture(fixture.to_string().as_bytes())
+    }
+
+    #[test]
+    fn agreeing_fixture_has_no_mismatch() {
+        assert_eq!(run(&fixture(&["61", "62", "63"])), vec![]);
+    }
+
+    #[test]
+    fn signed_hex_is_invalid() {
+        let mut fixture = fixture(&["61", "62", "63"]);
+        fixture["leaves"][0] = json!("+f");
+        assert_eq!(
+            run(&fixture),
+            vec![Mismatch::Invalid("invalid hex \"+f\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejected_proof_marked_valid_is_a_mismatch() {
+        let mut fixture = fixture(&["61", "62", "63"]);
+        fixture["proofs"][0]["path"][0] = json!({ "padding": "00" });
+        assert_eq!(
+            run(&fixture),
+            vec![Mismatch::Proof {
+                index: 0,
+                expected_valid: true
+            }]
+        );
+    }
+}
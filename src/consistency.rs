@@ -0,0 +1,368 @@
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+use crate::{
+    count_at_level, hash_internal_node, hash_odd_node, hashes_equal, height, padding, AsBytes,
+    Error, Hash, Limits, MerkleTree, Padding,
+};
+
+/// Proves that a tree of `count_leaves` leaves only appended to an older tree
+/// of `old_count_leaves` leaves.
+///
+/// The first hash is the largest complete subtree on the right edge of the
+/// old tree, which is identical in both trees. The remaining hashes are the
+/// siblings met while walking from it up to the new root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsistencyProof {
+    old_count_leaves: usize,
+    count_leaves: usize,
+    hashes: Vec<Hash>,
+    padding: Padding,
+}
+
+/// Level of the complete subtree a consistency proof starts from.
+fn start_level(old_count_leaves: usize) -> usize {
+    (old_count_leaves.trailing_zeros() as usize).min(height(old_count_leaves))
+}
+
+impl ConsistencyProof {
+    pub fn old_count_leaves(&self) -> usize {
+        self.old_count_leaves
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    /// How both trees pair the odd last node of a level.
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.usize(self.old_count_leaves);
+        w.usize(self.count_leaves);
+        w.usize(self.hashes.len());
+        for hash in &self.hashes {
+            w.bytes(hash);
+        }
+        padding::write(&mut w, self.padding);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ConsistencyProof, Error> {
+        ConsistencyProof::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on proofs going past `limits`.
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &Limits,
+    ) -> Result<ConsistencyProof, Error> {
+        let mut r = Reader::new(bytes);
+        let old_count_leaves = r.usize()?;
+        let count_leaves = r.usize()?;
+        limits.check_leaves(count_leaves)?;
+        limits.check_depth(height(count_leaves.max(1)))?;
+        let len = r.usize()?;
+        limits.check_proof_len(len)?;
+
+        let mut hashes = Vec::new();
+        for _ in 0..len {
+            hashes.push(r.bytes()?.to_vec());
+        }
+        let padding = padding::read(&mut r)?;
+        r.finish()?;
+
+        Ok(ConsistencyProof {
+            old_count_leaves,
+            count_leaves,
+            hashes,
+            padding,
+        })
+    }
+
+    /// Returns true if the proof links `old_root` to `new_root`, roots of a
+    /// tree using `Padding::Duplicate`.
+    pub fn verify_with_hasher<H>(&self, old_root: &[u8], new_root: &[u8], hasher: H) -> bool
+    where
+        H: Digest,
+    {
+        self.verify_with_padding(old_root, new_root, Padding::Duplicate, hasher)
+    }
+
+    /// Like `verify_with_hasher`, for a tree using `padding`. A proof made
+    /// for a tree using another padding is rejected.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                old_count_leaves = self.old_count_leaves,
+                count_leaves = self.count_leaves
+            )
+        )
+    )]
+    pub fn verify_with_padding<H>(
+        &self,
+        old_root: &[u8],
+        new_root: &[u8],
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        H: Digest,
+    {
+        if self.padding != padding || Limits::default().check_consistency_proof(self).is_err() {
+            return false;
+        }
+        let (m, n) = (self.old_count_leaves, self.count_leaves);
+        if m == 0 || m > n || self.hashes.is_empty() {
+            return false;
+        }
+
+        let old_height = height(m);
+        let start = start_level(m);
+        let mut hashes = self.hashes.iter();
+        let mut old = hashes.next().unwrap().clone();
+        let mut new = old.clone();
+        let mut j = (m - 1) >> start;
+
+        for level in start..height(n) {
+            if j % 2 == 1 {
+                let left = match hashes.next() {
+                    Some(h) => h,
+                    None => return false,
+                };
+                new = hash_internal_node(left, Some(&new), self.padding, &mut hasher);
+                old = hash_internal_node(left, Some(&old), self.padding, &mut hasher);
+            } else {
+                if j + 1 < count_at_level(n, level) {
+                    let right = match hashes.next() {
+                        Some(h) => h,
+                        None => return false,
+                    };
+                    new = hash_internal_node(&new, Some(right), self.padding, &mut hasher);
+                } else {
+                    new = match hash_odd_node(&new, padding, &mut hasher) {
+                        Some(h) => h,
+                        None => return false,
+                    };
+                }
+                if level < old_height {
+                    old = match hash_odd_node(&old, padding, &mut hasher) {
+                        Some(h) => h,
+                        None => return false,
+                    };
+                }
+            }
+            j >>= 1;
+        }
+
+        hashes.next().is_none() && hashes_equal(&old, old_root) & hashes_equal(&new, new_root)
+    }
+}
+
+impl ConsistencyProof {
+    /// Returns the complete subtrees on the right edge of the old tree, laid
+    /// out as the frontier of an `Appender` after `old_count_leaves` leaves,
+    /// or `None` if the hashes do not fit the sizes.
+    ///
+    /// These are the first hash and the left siblings of the walk; the right
+    /// siblings only cover appended leaves.
+    pub(crate) fn old_frontier(&self) -> Option<Vec<Option<Hash>>> {
+        let (m, n) = (self.old_count_leaves, self.count_leaves);
+        if m == 0 || m > n {
+            return None;
+        }
+
+        let start = start_level(m);
+        let mut frontier = vec![None; height(m) + 1];
+        let mut hashes = self.hashes.iter();
+        frontier[start] = Some(hashes.next()?.clone());
+        let mut j = (m - 1) >> start;
+
+        for level in start..height(n) {
+            if j % 2 == 1 {
+                *frontier.get_mut(level)? = Some(hashes.next()?.clone());
+            } else if j + 1 < count_at_level(n, level) {
+                hashes.next()?;
+            }
+            j >>= 1;
+        }
+
+        match hashes.next() {
+            Some(_) => None,
+            None => Some(frontier),
+        }
+    }
+}
+
+/// Builds a consistency proof from the `levels` of an up to date tree.
+pub(crate) fn consistency_proof_in(
+    levels: &[Vec<Hash>],
+    old_count_leaves: usize,
+    padding: Padding,
+) -> ConsistencyProof {
+    let count_leaves = levels[0].len();
+    let start = start_level(old_count_leaves);
+    let mut j = (old_count_leaves - 1) >> start;
+    let mut hashes = vec![levels[start][j].clone()];
+
+    for nodes in &levels[start..height(count_leaves)] {
+        if j % 2 == 1 {
+            hashes.push(nodes[j - 1].clone());
+        } else if j + 1 < nodes.len() {
+            hashes.push(nodes[j + 1].clone());
+        }
+        j >>= 1;
+    }
+
+    ConsistencyProof {
+        old_count_leaves,
+        count_leaves,
+        hashes,
+        padding,
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns a proof that the current tree extends its first
+    /// `old_count_leaves` leaves.
+    pub fn consistency_proof(&self, old_count_leaves: usize) -> ConsistencyProof {
+        self.try_consistency_proof(old_count_leaves)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `consistency_proof`, but fails instead of panicking if
+    /// `old_count_leaves` does not relate to any earlier tree or the tree has
+    /// deferred changes.
+    pub fn try_consistency_proof(
+        &self,
+        old_count_leaves: usize,
+    ) -> Result<ConsistencyProof, Error> {
+        if old_count_leaves == 0 || old_count_leaves > self.count_leaves() {
+            return Err(Error::InvalidSize(old_count_leaves));
+        }
+        self.check_clean()?;
+
+        Ok(consistency_proof_in(
+            &self.levels,
+            old_count_leaves,
+            self.padding,
+        ))
+    }
+
+    /// Appends `value` and returns a proof from the previous root to the new one.
+    pub fn push_with_proof<T>(&mut self, value: &T) -> ConsistencyProof
+    where
+        T: AsBytes,
+    {
+        self.extend_with_proof(std::slice::from_ref(value))
+    }
+
+    /// Appends `values` and returns a proof from the previous root to the new one.
+    pub fn extend_with_proof<T>(&mut self, values: &[T]) -> ConsistencyProof
+    where
+        T: AsBytes,
+    {
+        self.flush();
+        let old_count_leaves = self.count_leaves();
+        self.extend(values);
+        self.consistency_proof(old_count_leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    const VALUES: [&str; 7] = ["a", "b", "c", "d", "e", "f", "g"];
+
+    fn tree(count: usize, padding: Padding) -> MerkleTree<Sha256> {
+        let mut tree = MerkleTree::build_with_hasher(&VALUES[..count], Sha256::default());
+        tree.set_padding(padding);
+        tree
+    }
+
+    #[test]
+    fn consistency_proof_verifies_under_its_padding_only() {
+        let (old, new) = (tree(3, Padding::Empty), tree(7, Padding::Empty));
+        let proof = new.consistency_proof(3);
+
+        assert!(proof.verify_with_padding(
+            old.root_hash(),
+            new.root_hash(),
+            Padding::Empty,
+            Sha256::default()
+        ));
+        assert!(!proof.verify_with_hasher(old.root_hash(), new.root_hash(), Sha256::default()));
+    }
+
+    #[test]
+    fn reject_padding_on_odd_level_fails_without_panicking() {
+        let (old, new) = (tree(3, Padding::Duplicate), tree(7, Padding::Duplicate));
+        let proof = ConsistencyProof {
+            padding: Padding::Reject,
+            ..new.consistency_proof(3)
+        };
+
+        assert!(!proof.verify_with_padding(
+            old.root_hash(),
+            new.root_hash(),
+            Padding::Reject,
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    fn decoding_checks_depth() {
+        let proof = tree(7, Padding::Duplicate).consistency_proof(3);
+        let shallow = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            ConsistencyProof::from_bytes_with_limits(&proof.to_bytes(), &shallow),
+            Err(Error::LimitExceeded)
+        );
+        assert_eq!(ConsistencyProof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+
+    #[test]
+    fn push_with_proof_verifies_from_every_size() {
+        let mut tree = tree(1, Padding::Duplicate);
+        for (count, value) in VALUES.iter().enumerate().skip(1) {
+            let old_root = tree.root_hash().clone();
+            let proof = tree.push_with_proof(value);
+
+            assert_eq!(proof.old_count_leaves(), count);
+            assert_eq!(proof.count_leaves(), count + 1);
+            assert!(
+                proof.verify_with_hasher(&old_root, tree.root_hash(), Sha256::default()),
+                "{} leaves",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn extend_with_proof_rejects_other_old_root() {
+        let mut tree = tree(2, Padding::Duplicate);
+        let old_root = tree.root_hash().clone();
+        let proof = tree.extend_with_proof(&VALUES[2..]);
+
+        assert!(proof.verify_with_hasher(&old_root, tree.root_hash(), Sha256::default()));
+        let other = MerkleTree::build_with_hasher(&["a", "c"], Sha256::default());
+        assert!(!proof.verify_with_hasher(other.root_hash(), tree.root_hash(), Sha256::default()));
+    }
+}
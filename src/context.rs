@@ -0,0 +1,243 @@
+use digest::Digest;
+use subtle::ConstantTimeEq;
+
+use crate::codec::{Reader, Writer};
+use crate::mutation::LeafMutation;
+use crate::{AsBytes, Error, Hash, Limits, MerkleTree, Padding, Proof};
+
+const CONTEXT_SIG: u8 = 4u8;
+
+/// Hashes `value` into a leaf bound to `context`. The length prefix keeps a
+/// context from running into the value, so no two pairs share a leaf.
+fn hash_bound_leaf<H>(context: &[u8], value: &[u8], hasher: &mut H) -> Hash
+where
+    H: Digest,
+{
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_hash(9 + context.len() + value.len());
+
+    hasher.update([CONTEXT_SIG]);
+    hasher.update((context.len() as u64).to_le_bytes());
+    hasher.update(context);
+    hasher.update(value);
+    hasher.finalize_reset().to_vec()
+}
+
+/// Returns the hash a tree using `hasher` stores for `value` pushed with
+/// `push_bound` under `context`.
+pub fn bound_leaf_hash_with_hasher<T, H>(context: &[u8], value: &T, mut hasher: H) -> Hash
+where
+    H: Digest,
+    T: AsBytes,
+{
+    hash_bound_leaf(context, value.as_bytes(), &mut hasher)
+}
+
+/// An inclusion proof issued for one purpose, named by an application
+/// supplied context string.
+///
+/// A tree shared by several protocols proves the same leaves for all of
+/// them, so a bare `Proof` handed out by one can be replayed to another.
+/// Leaves pushed with `push_bound` hash their context in, and verifiers of
+/// a bound proof hash in the context they expect, so a proof rewrapped
+/// under any other context no longer leads to the root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundProof {
+    context: Vec<u8>,
+    proof: Proof,
+}
+
+impl BoundProof {
+    pub fn new(context: &[u8], proof: Proof) -> BoundProof {
+        BoundProof {
+            context: context.to_vec(),
+            proof,
+        }
+    }
+
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(&self.context);
+        w.bytes(&self.proof.to_bytes());
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<BoundProof, Error> {
+        BoundProof::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on proofs going past `limits`.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<BoundProof, Error> {
+        let mut r = Reader::new(bytes);
+        let context = r.bytes()?.to_vec();
+        let proof = Proof::from_bytes_with_limits(r.bytes()?, limits)?;
+        r.finish()?;
+
+        Ok(BoundProof { context, proof })
+    }
+
+    /// Returns true if the proof was issued under `context` and `value`,
+    /// bound to `context`, is the leaf at its position of the tree with
+    /// `root_hash`.
+    pub fn validate_with_hasher<T, H>(
+        &self,
+        context: &[u8],
+        root_hash: &[u8],
+        value: &T,
+        hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.validate_with_padding(context, root_hash, value, Padding::Duplicate, hasher)
+    }
+
+    /// Like `validate_with_hasher`, for a tree using `padding`.
+    pub fn validate_with_padding<T, H>(
+        &self,
+        context: &[u8],
+        root_hash: &[u8],
+        value: &T,
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let leaf = hash_bound_leaf(context, value.as_bytes(), &mut hasher);
+        bool::from(self.context.ct_eq(context))
+            && self
+                .proof
+                .validate_leaf(root_hash, leaf, padding, &mut hasher)
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Builds a tree over `values`, every leaf bound to `context`.
+    pub fn build_bound_with_hasher<T>(context: &[u8], values: &[T], mut hasher: H) -> MerkleTree<H>
+    where
+        T: AsBytes,
+    {
+        let leaves = values
+            .iter()
+            .map(|v| hash_bound_leaf(context, v.as_bytes(), &mut hasher))
+            .collect();
+        MerkleTree::build_from_leaves(leaves, hasher)
+    }
+
+    /// Appends `value` as a leaf bound to `context`, provable only with a
+    /// `BoundProof` for the same context.
+    pub fn push_bound<T>(&mut self, context: &[u8], value: &T)
+    where
+        T: AsBytes,
+    {
+        let leaf = hash_bound_leaf(context, value.as_bytes(), &mut self.hasher);
+        self.apply_leaves(vec![LeafMutation::Push(leaf)]);
+    }
+
+    /// Returns the inclusion proof of the leaf at `position`, pushed with
+    /// `push_bound` under `context`.
+    pub fn gen_bound_proof(&self, position: usize, context: &[u8]) -> BoundProof {
+        BoundProof::new(context, self.gen_proof(position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree(context: &[u8]) -> MerkleTree<Sha256> {
+        let values = vec!["a", "b", "c"];
+        MerkleTree::build_bound_with_hasher(context, &values, Sha256::default())
+    }
+
+    #[test]
+    fn bound_proof_validates_under_its_context() {
+        let tree = tree(b"login");
+        for position in 0..3 {
+            let proof = tree.gen_bound_proof(position, b"login");
+            let value = ["a", "b", "c"][position];
+            assert!(proof.validate_with_hasher(
+                b"login",
+                tree.root_hash(),
+                &value,
+                Sha256::default()
+            ));
+        }
+    }
+
+    #[test]
+    fn rewrapped_proof_is_rejected() {
+        let tree = tree(b"login");
+        let proof = tree.gen_bound_proof(1, b"login");
+        let rewrapped = BoundProof::new(b"payment", proof.proof().clone());
+
+        assert!(!rewrapped.validate_with_hasher(
+            b"payment",
+            tree.root_hash(),
+            &"b",
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_hasher(b"payment", tree.root_hash(), &"b", Sha256::default()));
+        assert!(!proof
+            .proof()
+            .validate_with_hasher(tree.root_hash(), &"b", Sha256::default()));
+    }
+
+    #[test]
+    fn bound_proof_validates_with_padding() {
+        let mut tree = tree(b"login");
+        tree.set_padding(Padding::Empty);
+        let proof = tree.gen_bound_proof(2, b"login");
+        let root = tree.root_hash();
+
+        assert!(proof.validate_with_padding(
+            b"login",
+            root,
+            &"c",
+            Padding::Empty,
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_padding(
+            b"login",
+            root,
+            &"c",
+            Padding::Duplicate,
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_hasher(b"login", root, &"c", Sha256::default()));
+    }
+
+    #[test]
+    fn context_does_not_run_into_value() {
+        let a = bound_leaf_hash_with_hasher(b"ab", &"c", Sha256::default());
+        let b = bound_leaf_hash_with_hasher(b"a", &"bc", Sha256::default());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn push_bound_matches_build() {
+        let mut pushed = MerkleTree::build_bound_with_hasher(b"ctx", &["a"], Sha256::default());
+        pushed.push_bound(b"ctx", &"b");
+        pushed.push_bound(b"ctx", &"c");
+        assert_eq!(pushed.root_hash(), tree(b"ctx").root_hash());
+    }
+
+    #[test]
+    fn bound_proof_round_trips() {
+        let proof = tree(b"ctx").gen_bound_proof(2, b"ctx");
+        assert_eq!(BoundProof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+}
@@ -0,0 +1,142 @@
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+use crate::{hashes_equal, Error, Hash, MerkleTree};
+
+/// A position in leaf order, tied to the root of the tree it was issued for
+/// so that every page of a traversal comes from the same tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    position: usize,
+    root: Hash,
+}
+
+impl Cursor {
+    /// Position of the first leaf of the next page.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.usize(self.position);
+        w.bytes(&self.root);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cursor, Error> {
+        let mut r = Reader::new(bytes);
+        let position = r.usize()?;
+        let root = r.bytes()?.to_vec();
+        r.finish()?;
+
+        Ok(Cursor { position, root })
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns a cursor at the first leaf of the current tree.
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            position: 0,
+            root: self.root_hash().clone(),
+        }
+    }
+
+    /// Returns up to `limit` leaf hashes from `cursor` on, and the cursor of
+    /// the following page unless this one reaches the last leaf.
+    ///
+    /// Fails if the tree changed since the cursor was issued, since the leaf
+    /// order may no longer be the one earlier pages were read from, and with
+    /// `Error::InvalidRange` if `limit` is zero.
+    pub fn next_page(
+        &self,
+        cursor: &Cursor,
+        limit: usize,
+    ) -> Result<(&[Hash], Option<Cursor>), Error> {
+        if limit == 0 {
+            return Err(Error::InvalidRange {
+                start: cursor.position,
+                end: cursor.position,
+            });
+        }
+
+        let root = self.root_hash();
+        if !hashes_equal(&cursor.root, root) {
+            return Err(Error::RootMismatch {
+                expected: cursor.root.clone(),
+                actual: root.clone(),
+            });
+        }
+        if cursor.position >= self.count_leaves() {
+            return Err(Error::InvalidPosition(cursor.position));
+        }
+
+        let end = self
+            .count_leaves()
+            .min(cursor.position.saturating_add(limit));
+        let next = if end < self.count_leaves() {
+            Some(Cursor {
+                position: end,
+                root: root.clone(),
+            })
+        } else {
+            None
+        };
+
+        Ok((&self.levels[0][cursor.position..end], next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn zero_page_limit_is_an_error() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let cursor = tree.cursor();
+        assert_eq!(
+            tree.next_page(&cursor, 0),
+            Err(Error::InvalidRange { start: 0, end: 0 })
+        );
+        assert_eq!(tree.next_page(&cursor, 5).unwrap().0.len(), 3);
+    }
+
+    #[test]
+    fn pages_cover_every_leaf_through_encoded_cursors() {
+        let values: Vec<Vec<u8>> = (0..7).map(|i| vec![i]).collect();
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+
+        let mut leaves = Vec::new();
+        let mut cursor = Some(tree.cursor());
+        while let Some(current) = cursor {
+            let decoded = Cursor::from_bytes(&current.to_bytes()).unwrap();
+            let (page, next) = tree.next_page(&decoded, 3).unwrap();
+            assert!(page.len() <= 3);
+            leaves.extend_from_slice(page);
+            cursor = next;
+        }
+        assert_eq!(leaves, tree.leaves().cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cursor_fails_once_the_tree_changed() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let (_, next) = tree.next_page(&tree.cursor(), 1).unwrap();
+        let next = next.unwrap();
+        assert_eq!(next.position(), 1);
+
+        tree.push(&"d");
+        assert!(matches!(
+            tree.next_page(&next, 1),
+            Err(Error::RootMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use std::ops::Range;
+
+use digest::Digest;
+
+use crate::{Error, Hash, MerkleTree};
+
+/// Iterator over the ranges of leaf positions at which two trees differ,
+/// created by `MerkleTree::diff`.
+///
+/// Ranges come in increasing order and adjacent ones are merged. Positions
+/// the larger tree has past the end of the smaller one are included.
+pub struct Diff<'a> {
+    left: &'a [Vec<Hash>],
+    right: &'a [Vec<Hash>],
+    /// Nodes still to compare, as `(level, index)`.
+    stack: Vec<(usize, usize)>,
+    pending: Option<Range<usize>>,
+}
+
+/// Returns the leaves covered by the node at `index` of `level` in `levels`,
+/// or `None` if there is no such node.
+fn covered(levels: &[Vec<Hash>], level: usize, index: usize) -> Option<(Range<usize>, &Hash)> {
+    let node = levels.get(level)?.get(index)?;
+    let start = index << level;
+    let end = ((index + 1) << level).min(levels[0].len());
+    Some((start..end, node))
+}
+
+impl<'a> Diff<'a> {
+    fn new(left: &'a [Vec<Hash>], right: &'a [Vec<Hash>]) -> Diff<'a> {
+        let top = left.len().max(right.len()) - 1;
+        Diff {
+            left,
+            right,
+            stack: vec![(top, 0)],
+            pending: None,
+        }
+    }
+
+    /// Compares the next node on the stack and returns the leaves it proves
+    /// different, if any.
+    fn step(&mut self) -> Option<Option<Range<usize>>> {
+        let (level, index) = self.stack.pop()?;
+        let (a, b) = (self.left[0].len(), self.right[0].len());
+        let start = index << level;
+        if start >= a.max(b) {
+            return Some(None);
+        }
+
+        let end = ((index + 1) << level).min(a.max(b));
+        if start >= a.min(b) {
+            return Some(Some(start..end));
+        }
+
+        match (
+            covered(self.left, level, index),
+            covered(self.right, level, index),
+        ) {
+            (Some((l, x)), Some((r, y))) if l == r && x == y => Some(None),
+            _ if level == 0 => Some(Some(start..start + 1)),
+            _ => {
+                self.stack.push((level - 1, 2 * index + 1));
+                self.stack.push((level - 1, 2 * index));
+                Some(None)
+            }
+        }
+    }
+}
+
+impl Iterator for Diff<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        while let Some(found) = self.step() {
+            let range = match found {
+                Some(range) => range,
+                None => continue,
+            };
+            match self.pending.take() {
+                Some(pending) if pending.end == range.start => {
+                    self.pending = Some(pending.start..range.end);
+                }
+                Some(pending) => {
+                    self.pending = Some(range);
+                    return Some(pending);
+                }
+                None => self.pending = Some(range),
+            }
+        }
+
+        self.pending.take()
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Iterates over the ranges of leaves that differ between this tree and
+    /// `other`, skipping every subtree whose hash both trees share.
+    pub fn diff<'a>(&'a self, other: &'a MerkleTree<H>) -> Diff<'a> {
+        self.try_diff(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `diff`, but fails instead of panicking if either tree has
+    /// deferred changes.
+    pub fn try_diff<'a>(&'a self, other: &'a MerkleTree<H>) -> Result<Diff<'a>, Error> {
+        self.check_clean()?;
+        other.check_clean()?;
+
+        Ok(Diff::new(&self.levels, &other.levels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn try_diff_fails_if_either_tree_has_deferred_changes() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let mut other = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        other.defer(&[Mutation::Set(2, "z")]);
+        assert!(matches!(tree.try_diff(&other), Err(Error::DeferredChanges)));
+        assert!(matches!(other.try_diff(&tree), Err(Error::DeferredChanges)));
+
+        other.flush();
+        assert_eq!(
+            tree.try_diff(&other).unwrap().collect::<Vec<_>>(),
+            vec![2..3]
+        );
+    }
+
+    fn tree(values: &[&str]) -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(values, Sha256::default())
+    }
+
+    #[test]
+    fn diff_merges_adjacent_ranges_and_covers_extra_leaves() {
+        let left = tree(&["a", "b", "c", "d", "e", "f"]);
+        let right = tree(&["a", "x", "y", "d", "e", "z", "g", "h"]);
+        assert_eq!(left.diff(&right).collect::<Vec<_>>(), vec![1..3, 5..8]);
+        assert_eq!(right.diff(&left).collect::<Vec<_>>(), vec![1..3, 5..8]);
+    }
+
+    #[test]
+    fn equal_trees_have_no_diff() {
+        let left = tree(&["a", "b", "c"]);
+        assert_eq!(left.diff(&tree(&["a", "b", "c"])).count(), 0);
+    }
+}
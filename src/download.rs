@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+
+use digest::Digest;
+
+use crate::{hash_leaf, Error, Hash, Padding, Proof};
+
+/// Reassembles content split into fixed-size chunks, each the leaf of a tree
+/// with a known root, from `(chunk, proof)` pairs arriving in any order.
+///
+/// Every chunk is checked against the root as it arrives, so a download from
+/// untrusted sources stops at the first bad chunk instead of after fetching
+/// everything.
+#[derive(Debug)]
+pub struct Download<H> {
+    root: Hash,
+    len: usize,
+    chunk_size: usize,
+    chunks: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    padding: Padding,
+    hasher: PhantomData<H>,
+}
+
+impl<H: Digest> Download<H> {
+    /// Expects `len` bytes split into chunks of `chunk_size` bytes, all but
+    /// the last one full, under `root`. Empty content is a single empty chunk.
+    pub fn new(root: &[u8], len: usize, chunk_size: usize) -> Download<H> {
+        Download::try_new(root, len, chunk_size).expect("chunk size must be positive")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `chunk_size` is zero.
+    pub fn try_new(root: &[u8], len: usize, chunk_size: usize) -> Result<Download<H>, Error> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidSize(chunk_size));
+        }
+
+        let count = len.div_ceil(chunk_size).max(1);
+        Ok(Download {
+            root: root.to_vec(),
+            len,
+            chunk_size,
+            chunks: vec![None; count],
+            missing: count,
+            padding: Padding::Duplicate,
+            hasher: PhantomData,
+        })
+    }
+
+    /// Checks chunks against a tree using `padding` rather than the default
+    /// `Padding::Duplicate`.
+    pub fn with_padding(mut self, padding: Padding) -> Download<H> {
+        self.padding = padding;
+        self
+    }
+
+    pub fn count_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the number of chunks not received yet.
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing == 0
+    }
+
+    /// Length the chunk at `position` must have.
+    fn chunk_len(&self, position: usize) -> usize {
+        if position + 1 == self.count_chunks() {
+            self.len - position * self.chunk_size
+        } else {
+            self.chunk_size
+        }
+    }
+
+    /// Checks `chunk` against `proof` and the root and keeps it.
+    ///
+    /// Fails with `Error::InvalidChunk` if the proof is not for a chunk of
+    /// this layout, the chunk has the wrong length or it is not the leaf the
+    /// proof leads to from the root. A chunk received twice is checked again
+    /// and otherwise ignored.
+    pub fn accept(&mut self, chunk: Vec<u8>, proof: &Proof) -> Result<(), Error> {
+        let position = proof.position();
+        if proof.count_leaves() != self.count_chunks()
+            || position >= self.count_chunks()
+            || chunk.len() != self.chunk_len(position)
+        {
+            return Err(Error::InvalidChunk(position));
+        }
+
+        let mut hasher = H::new();
+        let leaf = hash_leaf(&chunk, &mut hasher);
+        if !proof.validate_leaf(&self.root, leaf, self.padding, &mut hasher) {
+            return Err(Error::InvalidChunk(position));
+        }
+
+        if self.chunks[position].is_none() {
+            self.chunks[position] = Some(chunk);
+            self.missing -= 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the content once every chunk arrived, or fails with
+    /// `Error::InvalidPosition` naming the first missing one.
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        if let Some(position) = self.chunks.iter().position(Option::is_none) {
+            return Err(Error::InvalidPosition(position));
+        }
+
+        let mut content = Vec::with_capacity(self.len);
+        for chunk in self.chunks.into_iter().flatten() {
+            content.extend_from_slice(&chunk);
+        }
+        Ok(content)
+    }
+
+    /// Accepts pairs from `pairs`, such as a `std::sync::mpsc::Receiver`,
+    /// until every chunk arrived, and returns the content. Stops at the first
+    /// bad chunk.
+    pub fn consume<I>(mut self, pairs: I) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = (Vec<u8>, Proof)>,
+    {
+        for (chunk, proof) in pairs {
+            self.accept(chunk, &proof)?;
+            if self.is_complete() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Like `consume`, for pairs sent on a tokio channel.
+    #[cfg(feature = "tokio")]
+    pub async fn receive(
+        mut self,
+        pairs: &mut tokio::sync::mpsc::Receiver<(Vec<u8>, Proof)>,
+    ) -> Result<Vec<u8>, Error> {
+        while !self.is_complete() {
+            match pairs.recv().await {
+                Some((chunk, proof)) => self.accept(chunk, &proof)?,
+                None => break,
+            }
+        }
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::MerkleTree;
+
+    const CONTENT: &[u8] = b"abcdefghij";
+
+    fn chunks() -> (MerkleTree<Sha256>, Vec<(Vec<u8>, Proof)>) {
+        let chunks: Vec<Vec<u8>> = CONTENT.chunks(4).map(|c| c.to_vec()).collect();
+        let tree = MerkleTree::build_with_hasher(&chunks, Sha256::default());
+        let pairs = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| (chunk, tree.gen_proof(i)))
+            .collect();
+        (tree, pairs)
+    }
+
+    #[test]
+    fn chunks_in_any_order_reassemble() {
+        let (tree, mut pairs) = chunks();
+        pairs.reverse();
+        pairs.insert(1, pairs[0].clone());
+
+        let download = Download::<Sha256>::new(tree.root_hash(), CONTENT.len(), 4);
+        assert_eq!(download.count_chunks(), 3);
+        assert_eq!(download.consume(pairs), Ok(CONTENT.to_vec()));
+    }
+
+    #[test]
+    fn bad_chunks_are_rejected() {
+        let (tree, pairs) = chunks();
+        let mut download = Download::<Sha256>::new(tree.root_hash(), CONTENT.len(), 4);
+
+        assert_eq!(
+            download.accept(b"abcx".to_vec(), &pairs[0].1),
+            Err(Error::InvalidChunk(0))
+        );
+        assert_eq!(
+            download.accept(b"ij".to_vec(), &pairs[1].1),
+            Err(Error::InvalidChunk(1))
+        );
+        assert_eq!(download.accept(pairs[2].0.clone(), &pairs[2].1), Ok(()));
+        assert_eq!(download.missing(), 2);
+        assert_eq!(download.finish(), Err(Error::InvalidPosition(0)));
+    }
+
+    #[test]
+    fn empty_content_is_one_empty_chunk() {
+        let tree = MerkleTree::build_with_hasher(&[Vec::<u8>::new()], Sha256::default());
+        let mut download = Download::<Sha256>::new(tree.root_hash(), 0, 4);
+        assert_eq!(download.accept(Vec::new(), &tree.gen_proof(0)), Ok(()));
+        assert!(download.is_complete());
+        assert_eq!(download.finish(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn zero_chunk_size_is_refused() {
+        assert!(matches!(
+            Download::<Sha256>::try_new(&[0; 32], 10, 0),
+            Err(Error::InvalidSize(0))
+        ));
+        assert_eq!(
+            Download::<Sha256>::try_new(&[0; 32], 10, 4)
+                .map(|d| d.count_chunks())
+                .ok(),
+            Some(3)
+        );
+    }
+}
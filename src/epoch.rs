@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+
+use digest::Digest;
+
+use crate::{AsBytes, ConsistencyProof, Error, Hash, MerkleTree};
+
+/// The root of an append-only tree sealed at some point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epoch {
+    number: u64,
+    timestamp: u64,
+    root: Hash,
+    count_leaves: usize,
+    consistency: Option<ConsistencyProof>,
+}
+
+impl Epoch {
+    /// Position of the epoch among all epochs sealed, starting at 0.
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// When the epoch was sealed, in whatever unit the caller uses.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    /// Proof that this epoch extends the one before it, `None` for the first
+    /// epoch.
+    pub fn consistency(&self) -> Option<&ConsistencyProof> {
+        self.consistency.as_ref()
+    }
+}
+
+/// An append-only tree whose root is sealed into numbered, time-stamped
+/// epochs, on demand or whenever an interval has passed.
+///
+/// Only the latest `retention` epochs are kept, each with a consistency
+/// proof from the epoch before it, so a client holding any retained epoch
+/// root can be brought up to date one epoch at a time. Which epoch first
+/// contained a leaf can be looked up for every epoch ever sealed, at the
+/// cost of one leaf count per epoch.
+pub struct EpochedTree<H> {
+    tree: MerkleTree<H>,
+    epochs: VecDeque<Epoch>,
+    /// Leaf count of every epoch sealed, retained or not.
+    boundaries: Vec<usize>,
+    retention: usize,
+    interval: Option<u64>,
+}
+
+impl<H: Digest> EpochedTree<H> {
+    /// Wraps `tree`, keeping up to `retention` epochs. No epoch is sealed
+    /// yet.
+    pub fn new(tree: MerkleTree<H>, retention: usize) -> EpochedTree<H> {
+        EpochedTree::try_new(tree, retention).expect("must retain at least 1 epoch")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `retention` is zero.
+    pub fn try_new(tree: MerkleTree<H>, retention: usize) -> Result<EpochedTree<H>, Error> {
+        check_retention(retention)?;
+
+        Ok(EpochedTree {
+            tree,
+            epochs: VecDeque::new(),
+            boundaries: Vec::new(),
+            retention,
+            interval: None,
+        })
+    }
+
+    /// Makes `seal_if_due` seal an epoch once `interval` has passed since
+    /// the last one, or never with `None`, the default.
+    pub fn set_interval(&mut self, interval: Option<u64>) {
+        self.interval = interval;
+    }
+
+    pub fn retention(&self) -> usize {
+        self.retention
+    }
+
+    /// Keeps up to `retention` epochs from now on, dropping the oldest ones
+    /// beyond that.
+    pub fn set_retention(&mut self, retention: usize) {
+        self.try_set_retention(retention)
+            .expect("must retain at least 1 epoch")
+    }
+
+    /// Like `set_retention`, but fails with `Error::InvalidSize` instead of
+    /// panicking if `retention` is zero.
+    pub fn try_set_retention(&mut self, retention: usize) -> Result<(), Error> {
+        check_retention(retention)?;
+
+        self.retention = retention;
+        self.trim_epochs();
+        Ok(())
+    }
+
+    /// The tree, including leaves appended since the last epoch.
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> MerkleTree<H> {
+        self.tree
+    }
+
+    pub fn push<T>(&mut self, value: &T)
+    where
+        T: AsBytes,
+    {
+        self.tree.push(value);
+    }
+
+    pub fn extend<T>(&mut self, values: &[T])
+    where
+        T: AsBytes,
+    {
+        self.tree.extend(values);
+    }
+
+    /// Seals the current root into a new epoch at `timestamp` and returns
+    /// it. An epoch without new leaves has the same root as the one before.
+    ///
+    /// Fails with `Error::TimestampRegressed` if `timestamp` is before the
+    /// one of the last epoch.
+    pub fn seal(&mut self, timestamp: u64) -> Result<&Epoch, Error> {
+        let previous = self.epochs.back();
+        if let Some(last) = previous.filter(|e| e.timestamp > timestamp) {
+            return Err(Error::TimestampRegressed {
+                last: last.timestamp,
+                timestamp,
+            });
+        }
+        let consistency = previous.map(|e| e.count_leaves);
+
+        let root = self.tree.root().clone();
+        let count_leaves = self.tree.count_leaves();
+        let epoch = Epoch {
+            number: self.boundaries.len() as u64,
+            timestamp,
+            root,
+            count_leaves,
+            consistency: consistency.map(|old| self.tree.consistency_proof(old)),
+        };
+
+        self.boundaries.push(count_leaves);
+        self.epochs.push_back(epoch);
+        self.trim_epochs();
+        Ok(self.epochs.back().unwrap())
+    }
+
+    /// Seals an epoch at `now` if the interval set with `set_interval` has
+    /// passed since the last one. The first epoch is due right away, and
+    /// none is while `now` is before the last one.
+    pub fn seal_if_due(&mut self, now: u64) -> Option<&Epoch> {
+        let interval = self.interval?;
+        let due = match self.epochs.back() {
+            Some(last) => now
+                .checked_sub(last.timestamp)
+                .is_some_and(|elapsed| elapsed >= interval),
+            None => true,
+        };
+
+        if due {
+            self.seal(now).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The retained epochs, oldest first.
+    pub fn epochs(&self) -> impl DoubleEndedIterator<Item = &Epoch> + ExactSizeIterator {
+        self.epochs.iter()
+    }
+
+    /// Returns the epoch numbered `number`, if it is still retained.
+    pub fn epoch(&self, number: u64) -> Option<&Epoch> {
+        let first = self.epochs.front()?.number;
+        let offset = number.checked_sub(first)?;
+        self.epochs.get(usize::try_from(offset).ok()?)
+    }
+
+    pub fn latest(&self) -> Option<&Epoch> {
+        self.epochs.back()
+    }
+
+    /// Number of epochs sealed so far, retained or not.
+    pub fn count_epochs(&self) -> u64 {
+        self.boundaries.len() as u64
+    }
+
+    /// Returns the number of the first epoch that contained the leaf at
+    /// `position`, or `None` if no epoch has been sealed since it was
+    /// appended. The epoch itself may no longer be retained.
+    pub fn first_epoch_containing(&self, position: usize) -> Option<u64> {
+        let number = self.boundaries.partition_point(|&count| count <= position);
+        if number < self.boundaries.len() {
+            Some(number as u64)
+        } else {
+            None
+        }
+    }
+
+    /// Like `first_epoch_containing`, for the first leaf whose hash is
+    /// `leaf`, looked up as `MerkleTree::index_of` does.
+    pub fn first_epoch_of_leaf(&self, leaf: &[u8]) -> Option<u64> {
+        self.tree
+            .index_of(leaf)
+            .and_then(|position| self.first_epoch_containing(position))
+    }
+
+    fn trim_epochs(&mut self) {
+        while self.epochs.len() > self.retention {
+            self.epochs.pop_front();
+        }
+    }
+}
+
+fn check_retention(retention: usize) -> Result<(), Error> {
+    if retention == 0 {
+        Err(Error::InvalidSize(retention))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn epoched(retention: usize) -> EpochedTree<Sha256> {
+        EpochedTree::new(
+            MerkleTree::build_with_hasher(&["a"], Sha256::default()),
+            retention,
+        )
+    }
+
+    #[test]
+    fn each_epoch_proves_the_one_before() {
+        let mut tree = epoched(10);
+        tree.seal(1).unwrap();
+        tree.extend(&["b", "c"]);
+        tree.seal(2).unwrap();
+        tree.seal(2).unwrap();
+        tree.push(&"d");
+        tree.seal(5).unwrap();
+
+        let epochs: Vec<&Epoch> = tree.epochs().collect();
+        assert_eq!(epochs.len(), 4);
+        assert_eq!(epochs[0].consistency(), None);
+        for pair in epochs.windows(2) {
+            let proof = pair[1].consistency().unwrap();
+            assert_eq!(proof.old_count_leaves(), pair[0].count_leaves());
+            assert!(proof.verify_with_hasher(pair[0].root(), pair[1].root(), Sha256::default()));
+        }
+        assert_eq!(epochs[2].root(), epochs[1].root());
+        assert_eq!(tree.latest().unwrap().root(), tree.tree().root_hash());
+    }
+
+    #[test]
+    fn retention_drops_the_oldest_epochs() {
+        let mut tree = epoched(2);
+        for timestamp in 0..5 {
+            tree.push(&"x");
+            tree.seal(timestamp).unwrap();
+        }
+        let numbers: Vec<u64> = tree.epochs().map(Epoch::number).collect();
+        assert_eq!(numbers, [3, 4]);
+        assert_eq!(tree.count_epochs(), 5);
+        assert_eq!(tree.epoch(2), None);
+        assert_eq!(tree.epoch(4).unwrap().timestamp(), 4);
+
+        tree.set_retention(1);
+        assert_eq!(tree.epochs().map(Epoch::number).collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn first_epoch_containing_covers_dropped_epochs() {
+        let mut tree = epoched(1);
+        tree.seal(0).unwrap();
+        tree.extend(&["b", "c"]);
+        tree.seal(1).unwrap();
+        tree.push(&"d");
+
+        assert_eq!(tree.first_epoch_containing(0), Some(0));
+        assert_eq!(tree.first_epoch_containing(2), Some(1));
+        assert_eq!(tree.first_epoch_containing(3), None);
+        let leaf = crate::leaf_hash_with_hasher(&"c", Sha256::default());
+        assert_eq!(tree.first_epoch_of_leaf(&leaf), Some(1));
+    }
+
+    #[test]
+    fn seal_if_due_waits_for_the_interval() {
+        let mut tree = epoched(10);
+        assert!(tree.seal_if_due(0).is_none());
+
+        tree.set_interval(Some(10));
+        assert_eq!(tree.seal_if_due(3).map(Epoch::number), Some(0));
+        assert!(tree.seal_if_due(12).is_none());
+        assert_eq!(tree.seal_if_due(13).map(Epoch::timestamp), Some(13));
+    }
+
+    #[test]
+    fn sealing_in_the_past_fails() {
+        let mut tree = epoched(10);
+        tree.seal(5).unwrap();
+        assert_eq!(
+            tree.seal(4).map(Epoch::number),
+            Err(Error::TimestampRegressed {
+                last: 5,
+                timestamp: 4
+            })
+        );
+        assert_eq!(tree.count_epochs(), 1);
+
+        tree.set_interval(Some(0));
+        assert!(tree.seal_if_due(4).is_none());
+        assert_eq!(tree.seal_if_due(5).map(Epoch::number), Some(1));
+    }
+
+    #[test]
+    fn zero_retention_is_refused() {
+        let tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        assert!(matches!(
+            EpochedTree::try_new(tree, 0),
+            Err(Error::InvalidSize(0))
+        ));
+
+        let mut tree = epoched(2);
+        assert_eq!(tree.try_set_retention(0), Err(Error::InvalidSize(0)));
+        assert_eq!(tree.retention(), 2);
+    }
+}
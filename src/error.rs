@@ -0,0 +1,87 @@
+use std::fmt;
+
+use crate::Hash;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The tree root is not the one the caller expected.
+    RootMismatch { expected: Hash, actual: Hash },
+    /// A position does not relate to any leaf.
+    InvalidPosition(usize),
+    /// A tree cannot be left without leaves.
+    RemoveLastLeaf,
+    /// Bytes could not be decoded.
+    InvalidEncoding,
+    /// A stored node is not the hash of its children, or a leaf is not the
+    /// hash of its value and metadata. Also reported for a missing or extra
+    /// node, at the first position the level does not have the right length.
+    CorruptNode { level: usize, index: usize },
+    /// Decoded input describes more than the configured `Limits` allow.
+    LimitExceeded,
+    /// The tree has deferred changes that `root` has not recomputed yet.
+    DeferredChanges,
+    /// A range is empty or runs past the last leaf.
+    InvalidRange { start: usize, end: usize },
+    /// A leaf or sample count is zero or larger than the tree, or an epoch
+    /// retention, cache capacity or chunk size is zero.
+    InvalidSize(usize),
+    /// A height does not relate to any level of the tree.
+    InvalidHeight(usize),
+    /// A downloaded chunk or audited leaf does not fit its proof or the
+    /// expected layout.
+    InvalidChunk(usize),
+    /// A tree using `Padding::Reject` would end up with a leaf count that
+    /// is 1 or not a power of 2.
+    PaddingRejected(usize),
+    /// Trees to combine use different paddings.
+    PaddingMismatch,
+    /// An epoch would be sealed at a timestamp before the last one.
+    TimestampRegressed { last: u64, timestamp: u64 },
+    /// A record layout has no field with this name.
+    UnknownField(String),
+    /// A record layout names a field twice.
+    DuplicateField(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::RootMismatch { .. } => write!(f, "tree root does not match the expected root"),
+            Error::InvalidPosition(_) => write!(f, "position does not relate to any leaf"),
+            Error::RemoveLastLeaf => write!(f, "cannot remove the last leaf"),
+            Error::InvalidEncoding => write!(f, "invalid encoding"),
+            Error::LimitExceeded => write!(f, "input exceeds the configured limits"),
+            Error::DeferredChanges => write!(f, "tree has deferred changes, call root() first"),
+            Error::InvalidRange { .. } => write!(f, "range does not relate to any leaves"),
+            Error::InvalidSize(_) => write!(f, "size does not relate to the tree"),
+            Error::InvalidHeight(_) => write!(f, "height does not relate to any level"),
+            Error::InvalidChunk(position) => write!(f, "chunk {} does not verify", position),
+            Error::PaddingRejected(count) => {
+                write!(
+                    f,
+                    "{} leaves would need padding, which the tree rejects",
+                    count
+                )
+            }
+            Error::PaddingMismatch => write!(f, "trees use different paddings"),
+            Error::UnknownField(name) => write!(f, "record has no field {}", name),
+            Error::DuplicateField(name) => write!(f, "field {} is named twice", name),
+            Error::TimestampRegressed { last, timestamp } => {
+                write!(
+                    f,
+                    "epoch timestamp {} is before the last one, {}",
+                    timestamp, last
+                )
+            }
+            Error::CorruptNode { level, index } => {
+                write!(
+                    f,
+                    "node {} at level {} does not match the leaves",
+                    index, level
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,297 @@
+//! C bindings for SHA-256 trees, declared in `include/merkle.h`.
+//!
+//! Functions returning `int` return `MERKLE_OK` on success and one of the
+//! negative `MERKLE_ERR_*` codes otherwise. Nothing panics across the
+//! boundary: bad positions and malformed proofs are reported as errors.
+
+use std::ptr;
+use std::slice;
+
+use digest::Digest;
+use sha2::Sha256;
+
+use crate::{leaf_hash_with_hasher, Hash, MerkleTree, Proof};
+
+pub const MERKLE_OK: i32 = 0;
+/// A required pointer is null, or a count or position is out of range.
+pub const MERKLE_ERR_INVALID_ARGUMENT: i32 = -1;
+/// Proof bytes could not be decoded.
+pub const MERKLE_ERR_INVALID_ENCODING: i32 = -2;
+/// A proof decoded but does not prove the value against the root.
+pub const MERKLE_ERR_INVALID_PROOF: i32 = -3;
+
+/// Length of a root, leaf or node hash.
+pub const MERKLE_HASH_LEN: usize = 32;
+
+/// A tree of SHA-256 hashes, owned by the caller until `merkle_tree_free`.
+pub struct Tree {
+    inner: MerkleTree<Sha256>,
+}
+
+/// Bytes allocated by this library, released with `merkle_buffer_free`.
+#[repr(C)]
+pub struct Buffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl Buffer {
+    fn new(bytes: Vec<u8>) -> Buffer {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+        Buffer {
+            data: Box::into_raw(bytes) as *mut u8,
+            len,
+        }
+    }
+}
+
+/// Borrows `len` bytes at `data`, which may be null when `len` is 0.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len))
+    }
+}
+
+/// Builds a tree over `count` values, the `i`th being `lens[i]` bytes at
+/// `values[i]`. Returns null if `count` is 0 or a pointer is null.
+///
+/// # Safety
+///
+/// `values` and `lens` must point to `count` elements each, and every value
+/// must be readable for its length.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_new(
+    values: *const *const u8,
+    lens: *const usize,
+    count: usize,
+) -> *mut Tree {
+    if count == 0 || values.is_null() || lens.is_null() {
+        return ptr::null_mut();
+    }
+
+    let values = slice::from_raw_parts(values, count);
+    let lens = slice::from_raw_parts(lens, count);
+    let mut leaves: Vec<Hash> = Vec::with_capacity(count);
+    for (&value, &len) in values.iter().zip(lens) {
+        match bytes(value, len) {
+            Some(value) => leaves.push(leaf_hash_with_hasher(&value.to_vec(), Sha256::new())),
+            None => return ptr::null_mut(),
+        }
+    }
+
+    let inner = MerkleTree::build_from_leaves(leaves, Sha256::new());
+    Box::into_raw(Box::new(Tree { inner }))
+}
+
+/// Releases a tree made by `merkle_tree_new`. Does nothing for null.
+///
+/// # Safety
+///
+/// `tree` must be null or a tree not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_free(tree: *mut Tree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Returns the number of leaves, or 0 for null.
+///
+/// # Safety
+///
+/// `tree` must be null or a live tree.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_count_leaves(tree: *const Tree) -> usize {
+    match tree.as_ref() {
+        Some(tree) => tree.inner.count_leaves(),
+        None => 0,
+    }
+}
+
+/// Copies the root into the `MERKLE_HASH_LEN` bytes at `out`.
+///
+/// # Safety
+///
+/// `tree` must be null or a live tree, and `out` null or writable for
+/// `MERKLE_HASH_LEN` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_root(tree: *const Tree, out: *mut u8) -> i32 {
+    let tree = match tree.as_ref() {
+        Some(tree) if !out.is_null() => tree,
+        _ => return MERKLE_ERR_INVALID_ARGUMENT,
+    };
+
+    let root = tree.inner.root_hash();
+    ptr::copy_nonoverlapping(root.as_ptr(), out, root.len());
+    MERKLE_OK
+}
+
+/// Stores the inclusion proof of the leaf at `position` in `out`, encoded as
+/// `merkle_proof_verify` reads it.
+///
+/// # Safety
+///
+/// `tree` must be null or a live tree, and `out` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_gen_proof(
+    tree: *const Tree,
+    position: usize,
+    out: *mut Buffer,
+) -> i32 {
+    let tree = match tree.as_ref() {
+        Some(tree) if !out.is_null() => tree,
+        _ => return MERKLE_ERR_INVALID_ARGUMENT,
+    };
+
+    match tree.inner.try_gen_proof(position) {
+        Ok(proof) => {
+            out.write(Buffer::new(proof.to_bytes()));
+            MERKLE_OK
+        }
+        Err(_) => MERKLE_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Checks that `value` is the leaf proven by `proof` in the tree with `root`.
+/// Returns `MERKLE_OK` if it is and `MERKLE_ERR_INVALID_PROOF` if not.
+///
+/// # Safety
+///
+/// Each pointer must be readable for its length, or may be null if the
+/// length is 0.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_verify(
+    proof: *const u8,
+    proof_len: usize,
+    root: *const u8,
+    root_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    let (proof, root, value) = match (
+        bytes(proof, proof_len),
+        bytes(root, root_len),
+        bytes(value, value_len),
+    ) {
+        (Some(proof), Some(root), Some(value)) => (proof, root, value),
+        _ => return MERKLE_ERR_INVALID_ARGUMENT,
+    };
+
+    match Proof::from_bytes(proof) {
+        Ok(proof) if proof.validate::<_, Sha256>(root, &value.to_vec()) => MERKLE_OK,
+        Ok(_) => MERKLE_ERR_INVALID_PROOF,
+        Err(_) => MERKLE_ERR_INVALID_ENCODING,
+    }
+}
+
+/// Releases the bytes of a buffer filled by this library and empties it.
+///
+/// # Safety
+///
+/// `buffer` must be null or point to a buffer filled by this library and not
+/// freed already.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_buffer_free(buffer: *mut Buffer) {
+    if let Some(buffer) = buffer.as_mut() {
+        if !buffer.data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                buffer.data,
+                buffer.len,
+            )));
+        }
+        buffer.data = ptr::null_mut();
+        buffer.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [&[u8]; 3] = [b"a", b"b", b"c"];
+
+    fn new_tree() -> *mut Tree {
+        let values: Vec<*const u8> = VALUES.iter().map(|v| v.as_ptr()).collect();
+        let lens: Vec<usize> = VALUES.iter().map(|v| v.len()).collect();
+        unsafe { merkle_tree_new(values.as_ptr(), lens.as_ptr(), VALUES.len()) }
+    }
+
+    #[test]
+    fn tree_matches_the_rust_tree() {
+        let expected = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let tree = new_tree();
+        let mut root = [0u8; MERKLE_HASH_LEN];
+        unsafe {
+            assert_eq!(merkle_tree_count_leaves(tree), 3);
+            assert_eq!(merkle_tree_root(tree, root.as_mut_ptr()), MERKLE_OK);
+            merkle_tree_free(tree);
+        }
+        assert_eq!(&root[..], &expected.root_hash()[..]);
+    }
+
+    #[test]
+    fn generated_proof_verifies() {
+        let tree = new_tree();
+        let mut root = [0u8; MERKLE_HASH_LEN];
+        let mut proof = Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        unsafe {
+            merkle_tree_root(tree, root.as_mut_ptr());
+            assert_eq!(merkle_tree_gen_proof(tree, 1, &mut proof), MERKLE_OK);
+            let verify = |value: &[u8]| {
+                merkle_proof_verify(
+                    proof.data,
+                    proof.len,
+                    root.as_ptr(),
+                    root.len(),
+                    value.as_ptr(),
+                    value.len(),
+                )
+            };
+            assert_eq!(verify(b"b"), MERKLE_OK);
+            assert_eq!(verify(b"c"), MERKLE_ERR_INVALID_PROOF);
+
+            merkle_buffer_free(&mut proof);
+            assert!(proof.data.is_null());
+            merkle_tree_free(tree);
+        }
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        let tree = new_tree();
+        let mut proof = Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        unsafe {
+            assert!(merkle_tree_new(ptr::null(), ptr::null(), 0).is_null());
+            assert_eq!(merkle_tree_count_leaves(ptr::null()), 0);
+            assert_eq!(
+                merkle_tree_root(tree, ptr::null_mut()),
+                MERKLE_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                merkle_tree_gen_proof(tree, 3, &mut proof),
+                MERKLE_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                merkle_proof_verify(b"x".as_ptr(), 1, ptr::null(), 0, ptr::null(), 0),
+                MERKLE_ERR_INVALID_ENCODING
+            );
+            assert_eq!(
+                merkle_proof_verify(ptr::null(), 1, ptr::null(), 0, ptr::null(), 0),
+                MERKLE_ERR_INVALID_ARGUMENT
+            );
+            merkle_tree_free(tree);
+            merkle_tree_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Fuzz targets exercising the same decoding and verification paths as
+//! production callers, for `cargo fuzz`, oss-fuzz or any harness feeding
+//! byte strings.
+//!
+//! Each target takes an arbitrary input, returns normally on inputs the
+//! crate rejects, and panics only when a property of the tree or its proofs
+//! does not hold. Inputs are laid out with the encoding of `to_bytes`:
+//! little-endian `u64` integers and byte strings prefixed by their length,
+//! so structured corpora can be written with the same code that encodes
+//! proofs. All targets hash with SHA-256.
+//!
+//! Snapshots have no encoding of their own; replicas receive trees as
+//! patches, so `decode_patch` covers that path.
+
+use sha2::Sha256;
+
+use crate::codec::Reader;
+use crate::{hashes_equal, ConsistencyProof, MerkleTree, Mutation, Patch, Proof};
+
+/// Most initial leaves `apply_mutations` builds, to keep runs fast.
+const MAX_INITIAL_LEAVES: usize = 64;
+
+/// Decodes a proof and validates it.
+///
+/// The input is a root, a value and an encoded `Proof`, each as a byte
+/// string. Checks that a decoded proof encodes back to the same bytes and
+/// that a proof passing strict validation also passes plain validation.
+pub fn verify_proof(data: &[u8]) {
+    let mut r = Reader::new(data);
+    let (root, value, bytes) = match (r.bytes(), r.bytes(), r.bytes()) {
+        (Ok(root), Ok(value), Ok(bytes)) => (root, value.to_vec(), bytes),
+        _ => return,
+    };
+    let proof = match Proof::from_bytes(bytes) {
+        Ok(proof) => proof,
+        Err(_) => return,
+    };
+
+    assert_eq!(proof.to_bytes(), bytes, "proof does not encode back");
+    let valid = proof.validate_with_hasher(root, &value, Sha256::default());
+    let strict = proof.validate_strict_with_hasher(root, &value, Sha256::default());
+    assert!(
+        valid || !strict,
+        "strict validation accepts more than validation"
+    );
+}
+
+/// Decodes a consistency proof and verifies it.
+///
+/// The input is an old root, a new root and an encoded `ConsistencyProof`,
+/// each as a byte string. Checks that a decoded proof encodes back to the
+/// same bytes.
+pub fn verify_consistency_proof(data: &[u8]) {
+    let mut r = Reader::new(data);
+    let (old_root, new_root, bytes) = match (r.bytes(), r.bytes(), r.bytes()) {
+        (Ok(old_root), Ok(new_root), Ok(bytes)) => (old_root, new_root, bytes),
+        _ => return,
+    };
+    let proof = match ConsistencyProof::from_bytes(bytes) {
+        Ok(proof) => proof,
+        Err(_) => return,
+    };
+
+    assert_eq!(proof.to_bytes(), bytes, "proof does not encode back");
+    proof.verify_with_hasher(old_root, new_root, Sha256::default());
+}
+
+/// Builds a tree over the leaves `0`, `1`, ... `count - 1`, each a single
+/// byte.
+fn initial_tree(count: usize) -> (MerkleTree<Sha256>, Vec<Vec<u8>>) {
+    let values: Vec<Vec<u8>> = (0..count).map(|i| vec![i as u8]).collect();
+    let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+    (tree, values)
+}
+
+/// Decodes a patch and applies it to a tree over the leaves named by the
+/// first byte of the input, between 1 and 256 single-byte leaves.
+///
+/// The rest of the input is an encoded `Patch`. Checks that a patch either
+/// leaves the tree untouched or gives it the root it promises, and that the
+/// tree is consistent afterwards.
+pub fn decode_patch(data: &[u8]) {
+    let (&count, bytes) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let patch = match Patch::from_bytes(bytes) {
+        Ok(patch) => patch,
+        Err(_) => return,
+    };
+
+    let (mut tree, _) = initial_tree(count as usize + 1);
+    let old_root = tree.root().clone();
+    match tree.apply_patch(&patch) {
+        Ok(()) => assert!(hashes_equal(tree.root(), patch.new_root())),
+        Err(_) => assert!(hashes_equal(tree.root(), &old_root)),
+    }
+    assert!(tree.validate().is_ok(), "patch left the tree inconsistent");
+}
+
+/// Applies a batch of mutations and compares the result with a tree built
+/// from scratch.
+///
+/// The input starts with the number of initial leaves as an integer, then
+/// holds mutations until it ends: a byte `0` followed by a position and a
+/// value for `Set`, `1` and a value for `Push`, `2` and a position for
+/// `Remove`. Checks that a rejected batch leaves the tree untouched and that
+/// an applied one gives the root of the same leaves hashed in one go.
+pub fn apply_mutations(data: &[u8]) {
+    let mut r = Reader::new(data);
+    let count = match r.usize() {
+        Ok(count) => count % MAX_INITIAL_LEAVES + 1,
+        Err(_) => return,
+    };
+
+    let mut mutations = Vec::new();
+    while let Ok(op) = r.u8() {
+        let mutation = match op {
+            0 => match (r.usize(), r.bytes()) {
+                (Ok(i), Ok(value)) => Mutation::Set(i, value.to_vec()),
+                _ => return,
+            },
+            1 => match r.bytes() {
+                Ok(value) => Mutation::Push(value.to_vec()),
+                Err(_) => return,
+            },
+            2 => match r.usize() {
+                Ok(i) => Mutation::Remove(i),
+                Err(_) => return,
+            },
+            _ => return,
+        };
+        mutations.push(mutation);
+    }
+
+    let (mut tree, mut values) = initial_tree(count);
+    let old_root = tree.root().clone();
+    if tree.try_apply(&mutations).is_err() {
+        assert!(
+            hashes_equal(tree.root(), &old_root),
+            "rejected batch changed the tree"
+        );
+        return;
+    }
+
+    for mutation in mutations {
+        match mutation {
+            Mutation::Set(i, value) => values[i] = value,
+            Mutation::Push(value) => values.push(value),
+            Mutation::Remove(i) => {
+                values.remove(i);
+            }
+        }
+    }
+    let expected = MerkleTree::build_with_hasher(&values, Sha256::default());
+    assert!(
+        hashes_equal(tree.root(), expected.root_hash()),
+        "batch and rebuild disagree"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Writer;
+
+    fn byte_strings(parts: &[&[u8]]) -> Vec<u8> {
+        let mut w = Writer::default();
+        for part in parts {
+            w.bytes(part);
+        }
+        w.into_bytes()
+    }
+
+    #[test]
+    fn targets_accept_garbage() {
+        for data in [&b""[..], b"\x00", b"\xff\xff\xff\xff", &[7u8; 100]] {
+            verify_proof(data);
+            verify_consistency_proof(data);
+            decode_patch(data);
+            apply_mutations(data);
+        }
+    }
+
+    #[test]
+    fn targets_run_on_well_formed_input() {
+        let (mut tree, values) = initial_tree(5);
+        let proof = tree.gen_proof(2).to_bytes();
+        verify_proof(&byte_strings(&[tree.root_hash(), &values[2], &proof]));
+        verify_proof(&byte_strings(&[tree.root_hash(), b"x", &proof]));
+
+        let old_root = tree.root_hash().clone();
+        let patch = tree.apply_with_patch(&[Mutation::Push(vec![5]), Mutation::Set(0, vec![9])]);
+        let consistency = tree.consistency_proof(5).to_bytes();
+        verify_consistency_proof(&byte_strings(&[&old_root, tree.root_hash(), &consistency]));
+        decode_patch(&[&[4u8][..], &patch.to_bytes()].concat());
+        decode_patch(&[&[0u8][..], &patch.to_bytes()].concat());
+
+        let mut w = Writer::default();
+        w.usize(4);
+        w.u8(0);
+        w.usize(1);
+        w.bytes(b"x");
+        w.u8(1);
+        w.bytes(b"y");
+        w.u8(2);
+        w.usize(0);
+        apply_mutations(&w.into_bytes());
+    }
+}
@@ -0,0 +1,182 @@
+use digest::Digest;
+
+use crate::{hash_leaf, AsBytes, Error, MerkleTree, Mutation, Padding, Proof};
+
+/// Proves a leaf of a grafted child tree against the root of its parent tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraftProof {
+    inner: Proof,
+    outer: Proof,
+}
+
+impl GraftProof {
+    /// Chains `inner`, a proof within the child tree, with `outer`, the proof
+    /// of the child root within the parent tree.
+    pub fn new(inner: Proof, outer: Proof) -> GraftProof {
+        GraftProof { inner, outer }
+    }
+
+    pub fn inner(&self) -> &Proof {
+        &self.inner
+    }
+
+    pub fn outer(&self) -> &Proof {
+        &self.outer
+    }
+
+    /// Returns true if `value` is a leaf of a child tree grafted under
+    /// `root_hash`.
+    pub fn validate_with_hasher<T, H>(&self, root_hash: &[u8], value: &T, hasher: H) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.validate_with_padding(root_hash, value, Padding::Duplicate, hasher)
+    }
+
+    /// Like `validate_with_hasher`, for a child and a parent tree both using
+    /// `padding`.
+    pub fn validate_with_padding<T, H>(
+        &self,
+        root_hash: &[u8],
+        value: &T,
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let leaf = hash_leaf(value, &mut hasher);
+        match self.inner.compute_root(leaf, padding, &mut hasher) {
+            Some(child_root) => {
+                let leaf = hash_leaf(&child_root, &mut hasher);
+                self.outer
+                    .validate_leaf(root_hash, leaf, padding, &mut hasher)
+            }
+            None => false,
+        }
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Appends the root of `child` as a new leaf and returns its position.
+    pub fn graft(&mut self, child: &MerkleTree<H>) -> usize {
+        self.push(child.root_hash());
+        self.count_leaves() - 1
+    }
+
+    /// Replaces the leaf at `position` with the root of `child`, for a child
+    /// that changed since it was grafted.
+    pub fn regraft(&mut self, position: usize, child: &MerkleTree<H>) {
+        self.apply(&[Mutation::Set(position, child.root_hash().clone())]);
+    }
+
+    /// Returns a proof of the leaf at `child_position` of `child`, grafted at
+    /// `position` of this tree, against the root of this tree.
+    pub fn gen_graft_proof(
+        &mut self,
+        position: usize,
+        child: &MerkleTree<H>,
+        child_position: usize,
+    ) -> GraftProof {
+        self.try_gen_graft_proof(position, child, child_position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `gen_graft_proof`, but fails with `Error::InvalidPosition`
+    /// instead of panicking if `child` is not grafted at `position` or
+    /// `child_position` does not relate to any leaf of it.
+    pub fn try_gen_graft_proof(
+        &mut self,
+        position: usize,
+        child: &MerkleTree<H>,
+        child_position: usize,
+    ) -> Result<GraftProof, Error> {
+        if !self.try_verify(position, child.try_root_hash()?)? {
+            return Err(Error::InvalidPosition(position));
+        }
+
+        Ok(GraftProof::new(
+            child.try_gen_proof(child_position)?,
+            self.try_gen_proof(position)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn graft_proof_validates_child_leaf() {
+        let child = MerkleTree::build_with_hasher(&["x", "y", "z"], Sha256::default());
+        let mut parent = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let position = parent.graft(&child);
+        assert_eq!(position, 2);
+
+        let proof = parent.gen_graft_proof(position, &child, 1);
+        assert!(proof.validate_with_hasher(parent.root_hash(), &"y", Sha256::default()));
+        assert!(!proof.validate_with_hasher(parent.root_hash(), &"x", Sha256::default()));
+    }
+
+    #[test]
+    fn regraft_follows_child_changes() {
+        let mut child = MerkleTree::build_with_hasher(&["x", "y"], Sha256::default());
+        let mut parent = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        let position = parent.graft(&child);
+        let stale = parent.gen_graft_proof(position, &child, 0);
+
+        child.push(&"z");
+        parent.regraft(position, &child);
+        let proof = parent.gen_graft_proof(position, &child, 2);
+        assert!(proof.validate_with_hasher(parent.root_hash(), &"z", Sha256::default()));
+        assert!(!stale.validate_with_hasher(parent.root_hash(), &"x", Sha256::default()));
+    }
+
+    #[test]
+    fn graft_proof_validates_with_padding() {
+        let mut child = MerkleTree::build_with_hasher(&["x", "y", "z"], Sha256::default());
+        child.set_padding(Padding::Empty);
+        let mut parent = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        parent.set_padding(Padding::Empty);
+        let position = parent.graft(&child);
+
+        let proof = parent.gen_graft_proof(position, &child, 2);
+        let root = parent.root_hash();
+        assert!(proof.validate_with_padding(root, &"z", Padding::Empty, Sha256::default()));
+        assert!(!proof.validate_with_padding(root, &"z", Padding::Duplicate, Sha256::default()));
+        assert!(!proof.validate_with_hasher(root, &"z", Sha256::default()));
+    }
+
+    #[test]
+    #[should_panic(expected = "position does not relate to any leaf")]
+    fn gen_graft_proof_rejects_other_child() {
+        let child = MerkleTree::build_with_hasher(&["x", "y"], Sha256::default());
+        let mut parent = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        parent.gen_graft_proof(1, &child, 0);
+    }
+
+    #[test]
+    fn try_gen_graft_proof_fails_instead_of_panicking() {
+        let child = MerkleTree::build_with_hasher(&["x", "y"], Sha256::default());
+        let mut parent = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        assert_eq!(
+            parent.try_gen_graft_proof(1, &child, 0),
+            Err(Error::InvalidPosition(1))
+        );
+        assert_eq!(
+            parent.try_gen_graft_proof(5, &child, 0),
+            Err(Error::InvalidPosition(5))
+        );
+
+        let position = parent.graft(&child);
+        assert_eq!(
+            parent.try_gen_graft_proof(position, &child, 2),
+            Err(Error::InvalidPosition(2))
+        );
+        assert!(parent.try_gen_graft_proof(position, &child, 1).is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+//! A gRPC service serving roots and proofs of a tree, and a client for it.
+//!
+//! The `merkle.Proofs` service has three unary methods, `GetRoot`,
+//! `GetInclusionProof` and `GetConsistencyProof`. Proofs travel in the
+//! encoding of their `to_bytes` methods.
+
+use tokio::sync::watch;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Response, Status};
+
+use crate::{ConsistencyProof, Error, Hash, Proof, Snapshot};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/merkle.Proofs.rs"));
+}
+
+pub use generated::proofs_client::ProofsClient;
+pub use generated::proofs_server::{Proofs, ProofsServer};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetRootRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetRootResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub count_leaves: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetInclusionProofRequest {
+    #[prost(uint64, tag = "1")]
+    pub position: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetConsistencyProofRequest {
+    #[prost(uint64, tag = "1")]
+    pub old_count_leaves: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProofResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub proof: Vec<u8>,
+}
+
+fn status(e: Error) -> Status {
+    match e {
+        Error::InvalidPosition(_) | Error::InvalidSize(_) => Status::out_of_range(e.to_string()),
+        _ => Status::invalid_argument(e.to_string()),
+    }
+}
+
+fn too_large<E>(_: E) -> Status {
+    Status::out_of_range("count does not fit a usize")
+}
+
+/// Serves the latest snapshot sent on a watch channel, so the writer of the
+/// tree publishes each new version with `Sender::send` while requests keep
+/// reading a consistent one.
+///
+/// Trees kept in an `AsyncNodeStore` are served by loading them with
+/// `MerkleTree::load_async` and sending their snapshot.
+#[derive(Clone, Debug)]
+pub struct ProofServer {
+    snapshots: watch::Receiver<Snapshot>,
+}
+
+impl ProofServer {
+    pub fn new(snapshots: watch::Receiver<Snapshot>) -> ProofServer {
+        ProofServer { snapshots }
+    }
+
+    /// Wraps the server into the service to add to a
+    /// `tonic::transport::Server`.
+    pub fn into_service(self) -> ProofsServer<ProofServer> {
+        ProofsServer::new(self)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        self.snapshots.borrow().clone()
+    }
+}
+
+#[tonic::async_trait]
+impl Proofs for ProofServer {
+    async fn get_root(
+        &self,
+        _request: Request<GetRootRequest>,
+    ) -> Result<Response<GetRootResponse>, Status> {
+        let snapshot = self.snapshot();
+        Ok(Response::new(GetRootResponse {
+            root: snapshot.root_hash().clone(),
+            count_leaves: snapshot.count_leaves() as u64,
+        }))
+    }
+
+    async fn get_inclusion_proof(
+        &self,
+        request: Request<GetInclusionProofRequest>,
+    ) -> Result<Response<ProofResponse>, Status> {
+        let position = usize::try_from(request.into_inner().position).map_err(too_large)?;
+        let proof = self.snapshot().try_gen_proof(position).map_err(status)?;
+        Ok(Response::new(ProofResponse {
+            proof: proof.to_bytes(),
+        }))
+    }
+
+    async fn get_consistency_proof(
+        &self,
+        request: Request<GetConsistencyProofRequest>,
+    ) -> Result<Response<ProofResponse>, Status> {
+        let old_count_leaves =
+            usize::try_from(request.into_inner().old_count_leaves).map_err(too_large)?;
+        let proof = self
+            .snapshot()
+            .try_consistency_proof(old_count_leaves)
+            .map_err(status)?;
+        Ok(Response::new(ProofResponse {
+            proof: proof.to_bytes(),
+        }))
+    }
+}
+
+/// Calls a `ProofServer` and decodes what it returns. Proofs still have to
+/// be validated against a root the caller trusts.
+#[derive(Clone, Debug)]
+pub struct ProofClient {
+    inner: ProofsClient<Channel>,
+}
+
+impl ProofClient {
+    pub fn new(channel: Channel) -> ProofClient {
+        ProofClient {
+            inner: ProofsClient::new(channel),
+        }
+    }
+
+    pub async fn connect<D>(endpoint: D) -> Result<ProofClient, tonic::transport::Error>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let channel = Endpoint::new(endpoint)?.connect().await?;
+        Ok(ProofClient::new(channel))
+    }
+
+    /// Returns the current root and number of leaves.
+    pub async fn root(&mut self) -> Result<(Hash, usize), Status> {
+        let response = self.inner.get_root(GetRootRequest {}).await?.into_inner();
+        Ok((
+            response.root,
+            usize::try_from(response.count_leaves).map_err(too_large)?,
+        ))
+    }
+
+    pub async fn inclusion_proof(&mut self, position: usize) -> Result<Proof, Status> {
+        let request = GetInclusionProofRequest {
+            position: position as u64,
+        };
+        let response = self.inner.get_inclusion_proof(request).await?.into_inner();
+        Proof::from_bytes(&response.proof).map_err(|e| Status::data_loss(e.to_string()))
+    }
+
+    pub async fn consistency_proof(
+        &mut self,
+        old_count_leaves: usize,
+    ) -> Result<ConsistencyProof, Status> {
+        let request = GetConsistencyProofRequest {
+            old_count_leaves: old_count_leaves as u64,
+        };
+        let response = self
+            .inner
+            .get_consistency_proof(request)
+            .await?
+            .into_inner();
+        ConsistencyProof::from_bytes(&response.proof).map_err(|e| Status::data_loss(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+    use tonic::Code;
+
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::MerkleTree;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default())
+    }
+
+    #[test]
+    fn server_answers_from_the_latest_snapshot() {
+        let mut tree = tree();
+        let (sender, receiver) = watch::channel(tree.snapshot());
+        let server = ProofServer::new(receiver);
+        let old_root = tree.root_hash().clone();
+        tree.push(&"d");
+        sender.send(tree.snapshot()).unwrap();
+
+        let root = block_on(server.get_root(Request::new(GetRootRequest {})))
+            .unwrap()
+            .into_inner();
+        assert_eq!((&root.root, root.count_leaves), (tree.root_hash(), 4));
+
+        let request = Request::new(GetInclusionProofRequest { position: 3 });
+        let proof = block_on(server.get_inclusion_proof(request))
+            .unwrap()
+            .into_inner();
+        assert_eq!(Proof::from_bytes(&proof.proof), Ok(tree.gen_proof(3)));
+
+        let request = Request::new(GetConsistencyProofRequest {
+            old_count_leaves: 3,
+        });
+        let proof = block_on(server.get_consistency_proof(request))
+            .unwrap()
+            .into_inner();
+        let proof = ConsistencyProof::from_bytes(&proof.proof).unwrap();
+        assert!(proof.verify_with_hasher(&old_root, tree.root_hash(), Sha256::default()));
+    }
+
+    #[test]
+    fn bad_requests_map_to_status_codes() {
+        let (_sender, receiver) = watch::channel(tree().snapshot());
+        let server = ProofServer::new(receiver);
+
+        let request = Request::new(GetInclusionProofRequest { position: 3 });
+        let denied = block_on(server.get_inclusion_proof(request)).unwrap_err();
+        assert_eq!(denied.code(), Code::OutOfRange);
+
+        assert_eq!(status(Error::InvalidEncoding).code(), Code::InvalidArgument);
+    }
+}
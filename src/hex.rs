@@ -0,0 +1,48 @@
+//! Lowercase hex, the text form of hashes in the `merkle` tool's output and
+//! in JSON proofs.
+
+use crate::Error;
+
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes hex digits of either case, two per byte. Fails with
+/// `Error::InvalidEncoding` on anything else, including signs and
+/// whitespace.
+pub fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    let digit = |b: u8| match b {
+        b'0'..=b'9' => Ok(b - b'0'),
+        b'a'..=b'f' => Ok(b - b'a' + 10),
+        b'A'..=b'F' => Ok(b - b'A' + 10),
+        _ => Err(Error::InvalidEncoding),
+    };
+
+    if s.len() % 2 == 1 {
+        return Err(Error::InvalidEncoding);
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let bytes = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(encode(&bytes), "007fabff");
+        assert_eq!(decode("007fabff"), Ok(bytes.to_vec()));
+        assert_eq!(decode("007FABFF"), Ok(bytes.to_vec()));
+    }
+
+    #[test]
+    fn rejects_anything_but_digit_pairs() {
+        for s in &["+f", "-1", " f", "0", "0g", "é0"] {
+            assert_eq!(decode(s), Err(Error::InvalidEncoding), "{:?}", s);
+        }
+    }
+}
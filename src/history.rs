@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use digest::Digest;
+
+use crate::{Hash, MerkleTree, Proof, Snapshot};
+
+/// Past versions of a tree kept for clients that pinned an older root.
+#[derive(Default)]
+pub(crate) struct History {
+    retention: usize,
+    versions: VecDeque<(u64, Snapshot)>,
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the version of the tree, which grows by one every time changes
+    /// are recomputed into a new root.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Keeps up to `versions` past versions queryable by version number.
+    ///
+    /// Each retained version holds its own copy of the node storage, so memory
+    /// grows with the retention. Zero, the default, keeps no history.
+    pub fn set_retention(&mut self, versions: usize) {
+        self.history.retention = versions;
+        self.trim_history();
+    }
+
+    /// Returns a view of the tree as it was at `version`, if it is still
+    /// retained.
+    pub fn snapshot_at(&self, version: u64) -> Option<Snapshot> {
+        if version == self.version && !self.is_dirty() {
+            return Some(self.snapshot());
+        }
+
+        self.history
+            .versions
+            .iter()
+            .find(|(v, _)| *v == version)
+            .map(|(_, snapshot)| snapshot.clone())
+    }
+
+    pub fn root_at(&self, version: u64) -> Option<Hash> {
+        self.snapshot_at(version).map(|s| s.root_hash().clone())
+    }
+
+    pub fn proof_at(&self, version: u64, position: usize) -> Option<Proof> {
+        self.snapshot_at(version)
+            .and_then(|s| s.try_gen_proof(position).ok())
+    }
+
+    /// Records the current version before its leaves are changed.
+    pub(crate) fn record_version(&mut self) {
+        if self.history.retention == 0 || self.is_dirty() {
+            return;
+        }
+
+        let snapshot = self.snapshot();
+        self.history.versions.push_back((self.version, snapshot));
+        self.trim_history();
+    }
+
+    fn trim_history(&mut self) {
+        while self.history.versions.len() > self.history.retention {
+            self.history.versions.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use crate::MerkleTree;
+
+    #[test]
+    fn proof_at_bad_position_is_none() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.set_retention(2);
+        let version = tree.version();
+        tree.push(&"c");
+
+        assert!(tree.proof_at(version, 1).is_some());
+        assert!(tree.proof_at(version, 2).is_none());
+        assert!(tree.proof_at(tree.version(), 2).is_some());
+    }
+
+    #[test]
+    fn retains_only_the_latest_versions() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        tree.set_retention(2);
+        let mut roots = vec![(tree.version(), tree.root_hash().clone())];
+        for value in ["b", "c", "d"] {
+            tree.push(&value);
+            roots.push((tree.version(), tree.root_hash().clone()));
+        }
+
+        assert_eq!(tree.root_at(roots[0].0), None);
+        for (version, root) in &roots[1..] {
+            assert_eq!(tree.root_at(*version).as_ref(), Some(root));
+        }
+
+        tree.set_retention(1);
+        assert_eq!(tree.root_at(roots[1].0), None);
+        assert_eq!(tree.root_at(roots[2].0).as_ref(), Some(&roots[2].1));
+    }
+
+    #[test]
+    fn no_history_by_default() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        let version = tree.version();
+        tree.push(&"b");
+        assert_eq!(tree.version(), version + 1);
+        assert_eq!(tree.root_at(version), None);
+    }
+}
@@ -0,0 +1,85 @@
+use digest::Digest;
+
+use crate::{Hash, MerkleTree};
+
+/// Describes how the root moved after a batch of changes was recomputed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootChange {
+    pub old_root: Hash,
+    pub new_root: Hash,
+    pub old_count_leaves: usize,
+    pub count_leaves: usize,
+    /// Sorted positions of the leaves that were set, pushed or shifted.
+    pub changed: Vec<usize>,
+}
+
+pub(crate) type RootHook = Box<dyn FnMut(&RootChange) + Send>;
+
+impl<H: Digest> MerkleTree<H> {
+    /// Registers `hook` to be called every time changes to the tree have been
+    /// recomputed into a new root.
+    ///
+    /// Hooks run in registration order, after the tree is consistent again. To
+    /// forward changes to another thread, send them over a channel from the
+    /// hook.
+    pub fn on_root_change<F>(&mut self, hook: F)
+    where
+        F: FnMut(&RootChange) + Send + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub(crate) fn notify_root_change(&mut self, change: RootChange) {
+        for hook in self.hooks.iter_mut() {
+            hook(&change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn hooks_see_every_recomputed_root_in_order() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let old_root = tree.root_hash().clone();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..2 {
+            let changes = changes.clone();
+            tree.on_root_change(move |change| changes.lock().unwrap().push((i, change.clone())));
+        }
+
+        tree.apply(&[Mutation::Set(1, "d"), Mutation::Push("e")]);
+        let expected = RootChange {
+            old_root,
+            new_root: tree.root_hash().clone(),
+            old_count_leaves: 3,
+            count_leaves: 4,
+            changed: vec![1, 3],
+        };
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![(0, expected.clone()), (1, expected)]
+        );
+    }
+
+    #[test]
+    fn deferred_changes_notify_once_on_flush() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let count = Arc::new(Mutex::new(0));
+        let hook_count = count.clone();
+        tree.on_root_change(move |_| *hook_count.lock().unwrap() += 1);
+
+        tree.defer(&[Mutation::Set(0, "c")]);
+        tree.defer(&[Mutation::Set(1, "d")]);
+        assert_eq!(*count.lock().unwrap(), 0);
+        tree.root();
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+}
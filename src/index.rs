@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use digest::Digest;
+
+use crate::{Hash, MerkleTree};
+
+/// Positions of every leaf hash, in increasing order.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LeafIndex {
+    positions: HashMap<Hash, Vec<usize>>,
+}
+
+impl LeafIndex {
+    pub(crate) fn build(leaves: &[Hash]) -> LeafIndex {
+        let mut index = LeafIndex::default();
+        for (i, leaf) in leaves.iter().enumerate() {
+            index.insert(leaf.clone(), i);
+        }
+        index
+    }
+
+    pub(crate) fn insert(&mut self, leaf: Hash, position: usize) {
+        let positions = self.positions.entry(leaf).or_default();
+        if let Err(at) = positions.binary_search(&position) {
+            positions.insert(at, position);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, leaf: &[u8], position: usize) {
+        if let Some(positions) = self.positions.get_mut(leaf) {
+            positions.retain(|&i| i != position);
+            if positions.is_empty() {
+                self.positions.remove(leaf);
+            }
+        }
+    }
+
+    /// Approximate heap memory held by the map.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let entry = std::mem::size_of::<(Hash, Vec<usize>)>();
+        self.positions.capacity() * entry
+            + self
+                .positions
+                .iter()
+                .map(|(leaf, positions)| {
+                    leaf.capacity() + positions.capacity() * std::mem::size_of::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    fn first(&self, leaf: &[u8]) -> Option<usize> {
+        self.positions.get(leaf).map(|positions| positions[0])
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Keeps a map from leaf hashes to positions so `index_of` does not have
+    /// to scan the leaves, or drops it.
+    pub fn set_indexed(&mut self, enabled: bool) {
+        self.index = if enabled {
+            Some(LeafIndex::build(&self.levels[0]))
+        } else {
+            None
+        };
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Returns the first position whose leaf hash is `leaf`.
+    ///
+    /// Leaves with committed metadata are looked up by the hash stored in the
+    /// tree, which covers the metadata. Takes O(1) once `set_indexed` is on
+    /// and scans the leaves otherwise.
+    pub fn index_of(&self, leaf: &[u8]) -> Option<usize> {
+        match &self.index {
+            Some(index) => index.first(leaf),
+            None => self.leaves().position(|l| l.as_slice() == leaf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{leaf_hash_with_hasher, Mutation};
+
+    fn leaf(value: &str) -> Hash {
+        leaf_hash_with_hasher(&value, Sha256::default())
+    }
+
+    #[test]
+    fn index_agrees_with_scan_across_mutations() {
+        let values = ["a", "b", "a", "c"];
+        let mut indexed = MerkleTree::build_with_hasher(&values, Sha256::default());
+        indexed.set_indexed(true);
+        let mut scanned = MerkleTree::build_with_hasher(&values, Sha256::default());
+
+        let batches = [
+            vec![Mutation::Remove(0)],
+            vec![Mutation::Set(1, "d"), Mutation::Push("a")],
+            vec![Mutation::Set(0, "a"), Mutation::Remove(2)],
+        ];
+        for batch in &batches {
+            indexed.apply(batch);
+            scanned.apply(batch);
+            for value in ["a", "b", "c", "d", "e"] {
+                assert_eq!(
+                    indexed.index_of(&leaf(value)),
+                    scanned.index_of(&leaf(value)),
+                    "{}",
+                    value
+                );
+            }
+        }
+        assert_eq!(indexed.index_of(&leaf("a")), Some(0));
+        assert_eq!(indexed.index_of(&leaf("c")), None);
+    }
+
+    #[test]
+    fn index_can_be_dropped() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.set_indexed(true);
+        assert!(tree.is_indexed());
+        tree.set_indexed(false);
+        assert!(!tree.is_indexed());
+        assert_eq!(tree.index_of(&leaf("b")), Some(1));
+    }
+}
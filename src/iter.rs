@@ -0,0 +1,303 @@
+use std::slice;
+
+use digest::Digest;
+
+use crate::{Error, Hash, MerkleTree, Padding, Sibling};
+
+/// Iterator over `(node, sibling)` from a leaf up to the child of the root,
+/// created by `MerkleTree::path`.
+///
+/// The siblings are the ones a proof of the leaf holds, so the last node of
+/// a level with an odd count comes with what the tree's padding pairs it
+/// with.
+pub struct Path<'a> {
+    levels: &'a [Vec<Hash>],
+    position: usize,
+    level: usize,
+    padding: Padding,
+}
+
+impl<'a> Path<'a> {
+    pub(crate) fn new(levels: &'a [Vec<Hash>], position: usize, padding: Padding) -> Path<'a> {
+        Path {
+            levels,
+            position,
+            level: 0,
+            padding,
+        }
+    }
+}
+
+impl<'a> Iterator for Path<'a> {
+    type Item = (&'a Hash, Sibling);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.level + 1 >= self.levels.len() {
+            return None;
+        }
+
+        let nodes = &self.levels[self.level];
+        let j = self.position >> self.level;
+        self.level += 1;
+
+        let sibling = if j % 2 == 1 {
+            Sibling::Left(nodes[j - 1].clone())
+        } else if let Some(right) = nodes.get(j + 1) {
+            Sibling::Right(right.clone())
+        } else {
+            match self.padding {
+                Padding::Duplicate => Sibling::Duplicate,
+                Padding::Empty => Sibling::Empty,
+                Padding::Constant(sentinel) => Sibling::Padding(sentinel.as_bytes().to_vec()),
+                Padding::Reject => unreachable!("trees rejecting padding have no odd node"),
+                Padding::Promote => Sibling::Promoted,
+            }
+        };
+        Some((&nodes[j], sibling))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.levels.len() - 1 - self.level;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Path<'_> {}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the hash of the leaf at `position`.
+    pub fn leaf(&self, position: usize) -> Option<&Hash> {
+        self.levels[0].get(position)
+    }
+
+    /// Iterates over the leaf hashes in position order.
+    pub fn leaves(&self) -> slice::Iter<'_, Hash> {
+        self.levels[0].iter()
+    }
+
+    /// Iterates over the nodes from the leaf at `position` up to the root, with
+    /// their siblings.
+    pub fn path(&self, position: usize) -> Path<'_> {
+        self.try_path(position).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `path`, but fails instead of panicking if `position` does not
+    /// relate to any leaf or the tree has deferred changes.
+    pub fn try_path(&self, position: usize) -> Result<Path<'_>, Error> {
+        if position >= self.count_leaves() {
+            return Err(Error::InvalidPosition(position));
+        }
+        self.check_clean()?;
+
+        Ok(Path::new(&self.levels, position, self.padding))
+    }
+
+    /// Returns the sibling of the leaf at `position`, as its proof holds it.
+    pub fn sibling_of(&self, position: usize) -> Sibling {
+        self.try_sibling_of(position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `sibling_of`, but fails instead of panicking if `position` does
+    /// not relate to any leaf or the tree has deferred changes.
+    pub fn try_sibling_of(&self, position: usize) -> Result<Sibling, Error> {
+        let (_, sibling) = self
+            .try_path(position)?
+            .next()
+            .ok_or(Error::InvalidPosition(position))?;
+        Ok(sibling)
+    }
+
+    /// Iterates over the siblings of the ancestors of the leaf at `position`,
+    /// from its parent up to the child of the root.
+    pub fn uncles_of(&self, position: usize) -> impl ExactSizeIterator<Item = Sibling> + '_ {
+        self.try_uncles_of(position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `uncles_of`, but fails instead of panicking if `position` does
+    /// not relate to any leaf or the tree has deferred changes.
+    pub fn try_uncles_of(
+        &self,
+        position: usize,
+    ) -> Result<impl ExactSizeIterator<Item = Sibling> + '_, Error> {
+        Ok(self.try_path(position)?.skip(1).map(|(_, sibling)| sibling))
+    }
+
+    /// Iterates over `(index, hash)` of the nodes at height `h` above the
+    /// leaves, left to right. Height 0 holds the leaves and the root is alone
+    /// at the greatest height.
+    pub fn nodes_at_height(&self, h: usize) -> impl ExactSizeIterator<Item = (usize, &Hash)> {
+        self.try_nodes_at_height(h)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `nodes_at_height`, but fails instead of panicking if `h` does not
+    /// relate to any level or the tree has deferred changes.
+    pub fn try_nodes_at_height(
+        &self,
+        h: usize,
+    ) -> Result<impl ExactSizeIterator<Item = (usize, &Hash)>, Error> {
+        if h >= self.levels.len() {
+            return Err(Error::InvalidHeight(h));
+        }
+        self.check_clean()?;
+
+        Ok(self.levels[h].iter().enumerate())
+    }
+
+    /// Iterates over the levels of the tree, from the root down to the leaves.
+    pub fn levels(&self) -> impl DoubleEndedIterator<Item = &[Hash]> + ExactSizeIterator {
+        self.try_levels().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `levels`, but fails instead of panicking if the tree has deferred
+    /// changes.
+    pub fn try_levels(
+        &self,
+    ) -> Result<impl DoubleEndedIterator<Item = &[Hash]> + ExactSizeIterator, Error> {
+        self.check_clean()?;
+
+        Ok(self.levels.iter().rev().map(|level| level.as_slice()))
+    }
+}
+
+impl<'a, H: Digest> IntoIterator for &'a MerkleTree<H> {
+    type Item = &'a Hash;
+    type IntoIter = slice::Iter<'a, Hash>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.leaves()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default())
+    }
+
+    #[test]
+    fn try_variants_fail_on_bad_input() {
+        let tree = tree();
+        assert!(matches!(tree.try_path(3), Err(Error::InvalidPosition(3))));
+        assert!(matches!(
+            tree.try_sibling_of(3),
+            Err(Error::InvalidPosition(3))
+        ));
+        assert!(matches!(
+            tree.try_uncles_of(3),
+            Err(Error::InvalidPosition(3))
+        ));
+        assert!(matches!(
+            tree.try_nodes_at_height(3),
+            Err(Error::InvalidHeight(3))
+        ));
+        assert_eq!(tree.try_nodes_at_height(2).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn try_variants_fail_on_deferred_changes() {
+        let mut tree = tree();
+        tree.defer(&[Mutation::Set(0, "z")]);
+        assert!(matches!(tree.try_path(0), Err(Error::DeferredChanges)));
+        assert!(matches!(
+            tree.try_nodes_at_height(0),
+            Err(Error::DeferredChanges)
+        ));
+        assert!(matches!(tree.try_levels(), Err(Error::DeferredChanges)));
+
+        tree.flush();
+        assert_eq!(tree.try_levels().unwrap().len(), 3);
+        assert_eq!(tree.try_uncles_of(0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn siblings_match_proofs_under_every_padding() {
+        let sentinel = Padding::Constant(crate::Sentinel::new(&[7u8; 32]));
+        for &padding in &[Padding::Duplicate, Padding::Empty, sentinel] {
+            let mut tree =
+                MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+            tree.set_padding(padding);
+            for position in 0..5 {
+                let path = tree.gen_proof(position).path().to_vec();
+                assert_eq!(tree.sibling_of(position), path[0]);
+                assert_eq!(tree.uncles_of(position).collect::<Vec<_>>(), path[1..]);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_last_node_comes_with_its_padding() {
+        let mut tree = tree();
+        tree.set_padding(Padding::Empty);
+        assert_eq!(tree.sibling_of(2), Sibling::Empty);
+
+        let (node, sibling) = tree.path(2).next().unwrap();
+        assert_eq!(node, tree.leaf(2).unwrap());
+        assert_eq!(sibling, Sibling::Empty);
+    }
+
+    #[test]
+    fn leaves_are_the_leaf_hashes_in_order() {
+        let tree = tree();
+        let expected: Vec<Hash> = ["a", "b", "c"]
+            .iter()
+            .map(|v| crate::leaf_hash_with_hasher(v, Sha256::default()))
+            .collect();
+
+        assert_eq!(tree.leaves().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!((&tree).into_iter().count(), 3);
+        assert_eq!(tree.leaf(2), Some(&expected[2]));
+        assert_eq!(tree.leaf(3), None);
+    }
+
+    #[test]
+    fn levels_go_from_root_to_leaves() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        let widths: Vec<usize> = tree.levels().map(|level| level.len()).collect();
+        assert_eq!(widths, vec![1, 2, 3, 5]);
+
+        let mut levels = tree.levels();
+        assert_eq!(levels.next().unwrap(), &[tree.root_hash().clone()][..]);
+        assert_eq!(
+            levels.next_back().unwrap(),
+            &tree.leaves().cloned().collect::<Vec<_>>()[..]
+        );
+    }
+
+    #[test]
+    fn path_walks_the_ancestors_of_a_leaf() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        let levels: Vec<&[Hash]> = tree.levels().rev().collect();
+        for position in 0..5 {
+            let path: Vec<(&Hash, Sibling)> = tree.path(position).collect();
+            assert_eq!(tree.path(position).len(), 3);
+
+            let proof = tree.gen_proof(position);
+            for (level, (node, sibling)) in path.into_iter().enumerate() {
+                assert_eq!(node, &levels[level][position >> level]);
+                assert_eq!(sibling, proof.path()[level]);
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_at_height_match_levels() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        let levels: Vec<&[Hash]> = tree.levels().rev().collect();
+        for (h, level) in levels.iter().enumerate() {
+            let nodes: Vec<(usize, &Hash)> = tree.nodes_at_height(h).collect();
+            assert_eq!(nodes, level.iter().enumerate().collect::<Vec<_>>());
+        }
+        assert_eq!(
+            tree.nodes_at_height(3).collect::<Vec<_>>(),
+            vec![(0, tree.root_hash())]
+        );
+    }
+}
@@ -0,0 +1,576 @@
+extern crate digest;
+
+mod appender;
+mod audit;
+#[cfg(feature = "backup")]
+pub mod backup;
+pub mod base64;
+mod cache;
+mod canonical;
+mod checkpoint;
+mod codec;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+mod consistency;
+mod context;
+mod cursor;
+mod diff;
+mod download;
+mod epoch;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+mod graft;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hex;
+mod history;
+mod hooks;
+mod index;
+mod iter;
+mod limits;
+mod merge;
+mod metadata;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod mutation;
+mod padding;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod patch;
+mod proof;
+#[cfg(feature = "python")]
+mod python;
+mod range;
+mod record;
+#[cfg(feature = "rand")]
+mod sample;
+mod signed;
+mod snapshot;
+mod split;
+mod stats;
+#[cfg(feature = "tokio")]
+mod store;
+mod sync;
+mod tail;
+#[cfg(all(test, feature = "tokio"))]
+mod test_util;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transaction;
+mod undo;
+mod validate;
+mod values;
+mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "zeroize")]
+mod wipe;
+
+use std::sync::Arc;
+
+use digest::Digest;
+use subtle::ConstantTimeEq;
+
+pub use appender::Appender;
+pub use audit::{AuditChallenge, AuditResponse};
+pub use cache::{CacheMetrics, ProofCache};
+#[cfg(feature = "serde_json")]
+pub use canonical::Json;
+pub use canonical::{Canonical, Canonicalize};
+pub use checkpoint::{Checkpoint, NoteKey, NoteSignature, SignedCheckpoint};
+pub use consistency::ConsistencyProof;
+pub use context::{bound_leaf_hash_with_hasher, BoundProof};
+pub use cursor::Cursor;
+pub use diff::Diff;
+pub use download::Download;
+pub use epoch::{Epoch, EpochedTree};
+pub use error::Error;
+pub use graft::GraftProof;
+pub use hooks::RootChange;
+pub use iter::Path;
+pub use limits::Limits;
+pub use metadata::Metadata;
+#[cfg(feature = "metrics")]
+pub use metrics::{counters, reset_counters, Counters};
+pub use mutation::Mutation;
+pub use padding::{Padding, Sentinel};
+pub use patch::Patch;
+pub use proof::{Proof, Sibling};
+pub use range::RangeProof;
+pub use record::{FieldLayout, FieldProof, Record, RecordProof};
+pub use signed::{RootSigner, RootVerifier, SignedRoot};
+pub use snapshot::Snapshot;
+pub use stats::Stats;
+#[cfg(feature = "tokio")]
+pub use store::{gen_proof_async, AsyncNodeStore, MemoryStore};
+pub use sync::Peer;
+pub use tail::TailVerifier;
+pub use transaction::Transaction;
+
+const LEAF_SIG: u8 = 0u8;
+const INTERNAL_SIG: u8 = 1u8;
+
+pub type Hash = Vec<u8>;
+
+/// Returns the smallest power of 2 not below `n`, or `None` if it does not
+/// fit in a `usize`. The power of 2 closest to 0 is 1.
+pub fn next_power_of_2(n: usize) -> Option<usize> {
+    n.checked_next_power_of_two()
+}
+
+pub trait AsBytes {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl AsBytes for &str {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl AsBytes for String {
+    fn as_bytes(&self) -> &[u8] {
+        String::as_bytes(self)
+    }
+}
+
+impl AsBytes for Vec<u8> {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Returns the number of levels above the leaves of a tree with
+/// `count_leaves` leaves. Even a single leaf is hashed into a root.
+fn height(count_leaves: usize) -> usize {
+    let bits = (usize::BITS - (count_leaves - 1).leading_zeros()) as usize;
+    bits.max(1)
+}
+
+/// Returns the number of nodes at `level` of a tree with `count_leaves` leaves.
+fn count_at_level(count_leaves: usize, level: usize) -> usize {
+    ((count_leaves - 1) >> level) + 1
+}
+
+/// Returns the hash a tree using `hasher` stores for the leaf `value`.
+pub fn leaf_hash_with_hasher<T, H>(value: &T, mut hasher: H) -> Hash
+where
+    H: Digest,
+    T: AsBytes,
+{
+    hash_leaf(value, &mut hasher)
+}
+
+fn hash_leaf<T, H>(value: &T, hasher: &mut H) -> Hash
+where
+    H: Digest,
+    T: AsBytes,
+{
+    #[cfg(feature = "metrics")]
+    metrics::record_hash(1 + value.as_bytes().len());
+
+    hasher.update([LEAF_SIG]);
+    hasher.update(value.as_bytes());
+    hasher.finalize_reset().to_vec()
+}
+
+/// Compares hashes in time that depends on their lengths only, so verifiers
+/// answering requests online do not reveal how much of a forged hash matched.
+fn hashes_equal(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Hashes `left` with `right`, or with the node `padding` pairs it with when
+/// it is the odd last node of its level.
+fn hash_internal_node<H>(
+    left: &Hash,
+    right: Option<&Hash>,
+    padding: Padding,
+    hasher: &mut H,
+) -> Hash
+where
+    H: Digest,
+{
+    let pad = match right {
+        None if padding == Padding::Promote => return left.clone(),
+        None => padding::pad_hash(padding, hasher),
+        Some(_) => None,
+    };
+
+    let right = right.or(pad.as_ref()).unwrap_or(left);
+    #[cfg(feature = "metrics")]
+    metrics::record_hash(1 + left.len() + right.len());
+
+    hasher.update([INTERNAL_SIG]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize_reset().to_vec()
+}
+
+/// Hashes `node`, the odd last node of its level, with what `padding` pairs
+/// it with, or returns `None` under `Padding::Reject`, for verifiers that
+/// must not panic on untrusted shapes.
+fn hash_odd_node<H>(node: &Hash, padding: Padding, hasher: &mut H) -> Option<Hash>
+where
+    H: Digest,
+{
+    match padding {
+        Padding::Reject => None,
+        _ => Some(hash_internal_node(node, None, padding, hasher)),
+    }
+}
+
+fn build_upper_level<H>(nodes: &[Hash], padding: Padding, hasher: &mut H) -> Vec<Hash>
+where
+    H: Digest,
+{
+    let mut result = Vec::with_capacity(nodes.len().div_ceil(2));
+    let mut i = 0;
+
+    while i < nodes.len() {
+        if i + 1 < nodes.len() {
+            result.push(hash_internal_node(
+                &nodes[i],
+                Some(&nodes[i + 1]),
+                padding,
+                hasher,
+            ));
+            i += 2;
+        } else {
+            result.push(hash_internal_node(&nodes[i], None, padding, hasher));
+            i += 1;
+        }
+    }
+
+    result
+}
+
+/// Builds every level above `leaves`, ending with the single root node.
+///
+/// `levels[0]` holds the leaves and the last level holds the root. An odd node
+/// at the end of a level is paired as `padding` says.
+fn build_levels<H>(leaves: Vec<Hash>, padding: Padding, hasher: &mut H) -> Vec<Vec<Hash>>
+where
+    H: Digest,
+{
+    let mut levels = vec![leaves];
+
+    loop {
+        let upper = build_upper_level(levels.last().unwrap(), padding, hasher);
+        let is_root = upper.len() == 1;
+        levels.push(upper);
+        if is_root {
+            break;
+        }
+    }
+
+    levels
+}
+
+/// Builds the levels of a tree over `leaves`, taking every complete subtree
+/// that `reuse(level, i)` can provide instead of hashing it again.
+fn build_levels_reusing<H, F>(
+    leaves: Vec<Hash>,
+    padding: Padding,
+    hasher: &mut H,
+    mut reuse: F,
+) -> Vec<Vec<Hash>>
+where
+    H: Digest,
+    F: FnMut(usize, usize) -> Option<Hash>,
+{
+    let count_leaves = leaves.len();
+    let mut levels = vec![leaves];
+
+    let mut level = 1;
+    loop {
+        let children = &levels[level - 1];
+        let nodes: Vec<Hash> = (0..count_at_level(count_leaves, level))
+            .map(|i| {
+                reuse(level, i).unwrap_or_else(|| {
+                    hash_internal_node(&children[2 * i], children.get(2 * i + 1), padding, hasher)
+                })
+            })
+            .collect();
+
+        let is_root = nodes.len() == 1;
+        levels.push(nodes);
+        if is_root {
+            break;
+        }
+        level += 1;
+    }
+
+    levels
+}
+
+/// Recomputes the ancestors of the `dirty` leaves after the leaf level changed.
+///
+/// `dirty` must be sorted and only contain indices of the current leaf level.
+/// `old_count_leaves` is the leaf count before the change, so the pairing of
+/// the last node can be fixed up when the level grew or shrank.
+fn rebuild_levels<H>(
+    levels: &mut Vec<Vec<Hash>>,
+    mut dirty: Vec<usize>,
+    old_count_leaves: usize,
+    padding: Padding,
+    hasher: &mut H,
+) where
+    H: Digest,
+{
+    let mut level = 0;
+    let mut old_len = old_count_leaves;
+
+    loop {
+        if level + 1 == levels.len() {
+            levels.push(Vec::new());
+        }
+
+        let (lower, upper) = levels.split_at_mut(level + 1);
+        let children = &lower[level];
+        let parents = &mut upper[0];
+        let old_parents_len = parents.len();
+        parents.resize(children.len().div_ceil(2), Vec::new());
+
+        let mut dirty_parents: Vec<usize> = Vec::with_capacity(dirty.len());
+        for i in dirty.iter().map(|i| i / 2) {
+            if dirty_parents.last() != Some(&i) {
+                dirty_parents.push(i);
+            }
+        }
+        if old_len != children.len() {
+            let last = parents.len() - 1;
+            if dirty_parents.last().is_none_or(|&i| i < last) {
+                dirty_parents.push(last);
+            }
+        }
+
+        for &i in &dirty_parents {
+            parents[i] =
+                hash_internal_node(&children[2 * i], children.get(2 * i + 1), padding, hasher);
+        }
+
+        if parents.len() == 1 {
+            levels.truncate(level + 2);
+            break;
+        }
+
+        old_len = old_parents_len;
+        dirty = dirty_parents;
+        level += 1;
+    }
+}
+
+pub struct MerkleTree<H> {
+    hasher: H,
+    /// Shared with snapshots, copied on the first write after one was taken.
+    levels: Arc<Vec<Vec<Hash>>>,
+    /// Sorted leaf positions whose ancestors are out of date.
+    dirty: Vec<usize>,
+    /// Leaf count the internal nodes were last computed for.
+    clean_count_leaves: usize,
+    hooks: Vec<hooks::RootHook>,
+    version: u64,
+    history: history::History,
+    undo_log: undo::UndoLog,
+    /// Metadata per leaf, empty until some leaf gets metadata.
+    metadata: Vec<Option<metadata::StoredMetadata>>,
+    retains_values: bool,
+    /// A copy of every leaf value when `retains_values` is set, `None` where
+    /// the value is not known.
+    values: Vec<Option<Vec<u8>>>,
+    /// Positions of every leaf hash when `set_indexed` is on.
+    index: Option<index::LeafIndex>,
+    padding: Padding,
+    /// Scrubs the hasher when `set_zeroize_on_drop` is on, which also wipes
+    /// everything else the tree holds on drop.
+    #[cfg(feature = "zeroize")]
+    wipe: Option<fn(&mut H)>,
+}
+
+impl<H: Digest> MerkleTree<H> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(count_leaves = values.len()))
+    )]
+    pub fn build_with_hasher<T>(values: &[T], hasher: H) -> MerkleTree<H>
+    where
+        T: AsBytes,
+    {
+        MerkleTree::try_build_with_hasher(values, hasher).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `build_with_hasher`, but fails with `Error::InvalidSize` instead
+    /// of panicking if `values` is empty.
+    pub fn try_build_with_hasher<T>(values: &[T], mut hasher: H) -> Result<MerkleTree<H>, Error>
+    where
+        T: AsBytes,
+    {
+        if values.is_empty() {
+            return Err(Error::InvalidSize(0));
+        }
+
+        let leaves: Vec<Hash> = values.iter().map(|v| hash_leaf(v, &mut hasher)).collect();
+        let levels = build_levels(leaves, Padding::Duplicate, &mut hasher);
+
+        Ok(MerkleTree::from_levels(levels, Padding::Duplicate, hasher))
+    }
+
+    /// Builds a tree over already hashed `leaves`, such as the leaves of
+    /// another tree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(count_leaves = leaves.len()))
+    )]
+    pub fn build_from_leaves(leaves: Vec<Hash>, hasher: H) -> MerkleTree<H> {
+        MerkleTree::try_build_from_leaves(leaves, hasher).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `build_from_leaves`, but fails with `Error::InvalidSize` instead
+    /// of panicking if `leaves` is empty.
+    pub fn try_build_from_leaves(leaves: Vec<Hash>, mut hasher: H) -> Result<MerkleTree<H>, Error> {
+        if leaves.is_empty() {
+            return Err(Error::InvalidSize(0));
+        }
+
+        let levels = build_levels(leaves, Padding::Duplicate, &mut hasher);
+        Ok(MerkleTree::from_levels(levels, Padding::Duplicate, hasher))
+    }
+
+    /// Wraps `levels`, fully computed with `padding`, into a tree.
+    fn from_levels(levels: Vec<Vec<Hash>>, padding: Padding, hasher: H) -> MerkleTree<H> {
+        let clean_count_leaves = levels[0].len();
+
+        MerkleTree {
+            hasher,
+            levels: Arc::new(levels),
+            dirty: Vec::new(),
+            clean_count_leaves,
+            hooks: Vec::new(),
+            version: 0,
+            history: history::History::default(),
+            undo_log: undo::UndoLog::default(),
+            metadata: Vec::new(),
+            retains_values: false,
+            values: Vec::new(),
+            index: None,
+            padding,
+            #[cfg(feature = "zeroize")]
+            wipe: None,
+        }
+    }
+
+    /// Returns true if `value` is the leaf stored at `position`.
+    ///
+    /// Only checks this tree's copy of the leaf. Parties holding just the root
+    /// verify with a `Proof` from `gen_proof` instead.
+    pub fn verify<T>(&mut self, position: usize, value: &T) -> bool
+    where
+        T: AsBytes,
+    {
+        self.try_verify(position, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `verify`, but fails instead of panicking if `position` does not
+    /// relate to any leaf.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, value))
+    )]
+    pub fn try_verify<T>(&mut self, position: usize, value: &T) -> Result<bool, Error>
+    where
+        T: AsBytes,
+    {
+        self.check_position(position)?;
+        #[cfg(feature = "metrics")]
+        metrics::record_node_reads(1);
+
+        Ok(hashes_equal(
+            &self.levels[0][position],
+            &hash_leaf(value, &mut self.hasher),
+        ))
+    }
+
+    pub fn root_hash(&self) -> &Hash {
+        self.try_root_hash().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `root_hash`, but fails instead of panicking if the tree has
+    /// deferred changes.
+    pub fn try_root_hash(&self) -> Result<&Hash, Error> {
+        self.check_clean()?;
+        Ok(&self.levels[self.levels.len() - 1][0])
+    }
+
+    pub(crate) fn check_position(&self, position: usize) -> Result<(), Error> {
+        if position < self.count_leaves() {
+            Ok(())
+        } else {
+            Err(Error::InvalidPosition(position))
+        }
+    }
+
+    pub(crate) fn check_clean(&self) -> Result<(), Error> {
+        if self.is_dirty() {
+            Err(Error::DeferredChanges)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Recomputes any deferred changes and returns the root hash.
+    pub fn root(&mut self) -> &Hash {
+        self.flush();
+        self.root_hash()
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn count_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn hashes_equal_compares_whole_hashes() {
+        assert!(hashes_equal(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!hashes_equal(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!hashes_equal(&[1, 2, 3], &[1, 2]));
+        assert!(!hashes_equal(&[], &[0]));
+    }
+
+    #[test]
+    fn verify_checks_the_leaf_at_a_position() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        assert!(tree.verify(1, &"b"));
+        assert!(!tree.verify(1, &"a"));
+        assert_eq!(tree.try_verify(2, &"b"), Err(Error::InvalidPosition(2)));
+    }
+
+    #[test]
+    fn try_build_fails_without_leaves() {
+        let values: [&str; 0] = [];
+        assert_eq!(
+            MerkleTree::try_build_with_hasher(&values, Sha256::default()).map(|_| ()),
+            Err(Error::InvalidSize(0))
+        );
+        assert_eq!(
+            MerkleTree::try_build_from_leaves(Vec::new(), Sha256::default()).map(|_| ()),
+            Err(Error::InvalidSize(0))
+        );
+
+        let tree = MerkleTree::try_build_with_hasher(&["a"], Sha256::default()).unwrap();
+        let leaves = tree.leaves().cloned().collect();
+        let rebuilt = MerkleTree::try_build_from_leaves(leaves, Sha256::default()).unwrap();
+        assert_eq!(rebuilt.root_hash(), tree.root_hash());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{height, ConsistencyProof, Error, Patch, Proof, RangeProof};
+
+/// Bounds on what untrusted proofs and patches may describe.
+///
+/// Decoding with `from_bytes_with_limits` fails with `Error::LimitExceeded`
+/// as soon as an encoded count goes past a bound, before reading what it
+/// counts. `from_bytes` applies the defaults. Proofs assembled some other
+/// way can be checked with the `check_*` methods before verifying them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Most levels below the root a proof may walk through.
+    pub max_depth: usize,
+    /// Most leaves a proof may claim its tree has, and most mutations a patch
+    /// may carry.
+    pub max_leaves: usize,
+    /// Most hashes a single proof may carry.
+    pub max_proof_len: usize,
+}
+
+impl Default for Limits {
+    /// Allows trees of up to 2^48 leaves, far more than fit in memory, or as
+    /// many as a `usize` counts on smaller targets.
+    fn default() -> Limits {
+        Limits {
+            max_depth: 48,
+            max_leaves: (1u64 << 48).min(usize::MAX as u64) as usize,
+            max_proof_len: 2 * 48 + 1,
+        }
+    }
+}
+
+impl Limits {
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), Error> {
+        check(depth <= self.max_depth)
+    }
+
+    pub(crate) fn check_leaves(&self, count_leaves: usize) -> Result<(), Error> {
+        check(count_leaves <= self.max_leaves)
+    }
+
+    pub(crate) fn check_proof_len(&self, len: usize) -> Result<(), Error> {
+        check(len <= self.max_proof_len)
+    }
+
+    pub fn check_proof(&self, proof: &Proof) -> Result<(), Error> {
+        self.check_leaves(proof.count_leaves())?;
+        self.check_depth(proof.path().len())?;
+        self.check_proof_len(proof.path().len())
+    }
+
+    pub fn check_range_proof(&self, proof: &RangeProof) -> Result<(), Error> {
+        self.check_leaves(proof.count_leaves())?;
+        self.check_depth(height(proof.count_leaves().max(1)))?;
+        self.check_proof_len(proof.hashes().len())
+    }
+
+    pub fn check_consistency_proof(&self, proof: &ConsistencyProof) -> Result<(), Error> {
+        self.check_leaves(proof.count_leaves())?;
+        self.check_depth(height(proof.count_leaves().max(1)))?;
+        self.check_proof_len(proof.hashes().len())
+    }
+
+    pub fn check_patch(&self, patch: &Patch) -> Result<(), Error> {
+        self.check_leaves(patch.len())
+    }
+}
+
+fn check(within: bool) -> Result<(), Error> {
+    if within {
+        Ok(())
+    } else {
+        Err(Error::LimitExceeded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{MerkleTree, Mutation};
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default())
+    }
+
+    #[test]
+    fn proofs_within_defaults_pass() {
+        let tree = tree();
+        let limits = Limits::default();
+        assert_eq!(limits.check_proof(&tree.gen_proof(4)), Ok(()));
+        assert_eq!(
+            limits.check_range_proof(&tree.get_range_with_proof(1..4).1),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check_consistency_proof(&tree.consistency_proof(2)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn each_bound_is_checked() {
+        let tree = tree();
+        let proof = tree.gen_proof(4);
+        let shallow = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let small = Limits {
+            max_leaves: 4,
+            ..Limits::default()
+        };
+        let short = Limits {
+            max_proof_len: 2,
+            ..Limits::default()
+        };
+
+        for limits in [shallow, small, short] {
+            assert_eq!(limits.check_proof(&proof), Err(Error::LimitExceeded));
+            assert_eq!(
+                Proof::from_bytes_with_limits(&proof.to_bytes(), &limits),
+                Err(Error::LimitExceeded)
+            );
+        }
+        assert_eq!(
+            small.check_range_proof(&tree.get_range_with_proof(0..2).1),
+            Err(Error::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn patches_are_bounded_by_their_mutations() {
+        let mut tree = tree();
+        let patch = tree.apply_with_patch(&[Mutation::Push("f"), Mutation::Push("g")]);
+        let one = Limits {
+            max_leaves: 1,
+            ..Limits::default()
+        };
+
+        assert_eq!(one.check_patch(&patch), Err(Error::LimitExceeded));
+        assert!(matches!(
+            Patch::from_bytes_with_limits(&patch.to_bytes(), &one),
+            Err(Error::LimitExceeded)
+        ));
+        assert_eq!(Limits::default().check_patch(&patch), Ok(()));
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use digest::Digest;
+
+use crate::{build_levels_reusing, padding, Error, MerkleTree};
+
+impl<H: Digest> MerkleTree<H> {
+    /// Builds a tree over the leaves of `left` followed by the leaves of `right`.
+    ///
+    /// Complete subtrees of `left` are reused as they are. Complete subtrees of
+    /// `right` are reused at every level where they stay aligned, which is up
+    /// to the number of trailing zeros of the left leaf count. Only the nodes
+    /// spanning the seam are hashed again.
+    pub fn merge(left: MerkleTree<H>, right: MerkleTree<H>) -> MerkleTree<H> {
+        MerkleTree::try_merge(left, right).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `merge`, but fails instead of panicking with
+    /// `Error::PaddingMismatch` if the trees use different paddings, or with
+    /// `Error::PaddingRejected` if their padding rejects the merged count.
+    pub fn try_merge(
+        mut left: MerkleTree<H>,
+        mut right: MerkleTree<H>,
+    ) -> Result<MerkleTree<H>, Error> {
+        if left.padding != right.padding {
+            return Err(Error::PaddingMismatch);
+        }
+        padding::check_count(left.padding, left.count_leaves() + right.count_leaves())?;
+        left.flush();
+        right.flush();
+
+        let offset = left.count_leaves();
+        let count_leaves = offset + right.count_leaves();
+
+        let left_levels = Arc::make_mut(&mut left.levels);
+        let right_levels = Arc::make_mut(&mut right.levels);
+        let mut leaves = std::mem::take(&mut left_levels[0]);
+        leaves.append(&mut right_levels[0]);
+
+        let levels = build_levels_reusing(leaves, left.padding, &mut left.hasher, |level, i| {
+            let width = 1 << level;
+            let start = i * width;
+            if start + width <= offset {
+                Some(std::mem::take(&mut left_levels[level][i]))
+            } else if offset.is_multiple_of(width)
+                && start >= offset
+                && start + width <= count_leaves
+            {
+                Some(std::mem::take(
+                    &mut right_levels[level][(start - offset) / width],
+                ))
+            } else {
+                None
+            }
+        });
+
+        let mut metadata = std::mem::take(&mut left.metadata);
+        if !metadata.is_empty() || !right.metadata.is_empty() {
+            metadata.resize(offset, None);
+            right.metadata.resize(count_leaves - offset, None);
+            metadata.append(&mut right.metadata);
+        }
+
+        let retains_values = left.retains_values || right.retains_values;
+        let mut values = std::mem::take(&mut left.values);
+        if retains_values {
+            values.resize(offset, None);
+            right.values.resize(count_leaves - offset, None);
+            values.append(&mut right.values);
+        }
+
+        let hasher = std::mem::replace(&mut left.hasher, H::new());
+        let mut tree = MerkleTree::from_levels(levels, left.padding, hasher);
+        tree.metadata = metadata;
+        tree.retains_values = retains_values;
+        tree.values = values;
+        tree.set_indexed(left.index.is_some() || right.index.is_some());
+        #[cfg(feature = "zeroize")]
+        tree.set_zeroize_on_drop(left.wipe.is_some() || right.wipe.is_some());
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Padding;
+
+    fn values(range: std::ops::Range<u8>) -> Vec<Vec<u8>> {
+        range.map(|i| vec![i]).collect()
+    }
+
+    #[test]
+    fn merge_matches_build_over_both_leaves() {
+        for left in 1..=9 {
+            for right in 1..=9 {
+                let all = values(0..left + right);
+                let merged = MerkleTree::merge(
+                    MerkleTree::build_with_hasher(&all[..left as usize], Sha256::default()),
+                    MerkleTree::build_with_hasher(&all[left as usize..], Sha256::default()),
+                );
+                let expected = MerkleTree::build_with_hasher(&all, Sha256::default());
+                assert_eq!(
+                    merged.root_hash(),
+                    expected.root_hash(),
+                    "{} + {} leaves",
+                    left,
+                    right
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merge_keeps_values_of_either_side() {
+        let left = MerkleTree::build_with_hasher(&values(0..3), Sha256::default());
+        let right = MerkleTree::build_retaining_values(&values(3..5), Sha256::default());
+        let merged = MerkleTree::merge(left, right);
+
+        assert!(merged.retains_values());
+        assert_eq!(merged.value(0), None);
+        assert_eq!(merged.value(4), Some(&[4u8][..]));
+    }
+
+    #[test]
+    #[should_panic(expected = "trees use different paddings")]
+    fn merge_rejects_other_padding() {
+        let left = MerkleTree::build_with_hasher(&values(0..3), Sha256::default());
+        let mut right = MerkleTree::build_with_hasher(&values(3..5), Sha256::default());
+        right.set_padding(Padding::Empty);
+        MerkleTree::merge(left, right);
+    }
+
+    #[test]
+    fn try_merge_fails_on_paddings_that_do_not_fit() {
+        let left = MerkleTree::build_with_hasher(&values(0..3), Sha256::default());
+        let mut right = MerkleTree::build_with_hasher(&values(3..5), Sha256::default());
+        right.set_padding(Padding::Empty);
+        assert_eq!(
+            MerkleTree::try_merge(left, right).map(|_| ()),
+            Err(Error::PaddingMismatch)
+        );
+
+        let mut left = MerkleTree::build_with_hasher(&values(0..4), Sha256::default());
+        let mut right = MerkleTree::build_with_hasher(&values(4..6), Sha256::default());
+        left.set_padding(Padding::Reject);
+        right.set_padding(Padding::Reject);
+        assert_eq!(
+            MerkleTree::try_merge(left, right).map(|_| ()),
+            Err(Error::PaddingRejected(6))
+        );
+    }
+}
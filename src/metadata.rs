@@ -0,0 +1,234 @@
+use digest::Digest;
+
+use crate::mutation::LeafMutation;
+use crate::{hash_leaf, AsBytes, Hash, MerkleTree, Padding, Proof};
+
+const METADATA_SIG: u8 = 2u8;
+
+/// Small bookkeeping data attached to a leaf.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub timestamp: u64,
+    pub sequence: u64,
+    pub source: Vec<u8>,
+}
+
+impl Metadata {
+    /// Encodes the metadata the way it is committed into leaf hashes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.source.len());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&self.source);
+        bytes
+    }
+}
+
+/// Metadata of a leaf and whether it is part of the leaf hash.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LeafMetadata {
+    pub(crate) metadata: Metadata,
+    pub(crate) committed: bool,
+}
+
+/// Metadata as stored next to a leaf, with the hash of the leaf value alone so
+/// the committed hash can be redone when either of them changes.
+#[derive(Clone, Debug)]
+pub(crate) struct StoredMetadata {
+    pub(crate) leaf: LeafMetadata,
+    pub(crate) plain: Hash,
+}
+
+/// Hashes `metadata` into the hash of a leaf value.
+pub(crate) fn commit_metadata<H>(plain: &Hash, metadata: &Metadata, hasher: &mut H) -> Hash
+where
+    H: Digest,
+{
+    hasher.update([METADATA_SIG]);
+    hasher.update(plain);
+    hasher.update(metadata.to_bytes());
+    hasher.finalize_reset().to_vec()
+}
+
+/// Returns the leaf hash for a value hashed into `plain` carrying `metadata`.
+pub(crate) fn leaf_with_metadata<H>(
+    plain: Hash,
+    metadata: Option<&LeafMetadata>,
+    hasher: &mut H,
+) -> Hash
+where
+    H: Digest,
+{
+    match metadata {
+        Some(m) if m.committed => commit_metadata(&plain, &m.metadata, hasher),
+        _ => plain,
+    }
+}
+
+impl Proof {
+    /// Returns true if `value`, with `metadata` committed into its leaf hash, is
+    /// the leaf at `position` of the tree with `root_hash`.
+    pub fn validate_with_metadata<T, H>(
+        &self,
+        root_hash: &[u8],
+        value: &T,
+        metadata: &Metadata,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let plain = hash_leaf(value, &mut hasher);
+        let leaf = commit_metadata(&plain, metadata, &mut hasher);
+        self.validate_leaf(root_hash, leaf, Padding::Duplicate, &mut hasher)
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the metadata attached to the leaf at `position`.
+    pub fn metadata(&self, position: usize) -> Option<&Metadata> {
+        self.metadata
+            .get(position)
+            .and_then(|m| m.as_ref())
+            .map(|m| &m.leaf.metadata)
+    }
+
+    /// Returns true if the metadata of the leaf at `position` is part of its
+    /// leaf hash.
+    pub fn is_metadata_committed(&self, position: usize) -> bool {
+        self.metadata
+            .get(position)
+            .and_then(|m| m.as_ref())
+            .is_some_and(|m| m.leaf.committed)
+    }
+
+    /// Attaches `metadata` to the leaf at `position`, replacing any previous
+    /// metadata.
+    ///
+    /// When `commit` is true the metadata is hashed into the leaf, which
+    /// changes the root. Otherwise it is only stored next to the leaf. The
+    /// metadata stays attached when the leaf value is set again.
+    pub fn set_metadata(&mut self, position: usize, metadata: Metadata, commit: bool) {
+        let metadata = LeafMetadata {
+            metadata,
+            committed: commit,
+        };
+        self.apply_leaves(vec![LeafMutation::SetMetadata(position, Some(metadata))]);
+    }
+
+    /// Detaches the metadata of the leaf at `position`.
+    pub fn clear_metadata(&mut self, position: usize) {
+        self.apply_leaves(vec![LeafMutation::SetMetadata(position, None)]);
+    }
+
+    /// Appends `value` with `metadata` committed into its leaf hash.
+    pub fn push_with_metadata<T>(&mut self, value: &T, metadata: Metadata)
+    where
+        T: AsBytes,
+    {
+        let plain = hash_leaf(value, &mut self.hasher);
+        let position = self.count_leaves();
+        let metadata = LeafMetadata {
+            metadata,
+            committed: true,
+        };
+        let mut mutations = vec![LeafMutation::Push(plain)];
+        self.push_value(&mut mutations, position, value);
+        mutations.push(LeafMutation::SetMetadata(position, Some(metadata)));
+        self.apply_leaves(mutations);
+    }
+
+    /// Returns the inclusion proof of the leaf at `position` together with its
+    /// metadata.
+    pub fn gen_proof_with_metadata(&self, position: usize) -> (Proof, Option<Metadata>) {
+        (self.gen_proof(position), self.metadata(position).cloned())
+    }
+
+    /// Returns the hash of the leaf value at `position`, without committed
+    /// metadata.
+    pub(crate) fn plain_leaf(&self, position: usize) -> &Hash {
+        match self.metadata.get(position) {
+            Some(Some(m)) => &m.plain,
+            _ => &self.levels[0][position],
+        }
+    }
+
+    pub(crate) fn leaf_metadata(&self, position: usize) -> Option<&LeafMetadata> {
+        self.metadata
+            .get(position)
+            .and_then(|m| m.as_ref())
+            .map(|m| &m.leaf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    fn metadata(sequence: u64) -> Metadata {
+        Metadata {
+            timestamp: 1_700_000_000,
+            sequence,
+            source: b"ingest".to_vec(),
+        }
+    }
+
+    #[test]
+    fn committed_metadata_validates_with_proof() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.push_with_metadata(&"c", metadata(7));
+        assert!(tree.is_metadata_committed(2));
+
+        let (proof, attached) = tree.gen_proof_with_metadata(2);
+        assert_eq!(attached, Some(metadata(7)));
+        assert!(proof.validate_with_metadata(
+            tree.root_hash(),
+            &"c",
+            &metadata(7),
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_metadata(
+            tree.root_hash(),
+            &"c",
+            &metadata(8),
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_hasher(tree.root_hash(), &"c", Sha256::default()));
+    }
+
+    #[test]
+    fn uncommitted_metadata_keeps_the_root() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = tree.root_hash().clone();
+
+        tree.set_metadata(1, metadata(1), false);
+        assert_eq!(tree.root_hash(), &root);
+        assert_eq!(tree.metadata(1), Some(&metadata(1)));
+        assert!(!tree.is_metadata_committed(1));
+
+        tree.set_metadata(1, metadata(1), true);
+        assert_ne!(tree.root_hash(), &root);
+        tree.clear_metadata(1);
+        assert_eq!(tree.root_hash(), &root);
+        assert_eq!(tree.metadata(1), None);
+    }
+
+    #[test]
+    fn committed_metadata_stays_when_the_value_is_set() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.set_metadata(0, metadata(2), true);
+        tree.apply(&[Mutation::Set(0, "c")]);
+
+        let proof = tree.gen_proof(0);
+        assert!(proof.validate_with_metadata(
+            tree.root_hash(),
+            &"c",
+            &metadata(2),
+            Sha256::default()
+        ));
+    }
+}
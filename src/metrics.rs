@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static HASH_INVOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_HASHED: AtomicU64 = AtomicU64::new(0);
+static NODE_READS: AtomicU64 = AtomicU64::new(0);
+
+/// Work done by every tree in the process since start or the last
+/// `reset_counters`, as reported by `counters`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Number of leaf, internal and padding hashes computed.
+    pub hash_invocations: u64,
+    /// Total length of the input of those hashes, signature bytes included.
+    pub bytes_hashed: u64,
+    /// Number of stored nodes read to build proofs, verify leaves or load
+    /// trees from a store.
+    pub node_reads: u64,
+}
+
+pub fn counters() -> Counters {
+    Counters {
+        hash_invocations: HASH_INVOCATIONS.load(Ordering::Relaxed),
+        bytes_hashed: BYTES_HASHED.load(Ordering::Relaxed),
+        node_reads: NODE_READS.load(Ordering::Relaxed),
+    }
+}
+
+/// Sets every counter back to 0, for instance between two scrapes.
+pub fn reset_counters() {
+    HASH_INVOCATIONS.store(0, Ordering::Relaxed);
+    BYTES_HASHED.store(0, Ordering::Relaxed);
+    NODE_READS.store(0, Ordering::Relaxed);
+}
+
+pub(crate) fn record_hash(bytes: usize) {
+    HASH_INVOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_HASHED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_node_reads(count: usize) {
+    NODE_READS.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+// The counters are shared by every test running in the process, so these
+// only check that they grow by at least the work done here.
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn building_counts_every_hash() {
+        let before = counters();
+        MerkleTree::build_with_hasher(&["ab", "cd"], Sha256::default());
+        let after = counters();
+
+        assert!(after.hash_invocations - before.hash_invocations >= 3);
+        assert!(after.bytes_hashed - before.bytes_hashed >= 2 * 3 + 1 + 2 * 32);
+    }
+
+    #[test]
+    fn proofs_count_node_reads() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d"], Sha256::default());
+        let before = counters();
+        tree.gen_proof(1);
+        assert!(counters().node_reads - before.node_reads >= 2);
+    }
+}
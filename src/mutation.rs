@@ -0,0 +1,558 @@
+use std::sync::Arc;
+
+use digest::Digest;
+
+use crate::index::LeafIndex;
+use crate::metadata::{leaf_with_metadata, LeafMetadata, StoredMetadata};
+use crate::{
+    hash_leaf, hashes_equal, padding, rebuild_levels, AsBytes, Error, Hash, MerkleTree, RootChange,
+};
+
+/// A single change to the leaves of a `MerkleTree`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation<T> {
+    /// Replaces the leaf at the given position.
+    Set(usize, T),
+    /// Appends a leaf after the last one.
+    Push(T),
+    /// Removes the leaf at the given position, shifting later leaves down.
+    Remove(usize),
+}
+
+/// A mutation whose value has already been hashed into a leaf.
+#[derive(Clone, Debug)]
+pub(crate) enum LeafMutation {
+    Set(usize, Hash),
+    Push(Hash),
+    Remove(usize),
+    /// Inserts a leaf before the given position, only used to undo `Remove`.
+    Insert(usize, Hash, Option<LeafMetadata>),
+    /// Attaches metadata to the leaf at the given position, or detaches it.
+    SetMetadata(usize, Option<LeafMetadata>),
+    /// Records the value of the leaf at the given position when values are
+    /// retained. Follows the `Set` or `Push` it belongs to.
+    SetValue(usize, Option<Vec<u8>>),
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Applies `mutations` in order and recomputes the root once.
+    ///
+    /// Ancestors shared by several changed leaves are only hashed once. All
+    /// positions are checked before anything is changed, so a panic leaves
+    /// the tree untouched.
+    pub fn apply<T>(&mut self, mutations: &[Mutation<T>])
+    where
+        T: AsBytes,
+    {
+        let mutations = self.hash_mutations(mutations);
+        self.apply_leaves(mutations);
+    }
+
+    /// Like `apply`, but fails without changing the tree instead of panicking
+    /// if a mutation refers to a position that will not exist when it is
+    /// applied.
+    pub fn try_apply<T>(&mut self, mutations: &[Mutation<T>]) -> Result<(), Error>
+    where
+        T: AsBytes,
+    {
+        let mutations = self.hash_mutations(mutations);
+        self.check_positions(&mutations)?;
+        self.apply_leaves(mutations);
+        Ok(())
+    }
+
+    /// Applies `mutations` only if the current root is `expected_root`.
+    ///
+    /// Lets writers sharing a tree detect that someone else changed it since
+    /// they last read the root.
+    pub fn apply_if_root<T>(
+        &mut self,
+        expected_root: &[u8],
+        mutations: &[Mutation<T>],
+    ) -> Result<(), Error>
+    where
+        T: AsBytes,
+    {
+        let actual = self.root();
+        if !hashes_equal(actual, expected_root) {
+            return Err(Error::RootMismatch {
+                expected: expected_root.to_vec(),
+                actual: actual.clone(),
+            });
+        }
+
+        self.try_apply(mutations)
+    }
+
+    /// Appends `value` as a new leaf.
+    pub fn push<T>(&mut self, value: &T)
+    where
+        T: AsBytes,
+    {
+        self.extend(std::slice::from_ref(value));
+    }
+
+    /// Replaces the leaf at `position` with `value`, rehashing only its
+    /// ancestors.
+    pub fn update<T>(&mut self, position: usize, value: &T)
+    where
+        T: AsBytes,
+    {
+        self.try_update(position, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `update`, but fails without changing the tree instead of
+    /// panicking if `position` does not relate to any leaf.
+    pub fn try_update<T>(&mut self, position: usize, value: &T) -> Result<(), Error>
+    where
+        T: AsBytes,
+    {
+        let mut mutations = vec![LeafMutation::Set(
+            position,
+            hash_leaf(value, &mut self.hasher),
+        )];
+        self.push_value(&mut mutations, position, value);
+        self.check_positions(&mutations)?;
+        self.apply_leaves(mutations);
+        Ok(())
+    }
+
+    /// Appends `values` as new leaves.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(count = values.len()))
+    )]
+    pub fn extend<T>(&mut self, values: &[T])
+    where
+        T: AsBytes,
+    {
+        let count_leaves = self.count_leaves();
+        let mut mutations = Vec::with_capacity(values.len());
+        for (i, v) in values.iter().enumerate() {
+            mutations.push(LeafMutation::Push(hash_leaf(v, &mut self.hasher)));
+            self.push_value(&mut mutations, count_leaves + i, v);
+        }
+        self.apply_leaves(mutations);
+    }
+
+    /// Applies `mutations` to the leaves but defers recomputing their ancestors.
+    ///
+    /// The affected paths are remembered and hashed together on the next call
+    /// to `root`, so bursts of writes only pay for shared ancestors once.
+    pub fn defer<T>(&mut self, mutations: &[Mutation<T>])
+    where
+        T: AsBytes,
+    {
+        let mutations = self.hash_mutations(mutations);
+        self.stage_leaves(mutations);
+    }
+
+    /// Returns true if some leaves changed since the internal nodes were last
+    /// recomputed.
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty() || self.clean_count_leaves != self.count_leaves()
+    }
+
+    /// Recomputes the internal nodes above deferred changes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn flush(&mut self) {
+        if !self.is_dirty() {
+            return;
+        }
+
+        let dirty = std::mem::take(&mut self.dirty);
+        let change = if self.hooks.is_empty() {
+            None
+        } else {
+            Some(RootChange {
+                old_root: self.levels[self.levels.len() - 1][0].clone(),
+                new_root: Vec::new(),
+                old_count_leaves: self.clean_count_leaves,
+                count_leaves: self.count_leaves(),
+                changed: dirty.clone(),
+            })
+        };
+
+        rebuild_levels(
+            Arc::make_mut(&mut self.levels),
+            dirty,
+            self.clean_count_leaves,
+            self.padding,
+            &mut self.hasher,
+        );
+        self.clean_count_leaves = self.count_leaves();
+        self.version += 1;
+
+        if let Some(mut change) = change {
+            change.new_root = self.root_hash().clone();
+            self.notify_root_change(change);
+        }
+    }
+
+    pub(crate) fn hash_mutations<T>(&mut self, mutations: &[Mutation<T>]) -> Vec<LeafMutation>
+    where
+        T: AsBytes,
+    {
+        let mut count_leaves = self.count_leaves();
+        let mut leaf_mutations = Vec::with_capacity(mutations.len());
+        for m in mutations {
+            match m {
+                Mutation::Set(i, v) => {
+                    leaf_mutations.push(LeafMutation::Set(*i, hash_leaf(v, &mut self.hasher)));
+                    self.push_value(&mut leaf_mutations, *i, v);
+                }
+                Mutation::Push(v) => {
+                    leaf_mutations.push(LeafMutation::Push(hash_leaf(v, &mut self.hasher)));
+                    self.push_value(&mut leaf_mutations, count_leaves, v);
+                    count_leaves += 1;
+                }
+                Mutation::Remove(i) => {
+                    leaf_mutations.push(LeafMutation::Remove(*i));
+                    count_leaves = count_leaves.saturating_sub(1);
+                }
+            }
+        }
+
+        leaf_mutations
+    }
+
+    /// Adds a copy of `value` for `position` to `mutations` if this tree
+    /// retains leaf values.
+    pub(crate) fn push_value<T>(
+        &self,
+        mutations: &mut Vec<LeafMutation>,
+        position: usize,
+        value: &T,
+    ) where
+        T: AsBytes,
+    {
+        if self.retains_values {
+            mutations.push(LeafMutation::SetValue(
+                position,
+                Some(value.as_bytes().to_vec()),
+            ));
+        }
+    }
+
+    pub(crate) fn apply_leaves(&mut self, mutations: Vec<LeafMutation>) {
+        self.stage_leaves(mutations);
+        self.flush();
+    }
+
+    /// Changes the leaf level and records which leaves need their ancestors
+    /// recomputed.
+    fn stage_leaves(&mut self, mutations: Vec<LeafMutation>) {
+        self.check_mutations(&mutations);
+        if mutations.is_empty() {
+            return;
+        }
+
+        self.record_undo(&mutations);
+        self.change_leaves(mutations);
+    }
+
+    /// Panics if a mutation refers to a position that will not exist when it
+    /// is applied.
+    fn check_mutations(&self, mutations: &[LeafMutation]) {
+        if let Err(e) = self.check_positions(mutations) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Checks that every mutation refers to a position that exists when it is
+    /// applied, and that the tree can be padded once they all are.
+    pub(crate) fn check_positions(&self, mutations: &[LeafMutation]) -> Result<(), Error> {
+        let mut count_leaves = self.count_leaves();
+        for m in mutations {
+            match *m {
+                LeafMutation::Set(i, _)
+                | LeafMutation::SetMetadata(i, _)
+                | LeafMutation::SetValue(i, _)
+                    if i >= count_leaves =>
+                {
+                    return Err(Error::InvalidPosition(i));
+                }
+                LeafMutation::Push(_) => count_leaves += 1,
+                LeafMutation::Remove(i) => {
+                    if i >= count_leaves {
+                        return Err(Error::InvalidPosition(i));
+                    }
+                    if count_leaves == 1 {
+                        return Err(Error::RemoveLastLeaf);
+                    }
+                    count_leaves -= 1;
+                }
+                LeafMutation::Insert(i, _, _) => {
+                    if i > count_leaves {
+                        return Err(Error::InvalidPosition(i));
+                    }
+                    count_leaves += 1;
+                }
+                _ => {}
+            }
+        }
+
+        padding::check_count(self.padding, count_leaves)
+    }
+
+    /// Applies already checked `mutations` to the leaf level.
+    pub(crate) fn change_leaves(&mut self, mutations: Vec<LeafMutation>) {
+        self.record_version();
+
+        let activates_metadata = mutations.iter().any(|m| match m {
+            LeafMutation::SetMetadata(_, m) | LeafMutation::Insert(_, _, m) => m.is_some(),
+            _ => false,
+        });
+        if activates_metadata && self.metadata.is_empty() {
+            self.metadata.resize(self.count_leaves(), None);
+        }
+
+        let leaves = &mut Arc::make_mut(&mut self.levels)[0];
+        let metadata = &mut self.metadata;
+        let tracks_metadata = !metadata.is_empty();
+        let values = &mut self.values;
+        let tracks_values = self.retains_values;
+        #[cfg(feature = "zeroize")]
+        let wipes = self.wipe.is_some();
+        // Zeroes a retained value that is about to be replaced or removed.
+        let discard = |_value: &mut Option<Vec<u8>>| {
+            #[cfg(feature = "zeroize")]
+            if wipes {
+                crate::wipe::wipe_value(_value);
+            }
+        };
+        let hasher = &mut self.hasher;
+        let dirty = &mut self.dirty;
+        let index = &mut self.index;
+        // Every position from here on shifted because of a removal or insertion.
+        let mut shifted_from = usize::MAX;
+
+        for m in mutations {
+            match m {
+                LeafMutation::Set(i, leaf) => {
+                    let leaf = match metadata.get_mut(i).and_then(|m| m.as_mut()) {
+                        Some(stored) => {
+                            stored.plain = leaf.clone();
+                            leaf_with_metadata(leaf, Some(&stored.leaf), hasher)
+                        }
+                        None => leaf,
+                    };
+                    if let Some(index) = index {
+                        index.remove(&leaves[i], i);
+                        index.insert(leaf.clone(), i);
+                    }
+                    leaves[i] = leaf;
+                    if tracks_values {
+                        discard(&mut values[i]);
+                        values[i] = None;
+                    }
+                    dirty.push(i);
+                }
+                LeafMutation::Push(leaf) => {
+                    if let Some(index) = index {
+                        index.insert(leaf.clone(), leaves.len());
+                    }
+                    dirty.push(leaves.len());
+                    leaves.push(leaf);
+                    if tracks_metadata {
+                        metadata.push(None);
+                    }
+                    if tracks_values {
+                        values.push(None);
+                    }
+                }
+                LeafMutation::Remove(i) => {
+                    leaves.remove(i);
+                    if tracks_metadata {
+                        metadata.remove(i);
+                    }
+                    if tracks_values {
+                        discard(&mut values[i]);
+                        values.remove(i);
+                    }
+                    shifted_from = shifted_from.min(i);
+                }
+                LeafMutation::Insert(i, leaf, leaf_metadata) => {
+                    if tracks_metadata {
+                        leaves.insert(
+                            i,
+                            leaf_with_metadata(leaf.clone(), leaf_metadata.as_ref(), hasher),
+                        );
+                        metadata.insert(
+                            i,
+                            leaf_metadata.map(|m| StoredMetadata {
+                                leaf: m,
+                                plain: leaf,
+                            }),
+                        );
+                    } else {
+                        leaves.insert(i, leaf);
+                    }
+                    if tracks_values {
+                        values.insert(i, None);
+                    }
+                    shifted_from = shifted_from.min(i);
+                }
+                LeafMutation::SetMetadata(i, leaf_metadata) => {
+                    let plain = match &metadata[i] {
+                        Some(stored) => stored.plain.clone(),
+                        None => leaves[i].clone(),
+                    };
+                    let leaf = leaf_with_metadata(plain.clone(), leaf_metadata.as_ref(), hasher);
+                    if let Some(index) = index {
+                        index.remove(&leaves[i], i);
+                        index.insert(leaf.clone(), i);
+                    }
+                    leaves[i] = leaf;
+                    metadata[i] = leaf_metadata.map(|m| StoredMetadata { leaf: m, plain });
+                    dirty.push(i);
+                }
+                LeafMutation::SetValue(i, value) => {
+                    if tracks_values {
+                        discard(&mut values[i]);
+                        values[i] = value;
+                    }
+                }
+            }
+        }
+
+        let count_leaves = leaves.len();
+        if shifted_from != usize::MAX {
+            if let Some(index) = index {
+                // Positions after a removal or insertion all moved.
+                *index = LeafIndex::build(leaves);
+            }
+        }
+        dirty.retain(|&i| i < shifted_from.min(count_leaves));
+        dirty.extend(shifted_from..count_leaves);
+        dirty.sort_unstable();
+        dirty.dedup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn apply_if_root_fails_on_bad_position_without_changing_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = tree.root().clone();
+
+        let result = tree.apply_if_root(&root, &[Mutation::Set(0, "c"), Mutation::Remove(5)]);
+        assert_eq!(result, Err(Error::InvalidPosition(5)));
+        assert_eq!(tree.root(), &root);
+    }
+
+    fn values(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i as u8]).collect()
+    }
+
+    #[test]
+    fn push_and_update_match_rebuild_across_powers_of_two() {
+        let mut tree = MerkleTree::build_with_hasher(&values(1), Sha256::default());
+        for count in 2..=17 {
+            tree.push(&vec![count as u8 - 1]);
+            let expected = MerkleTree::build_with_hasher(&values(count), Sha256::default());
+            assert_eq!(tree.root_hash(), expected.root_hash(), "{} leaves", count);
+
+            let mut updated = values(count);
+            updated[count - 1] = vec![0xff];
+            tree.update(count - 1, &vec![0xff]);
+            let expected = MerkleTree::build_with_hasher(&updated, Sha256::default());
+            assert_eq!(tree.root_hash(), expected.root_hash(), "{} leaves", count);
+            tree.update(count - 1, &vec![count as u8 - 1]);
+        }
+    }
+
+    #[test]
+    fn update_single_leaf_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        tree.update(0, &"b");
+        let expected = MerkleTree::build_with_hasher(&["b"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+
+    #[test]
+    fn try_update_fails_on_bad_position_without_changing_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        let root = tree.root_hash().clone();
+        assert_eq!(tree.try_update(1, &"b"), Err(Error::InvalidPosition(1)));
+        assert_eq!(tree.root_hash(), &root);
+    }
+
+    #[test]
+    fn batch_matches_rebuild() {
+        let mut tree = MerkleTree::build_with_hasher(&values(5), Sha256::default());
+        tree.apply(&[
+            Mutation::Set(1, vec![0xa1]),
+            Mutation::Push(vec![0xa2]),
+            Mutation::Remove(0),
+            Mutation::Push(vec![0xa3]),
+            Mutation::Set(5, vec![0xa4]),
+        ]);
+
+        let expected = vec![
+            vec![0xa1],
+            vec![2],
+            vec![3],
+            vec![4],
+            vec![0xa2],
+            vec![0xa4],
+        ];
+        let rebuilt = MerkleTree::build_with_hasher(&expected, Sha256::default());
+        assert_eq!(tree.count_leaves(), 6);
+        assert_eq!(tree.root_hash(), rebuilt.root_hash());
+    }
+
+    #[test]
+    fn try_apply_fails_on_removing_last_leaf_without_changing_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        let root = tree.root_hash().clone();
+        let result = tree.try_apply(&[Mutation::Set(0, "b"), Mutation::Remove(0)]);
+        assert_eq!(result, Err(Error::RemoveLastLeaf));
+        assert_eq!(tree.root_hash(), &root);
+    }
+
+    #[test]
+    fn deferred_mutations_match_apply() {
+        let mutations = [
+            Mutation::Set(0, vec![0xa0]),
+            Mutation::Push(vec![0xa1]),
+            Mutation::Set(3, vec![0xa2]),
+            Mutation::Remove(2),
+        ];
+        let mut applied = MerkleTree::build_with_hasher(&values(7), Sha256::default());
+        applied.apply(&mutations);
+
+        let mut deferred = MerkleTree::build_with_hasher(&values(7), Sha256::default());
+        deferred.defer(&mutations[..2]);
+        deferred.defer(&mutations[2..]);
+        assert!(deferred.is_dirty());
+        assert_eq!(deferred.try_root_hash(), Err(Error::DeferredChanges));
+
+        assert_eq!(deferred.root(), applied.root_hash());
+        assert!(!deferred.is_dirty());
+    }
+
+    #[test]
+    fn apply_if_root_fails_on_stale_root_without_changing_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let stale = tree.root_hash().clone();
+        tree.apply_if_root(&stale, &[Mutation::Push("c")]).unwrap();
+        let root = tree.root_hash().clone();
+
+        let result = tree.apply_if_root(&stale, &[Mutation::Push("d")]);
+        assert_eq!(
+            result,
+            Err(Error::RootMismatch {
+                expected: stale,
+                actual: root.clone()
+            })
+        );
+        assert_eq!(tree.root_hash(), &root);
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::sync::Arc;
+
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+use crate::{build_levels, Error, Hash, MerkleTree};
+
+const PADDING_SIG: u8 = 3u8;
+
+/// Longest hash a `Sentinel` holds, the output of SHA-512.
+const MAX_SENTINEL_LEN: usize = 64;
+
+/// What the last node of a level with an odd count is hashed with.
+///
+/// Pairing the node with itself, the default, gives the leaves `[a, b, c]`
+/// and `[a, b, c, c]` the same root, so a proof cannot tell which one it
+/// came from (the ambiguity behind CVE-2012-2459). Pairing it with the empty
+/// hash instead, the hash of a marker byte no leaf or internal node starts
+/// with, gives every leaf sequence a root of its own.
+///
+/// Interoperating with other implementations may call for pairing the node
+/// with a constant they define, such as the all-zero hash, or for refusing to
+/// pad at all. Under `Padding::Reject` every level must have an even count
+/// below the root, so trees need a power of 2 leaves, and at least 2 since
+/// a single leaf is paired on its way to the root: fallible operations that
+/// would break this fail with `Error::PaddingRejected`, others panic.
+///
+/// `Padding::Promote` pairs the node with nothing and carries it up to the
+/// next level as it is, which gives the trees of RFC 6962 (Certificate
+/// Transparency): a single leaf is its own root.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Padding {
+    #[default]
+    Duplicate,
+    Empty,
+    Constant(Sentinel),
+    Reject,
+    Promote,
+}
+
+/// A hash of up to 64 bytes that odd nodes are paired with under
+/// `Padding::Constant`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Sentinel {
+    bytes: [u8; MAX_SENTINEL_LEN],
+    len: u8,
+}
+
+impl Sentinel {
+    pub fn new(hash: &[u8]) -> Sentinel {
+        Sentinel::try_new(hash).expect("sentinel must be at most 64 bytes")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidEncoding` instead of
+    /// panicking if `hash` is longer than 64 bytes.
+    pub fn try_new(hash: &[u8]) -> Result<Sentinel, Error> {
+        if hash.len() > MAX_SENTINEL_LEN {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let mut bytes = [0; MAX_SENTINEL_LEN];
+        bytes[..hash.len()].copy_from_slice(hash);
+        Ok(Sentinel {
+            bytes,
+            len: hash.len() as u8,
+        })
+    }
+
+    /// Returns the hash of `len` zero bytes, `len` usually being the output
+    /// size of the hasher.
+    pub fn zero(len: usize) -> Sentinel {
+        Sentinel::try_zero(len).expect("sentinel must be at most 64 bytes")
+    }
+
+    /// Like `zero`, but fails with `Error::InvalidEncoding` instead of
+    /// panicking if `len` is more than 64.
+    pub fn try_zero(len: usize) -> Result<Sentinel, Error> {
+        Sentinel::try_new(&vec![0; len])
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Sentinel").field(&self.as_bytes()).finish()
+    }
+}
+
+/// Returns the hash an odd node is paired with under `Padding::Empty`.
+fn empty_hash<H>(hasher: &mut H) -> Vec<u8>
+where
+    H: Digest,
+{
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_hash(1);
+
+    hasher.update([PADDING_SIG]);
+    hasher.finalize_reset().to_vec()
+}
+
+/// Returns what an odd node is paired with under `padding`, or `None` if it
+/// is paired with itself.
+pub(crate) fn pad_hash<H>(padding: Padding, hasher: &mut H) -> Option<Hash>
+where
+    H: Digest,
+{
+    match padding {
+        Padding::Duplicate => None,
+        Padding::Empty => Some(empty_hash(hasher)),
+        Padding::Constant(sentinel) => Some(sentinel.as_bytes().to_vec()),
+        Padding::Reject => panic!("level has an odd count, which Padding::Reject forbids"),
+        Padding::Promote => unreachable!("odd nodes are carried up unpaired"),
+    }
+}
+
+/// Fails if a tree of `count_leaves` leaves cannot be hashed with `padding`.
+pub(crate) fn check_count(padding: Padding, count_leaves: usize) -> Result<(), Error> {
+    if padding == Padding::Reject && (count_leaves < 2 || !count_leaves.is_power_of_two()) {
+        Err(Error::PaddingRejected(count_leaves))
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes `padding` the way `read` reads it back.
+pub(crate) fn write(w: &mut Writer, padding: Padding) {
+    match padding {
+        Padding::Duplicate => w.u8(0),
+        Padding::Empty => w.u8(1),
+        Padding::Constant(sentinel) => {
+            w.u8(2);
+            w.bytes(sentinel.as_bytes());
+        }
+        Padding::Reject => w.u8(3),
+        Padding::Promote => w.u8(4),
+    }
+}
+
+pub(crate) fn read(r: &mut Reader) -> Result<Padding, Error> {
+    match r.u8()? {
+        0 => Ok(Padding::Duplicate),
+        1 => Ok(Padding::Empty),
+        2 => Ok(Padding::Constant(Sentinel::try_new(r.bytes()?)?)),
+        3 => Ok(Padding::Reject),
+        4 => Ok(Padding::Promote),
+        _ => Err(Error::InvalidEncoding),
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Hashes the tree again with `padding`, which changes its root unless
+    /// every level has an even count below the root.
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.try_set_padding(padding)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `set_padding`, but fails without changing the tree if `padding`
+    /// is `Padding::Reject` and the tree does not have a power of 2 leaves,
+    /// at least 2.
+    pub fn try_set_padding(&mut self, padding: Padding) -> Result<(), Error> {
+        self.flush();
+        check_count(padding, self.count_leaves())?;
+        if padding == self.padding {
+            return Ok(());
+        }
+
+        self.padding = padding;
+        let leaves = self.levels[0].clone();
+        let old_root = self.root_hash().clone();
+        self.levels = Arc::new(build_levels(leaves, padding, &mut self.hasher));
+        if *self.root_hash() != old_root {
+            self.version += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns true if some node has two identical children, the shape that
+    /// lets a shorter or longer leaf sequence share the root under
+    /// `Padding::Duplicate`.
+    ///
+    /// Like Bitcoin, this flags every identical pair, including ones that do
+    /// not produce a collision, such as two equal leaves next to each other.
+    pub fn is_ambiguous(&self) -> bool {
+        self.try_is_ambiguous().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `is_ambiguous`, but fails instead of panicking if the tree has
+    /// deferred changes.
+    pub fn try_is_ambiguous(&self) -> Result<bool, Error> {
+        self.check_clean()?;
+
+        Ok(self.levels[..self.levels.len() - 1]
+            .iter()
+            .any(|nodes| nodes.chunks_exact(2).any(|pair| pair[0] == pair[1])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{Appender, Mutation, Proof, Sibling};
+
+    #[test]
+    fn sentinel_longer_than_any_hash_is_an_error() {
+        assert_eq!(
+            Sentinel::try_new(&[0; MAX_SENTINEL_LEN + 1]),
+            Err(Error::InvalidEncoding)
+        );
+        let sentinel = Sentinel::try_new(&[1; MAX_SENTINEL_LEN]).unwrap();
+        assert_eq!(sentinel.as_bytes(), &[1; MAX_SENTINEL_LEN][..]);
+
+        assert_eq!(
+            Sentinel::try_zero(MAX_SENTINEL_LEN + 1),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(Sentinel::try_zero(32), Ok(Sentinel::new(&[0; 32])));
+    }
+
+    #[test]
+    fn reject_needs_two_leaves_at_least() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        assert_eq!(
+            tree.try_set_padding(Padding::Reject),
+            Err(Error::PaddingRejected(1))
+        );
+        assert_eq!(tree.padding(), Padding::Duplicate);
+    }
+
+    #[test]
+    fn try_is_ambiguous_fails_on_deferred_changes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "a"], Sha256::default());
+        assert_eq!(tree.try_is_ambiguous(), Ok(true));
+
+        tree.defer(&[Mutation::Set(1, "b")]);
+        assert_eq!(tree.try_is_ambiguous(), Err(Error::DeferredChanges));
+        tree.flush();
+        assert_eq!(tree.try_is_ambiguous(), Ok(false));
+    }
+
+    /// The leaves and roots of the RFC 6962 test vectors of Certificate
+    /// Transparency.
+    const RFC6962_LEAVES: [&str; 8] = [
+        "",
+        "00",
+        "10",
+        "2021",
+        "3031",
+        "40414243",
+        "5051525354555657",
+        "606162636465666768696a6b6c6d6e6f",
+    ];
+    const RFC6962_ROOTS: [&str; 8] = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+
+    fn rfc6962_tree(count: usize) -> MerkleTree<Sha256> {
+        let values: Vec<Vec<u8>> = RFC6962_LEAVES[..count]
+            .iter()
+            .map(|leaf| crate::hex::decode(leaf).unwrap())
+            .collect();
+        let mut tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        tree.set_padding(Padding::Promote);
+        tree
+    }
+
+    #[test]
+    fn promote_matches_rfc6962_roots() {
+        for (count, root) in RFC6962_ROOTS.iter().enumerate().map(|(i, r)| (i + 1, r)) {
+            let tree = rfc6962_tree(count);
+            assert_eq!(
+                crate::hex::encode(tree.root_hash()),
+                *root,
+                "{} leaves",
+                count
+            );
+
+            let mut appender = Appender::with_padding(Sha256::default(), Padding::Promote);
+            for leaf in &RFC6962_LEAVES[..count] {
+                appender.append(&crate::hex::decode(leaf).unwrap());
+            }
+            assert_eq!(appender.root().as_ref(), Some(tree.root_hash()));
+        }
+    }
+
+    #[test]
+    fn promote_proofs_skip_unpaired_levels() {
+        let tree = rfc6962_tree(7);
+        let value = crate::hex::decode(RFC6962_LEAVES[6]).unwrap();
+        let proof = tree.gen_proof(6);
+        assert_eq!(proof.path()[0], Sibling::Promoted);
+        assert!(proof.validate_with_padding(
+            tree.root_hash(),
+            &value,
+            Padding::Promote,
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_hasher(tree.root_hash(), &value, Sha256::default()));
+        assert_eq!(Proof::from_bytes(&proof.to_bytes()), Ok(proof));
+
+        let old = rfc6962_tree(3);
+        let consistency = tree.consistency_proof(3);
+        assert!(consistency.verify_with_padding(
+            old.root_hash(),
+            tree.root_hash(),
+            Padding::Promote,
+            Sha256::default()
+        ));
+    }
+}
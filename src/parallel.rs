@@ -0,0 +1,91 @@
+use digest::Digest;
+use rayon::prelude::*;
+use rayon::slice;
+
+use crate::{Error, Hash, MerkleTree};
+
+impl<H: Digest> MerkleTree<H> {
+    /// Does the work of `validate` on the rayon thread pool, one level at a
+    /// time, and fails with the same node.
+    pub fn par_validate(&self) -> Result<(), Error> {
+        self.check_clean()?;
+
+        let stored = self.stored();
+        stored.check_shape()?;
+        for level in 0..stored.levels.len() {
+            let corrupt = (0..stored.levels[level].len())
+                .into_par_iter()
+                .map_init(H::new, |hasher, index| {
+                    let matches = if level == 0 {
+                        stored.leaf_matches(index, hasher)
+                    } else {
+                        stored.node_matches(level, index, hasher)
+                    };
+                    (index, matches)
+                })
+                .find_first(|&(_, matches)| !matches);
+            if let Some((index, _)) = corrupt {
+                return Err(Error::CorruptNode { level, index });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Iterates over the leaf hashes in parallel. The iterator is indexed, so
+    /// `enumerate` yields leaf positions.
+    pub fn par_leaves(&self) -> slice::Iter<'_, Hash> {
+        self.levels[0].par_iter()
+    }
+}
+
+impl<'a, H: Digest> IntoParallelIterator for &'a MerkleTree<H> {
+    type Item = &'a Hash;
+    type Iter = slice::Iter<'a, Hash>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_leaves()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn par_leaves_match_leaves() {
+        let values: Vec<Vec<u8>> = (0..100).map(|i| vec![i]).collect();
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+
+        let leaves: Vec<&Hash> = tree.leaves().collect();
+        let positions: Vec<usize> = tree
+            .par_leaves()
+            .enumerate()
+            .filter(|(i, leaf)| leaves[*i] == *leaf)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(positions, (0..100).collect::<Vec<_>>());
+        assert_eq!((&tree).into_par_iter().count(), 100);
+    }
+
+    #[test]
+    fn par_validate_fails_like_validate() {
+        let values: Vec<Vec<u8>> = (0..100).map(|i| vec![i]).collect();
+        let mut tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        assert_eq!(tree.par_validate(), Ok(()));
+
+        let levels = std::sync::Arc::make_mut(&mut tree.levels);
+        levels[1][40][0] ^= 1;
+        levels[1][7][0] ^= 1;
+        assert_eq!(tree.par_validate(), tree.validate());
+        assert_eq!(
+            tree.par_validate(),
+            Err(Error::CorruptNode { level: 1, index: 7 })
+        );
+
+        tree.defer(&[crate::Mutation::Set(0, vec![1])]);
+        assert_eq!(tree.par_validate(), Err(Error::DeferredChanges));
+    }
+}
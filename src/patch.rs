@@ -0,0 +1,279 @@
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+use crate::metadata::LeafMetadata;
+use crate::mutation::LeafMutation;
+use crate::{hashes_equal, AsBytes, Error, Hash, Limits, MerkleTree, Metadata, Mutation};
+
+/// A batch of leaf changes with the roots before and after it, for shipping
+/// changes from a primary tree to its replicas.
+#[derive(Clone, Debug)]
+pub struct Patch {
+    old_root: Hash,
+    new_root: Hash,
+    mutations: Vec<LeafMutation>,
+}
+
+fn write_metadata(w: &mut Writer, metadata: &Option<LeafMetadata>) {
+    match metadata {
+        Some(m) => {
+            w.u8(1);
+            w.u8(m.committed as u8);
+            w.u64(m.metadata.timestamp);
+            w.u64(m.metadata.sequence);
+            w.bytes(&m.metadata.source);
+        }
+        None => w.u8(0),
+    }
+}
+
+fn read_metadata(r: &mut Reader) -> Result<Option<LeafMetadata>, Error> {
+    match r.u8()? {
+        0 => Ok(None),
+        1 => {
+            let committed = match r.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(Error::InvalidEncoding),
+            };
+            let metadata = Metadata {
+                timestamp: r.u64()?,
+                sequence: r.u64()?,
+                source: r.bytes()?.to_vec(),
+            };
+            Ok(Some(LeafMetadata {
+                metadata,
+                committed,
+            }))
+        }
+        _ => Err(Error::InvalidEncoding),
+    }
+}
+
+impl Patch {
+    pub fn old_root(&self) -> &Hash {
+        &self.old_root
+    }
+
+    pub fn new_root(&self) -> &Hash {
+        &self.new_root
+    }
+
+    /// Returns the number of mutations in the patch.
+    pub fn len(&self) -> usize {
+        self.mutations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(&self.old_root);
+        w.bytes(&self.new_root);
+        w.usize(self.mutations.len());
+        for m in &self.mutations {
+            match m {
+                LeafMutation::Set(i, leaf) => {
+                    w.u8(0);
+                    w.usize(*i);
+                    w.bytes(leaf);
+                }
+                LeafMutation::Push(leaf) => {
+                    w.u8(1);
+                    w.bytes(leaf);
+                }
+                LeafMutation::Remove(i) => {
+                    w.u8(2);
+                    w.usize(*i);
+                }
+                LeafMutation::Insert(i, leaf, metadata) => {
+                    w.u8(3);
+                    w.usize(*i);
+                    w.bytes(leaf);
+                    write_metadata(&mut w, metadata);
+                }
+                LeafMutation::SetMetadata(i, metadata) => {
+                    w.u8(4);
+                    w.usize(*i);
+                    write_metadata(&mut w, metadata);
+                }
+                LeafMutation::SetValue(i, value) => {
+                    w.u8(5);
+                    w.usize(*i);
+                    match value {
+                        Some(v) => {
+                            w.u8(1);
+                            w.bytes(v);
+                        }
+                        None => w.u8(0),
+                    }
+                }
+            }
+        }
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Patch, Error> {
+        Patch::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on patches going past `limits`.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<Patch, Error> {
+        let mut r = Reader::new(bytes);
+        let old_root = r.bytes()?.to_vec();
+        let new_root = r.bytes()?.to_vec();
+        let count = r.usize()?;
+        limits.check_leaves(count)?;
+
+        let mut mutations = Vec::new();
+        for _ in 0..count {
+            let m = match r.u8()? {
+                0 => LeafMutation::Set(r.usize()?, r.bytes()?.to_vec()),
+                1 => LeafMutation::Push(r.bytes()?.to_vec()),
+                2 => LeafMutation::Remove(r.usize()?),
+                3 => LeafMutation::Insert(r.usize()?, r.bytes()?.to_vec(), read_metadata(&mut r)?),
+                4 => LeafMutation::SetMetadata(r.usize()?, read_metadata(&mut r)?),
+                5 => {
+                    let i = r.usize()?;
+                    let value = match r.u8()? {
+                        0 => None,
+                        1 => Some(r.bytes()?.to_vec()),
+                        _ => return Err(Error::InvalidEncoding),
+                    };
+                    LeafMutation::SetValue(i, value)
+                }
+                _ => return Err(Error::InvalidEncoding),
+            };
+            mutations.push(m);
+        }
+        r.finish()?;
+
+        Ok(Patch {
+            old_root,
+            new_root,
+            mutations,
+        })
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Applies `mutations` like `apply` and returns them as a `Patch` that
+    /// replicas can apply.
+    pub fn apply_with_patch<T>(&mut self, mutations: &[Mutation<T>]) -> Patch
+    where
+        T: AsBytes,
+    {
+        let old_root = self.root().clone();
+        let mutations = self.hash_mutations(mutations);
+        self.apply_leaves(mutations.clone());
+
+        Patch {
+            old_root,
+            new_root: self.root_hash().clone(),
+            mutations,
+        }
+    }
+
+    /// Applies a patch produced by `apply_with_patch` on another tree.
+    ///
+    /// Fails without changing the tree if the current root is not the root the
+    /// patch was made against or if a position is out of range. If the result
+    /// does not have the root the patch promises, the changes are reverted,
+    /// though root-change hooks will have seen both the change and the revert.
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), Error> {
+        let root = self.root();
+        if !hashes_equal(root, &patch.old_root) {
+            return Err(Error::RootMismatch {
+                expected: patch.old_root.clone(),
+                actual: root.clone(),
+            });
+        }
+        self.check_positions(&patch.mutations)?;
+
+        let inverse = self.inverse_mutations(&patch.mutations);
+        self.apply_leaves(patch.mutations.clone());
+
+        let root = self.root_hash().clone();
+        if !hashes_equal(&root, &patch.new_root) {
+            self.change_leaves(inverse);
+            self.flush();
+            self.forget_last_undo();
+            return Err(Error::RootMismatch {
+                expected: patch.new_root.clone(),
+                actual: root,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default())
+    }
+
+    #[test]
+    fn replica_replays_decoded_patch() {
+        let (mut primary, mut replica) = (tree(), tree());
+        primary.set_metadata(0, Metadata::default(), true);
+        replica.set_metadata(0, Metadata::default(), true);
+        let patch = primary.apply_with_patch(&[
+            Mutation::Set(0, "d"),
+            Mutation::Remove(1),
+            Mutation::Push("e"),
+        ]);
+        assert_eq!(patch.len(), 3);
+
+        let decoded = Patch::from_bytes(&patch.to_bytes()).unwrap();
+        assert_eq!(decoded.old_root(), patch.old_root());
+        assert_eq!(decoded.new_root(), primary.root_hash());
+        replica.apply_patch(&decoded).unwrap();
+        assert_eq!(replica.root_hash(), primary.root_hash());
+    }
+
+    #[test]
+    fn patch_against_other_root_is_rejected() {
+        let mut primary = tree();
+        let patch = primary.apply_with_patch(&[Mutation::Push("d")]);
+
+        let mut replica = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = replica.root_hash().clone();
+        assert!(matches!(
+            replica.apply_patch(&patch),
+            Err(Error::RootMismatch { .. })
+        ));
+        assert_eq!(replica.root_hash(), &root);
+    }
+
+    #[test]
+    fn patch_promising_other_root_is_reverted() {
+        let mut primary = tree();
+        let patch = primary.apply_with_patch(&[Mutation::Set(1, "d")]);
+        let forged = Patch {
+            new_root: tree().root_hash().clone(),
+            ..patch
+        };
+
+        let mut replica = tree();
+        let root = replica.root_hash().clone();
+        assert!(matches!(
+            replica.apply_patch(&forged),
+            Err(Error::RootMismatch { .. })
+        ));
+        assert_eq!(replica.root_hash(), &root);
+    }
+
+    #[test]
+    fn truncated_patch_fails_to_decode() {
+        let bytes = tree().apply_with_patch(&[Mutation::Push("d")]).to_bytes();
+        assert!(Patch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
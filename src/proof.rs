@@ -0,0 +1,632 @@
+use digest::Digest;
+
+use crate::codec::{Reader, Writer};
+#[cfg(feature = "serde_json")]
+use crate::hex;
+use crate::iter::Path;
+use crate::{
+    count_at_level, hash_internal_node, hash_leaf, hashes_equal, height, AsBytes, Error, Hash,
+    Limits, MerkleTree, Padding,
+};
+
+/// The sibling of a node on the path from a leaf to the root.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sibling {
+    Left(Hash),
+    Right(Hash),
+    /// The node is the last one of a level with an odd count and is paired
+    /// with itself.
+    Duplicate,
+    /// The node is the last one of a level with an odd count and is paired
+    /// with the empty hash, as in trees using `Padding::Empty`.
+    Empty,
+    /// The node is the last one of a level with an odd count and is paired
+    /// with the sentinel of a tree using `Padding::Constant`.
+    Padding(Hash),
+    /// The node is the last one of a level with an odd count and is carried
+    /// up unpaired, as in trees using `Padding::Promote`.
+    Promoted,
+}
+
+/// Proves that a leaf is part of a tree with a given root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Proof {
+    position: usize,
+    count_leaves: usize,
+    path: Vec<Sibling>,
+}
+
+impl Proof {
+    /// Assembles a proof from its parts, such as ones received in another
+    /// encoding. Whether they fit together is only checked when validating.
+    pub fn new(position: usize, count_leaves: usize, path: Vec<Sibling>) -> Proof {
+        Proof {
+            position,
+            count_leaves,
+            path,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    /// Siblings from the leaf level up to the level below the root.
+    pub fn path(&self) -> &[Sibling] {
+        &self.path
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.usize(self.position);
+        w.usize(self.count_leaves);
+        w.usize(self.path.len());
+        for sibling in &self.path {
+            match sibling {
+                Sibling::Left(hash) => {
+                    w.u8(0);
+                    w.bytes(hash);
+                }
+                Sibling::Right(hash) => {
+                    w.u8(1);
+                    w.bytes(hash);
+                }
+                Sibling::Duplicate => w.u8(2),
+                Sibling::Empty => w.u8(3),
+                Sibling::Padding(hash) => {
+                    w.u8(4);
+                    w.bytes(hash);
+                }
+                Sibling::Promoted => w.u8(5),
+            }
+        }
+        w.into_bytes()
+    }
+
+    /// Decodes a proof written by `to_bytes`. Whether it fits the tree it
+    /// claims to come from is only checked when validating it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Proof, Error> {
+        Proof::from_bytes_with_limits(bytes, &Limits::default())
+    }
+
+    /// Like `from_bytes`, but fails on proofs going past `limits`.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<Proof, Error> {
+        let mut r = Reader::new(bytes);
+        let position = r.usize()?;
+        let count_leaves = r.usize()?;
+        limits.check_leaves(count_leaves)?;
+        let len = r.usize()?;
+        limits.check_depth(len)?;
+        limits.check_proof_len(len)?;
+
+        let mut path = Vec::new();
+        for _ in 0..len {
+            let sibling = match r.u8()? {
+                0 => Sibling::Left(r.bytes()?.to_vec()),
+                1 => Sibling::Right(r.bytes()?.to_vec()),
+                2 => Sibling::Duplicate,
+                3 => Sibling::Empty,
+                4 => Sibling::Padding(r.bytes()?.to_vec()),
+                5 => Sibling::Promoted,
+                _ => return Err(Error::InvalidEncoding),
+            };
+            path.push(sibling);
+        }
+        r.finish()?;
+
+        Ok(Proof {
+            position,
+            count_leaves,
+            path,
+        })
+    }
+
+    /// Describes the proof as a JSON object, with siblings from the leaf up
+    /// and hashes in hex:
+    ///
+    /// ```json
+    /// { "position": 2, "count_leaves": 3, "path": ["duplicate", { "left": "…" }] }
+    /// ```
+    ///
+    /// Siblings are `{ "left": … }`, `{ "right": … }`, `{ "padding": … }`,
+    /// `"duplicate"`, `"empty"` or `"promoted"`.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let path: Vec<_> = self
+            .path
+            .iter()
+            .map(|sibling| match sibling {
+                Sibling::Left(hash) => json!({ "left": hex::encode(hash) }),
+                Sibling::Right(hash) => json!({ "right": hex::encode(hash) }),
+                Sibling::Duplicate => json!("duplicate"),
+                Sibling::Empty => json!("empty"),
+                Sibling::Padding(hash) => json!({ "padding": hex::encode(hash) }),
+                Sibling::Promoted => json!("promoted"),
+            })
+            .collect();
+
+        json!({
+            "position": self.position,
+            "count_leaves": self.count_leaves,
+            "path": path,
+        })
+    }
+
+    /// Reads a proof written by `to_json`, ignoring any other fields.
+    #[cfg(feature = "serde_json")]
+    pub fn from_json(value: &serde_json::Value) -> Result<Proof, Error> {
+        Proof::from_json_with_limits(value, &Limits::default())
+    }
+
+    /// Like `from_json`, but fails on proofs going past `limits`.
+    #[cfg(feature = "serde_json")]
+    pub fn from_json_with_limits(
+        value: &serde_json::Value,
+        limits: &Limits,
+    ) -> Result<Proof, Error> {
+        // Numbers too large for this target are rejected, not truncated, so a
+        // proof means the same thing on every platform.
+        let number = |name: &str| {
+            value[name]
+                .as_u64()
+                .and_then(|n| usize::try_from(n).ok())
+                .ok_or(Error::InvalidEncoding)
+        };
+        let hash = |v: &serde_json::Value| hex::decode(v.as_str().ok_or(Error::InvalidEncoding)?);
+
+        let path = value["path"]
+            .as_array()
+            .ok_or(Error::InvalidEncoding)?
+            .iter()
+            .map(|sibling| {
+                if sibling == "duplicate" {
+                    Ok(Sibling::Duplicate)
+                } else if sibling == "empty" {
+                    Ok(Sibling::Empty)
+                } else if sibling == "promoted" {
+                    Ok(Sibling::Promoted)
+                } else if let Some(left) = sibling.get("left") {
+                    Ok(Sibling::Left(hash(left)?))
+                } else if let Some(right) = sibling.get("right") {
+                    Ok(Sibling::Right(hash(right)?))
+                } else if let Some(padding) = sibling.get("padding") {
+                    Ok(Sibling::Padding(hash(padding)?))
+                } else {
+                    Err(Error::InvalidEncoding)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let proof = Proof::new(number("position")?, number("count_leaves")?, path);
+        limits.check_proof(&proof)?;
+        Ok(proof)
+    }
+
+    /// Returns true if `value` is the leaf at `position` of the tree with
+    /// `root_hash`, using a fresh `H` to hash.
+    ///
+    /// Needs nothing but the root, so a client can check a value handed out
+    /// by a server it does not trust. Expects the tree to use the default
+    /// `Padding::Duplicate`; see `validate_with_padding` for other trees.
+    pub fn validate<T, H>(&self, root_hash: &[u8], value: &T) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.validate_with_hasher(root_hash, value, H::new())
+    }
+
+    /// Returns true if `value` is the leaf at `position` of the tree with
+    /// `root_hash`, a tree using `Padding::Duplicate`.
+    pub fn validate_with_hasher<T, H>(&self, root_hash: &[u8], value: &T, hasher: H) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.validate_with_padding(root_hash, value, Padding::Duplicate, hasher)
+    }
+
+    /// Returns true if `value` is the leaf at `position` of the tree with
+    /// `root_hash`, a tree using `padding`.
+    ///
+    /// The padding is the verifier's to choose: the odd last node of a level
+    /// must come with the sibling `padding` pairs it with and nothing else,
+    /// so a prover cannot pass off a real right sibling as padding to claim
+    /// a smaller tree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(position = self.position, count_leaves = self.count_leaves)
+        )
+    )]
+    pub fn validate_with_padding<T, H>(
+        &self,
+        root_hash: &[u8],
+        value: &T,
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let leaf = hash_leaf(value, &mut hasher);
+        self.validate_leaf(root_hash, leaf, padding, &mut hasher)
+    }
+
+    /// Like `validate_with_hasher`, but also rejects proofs that pair a node
+    /// with an identical sibling.
+    ///
+    /// Under `Padding::Duplicate` such a pair is how a proof passes off the
+    /// odd last node of a level as two leaves, claiming a tree with more
+    /// leaves than the one the root was computed over. Honest trees only
+    /// contain one when two neighbouring leaves are equal.
+    pub fn validate_strict_with_hasher<T, H>(
+        &self,
+        root_hash: &[u8],
+        value: &T,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let leaf = hash_leaf(value, &mut hasher);
+        match self.fold(leaf, true, Padding::Duplicate, &mut hasher) {
+            Some(root) => hashes_equal(&root, root_hash),
+            None => false,
+        }
+    }
+
+    pub(crate) fn validate_leaf<H>(
+        &self,
+        root_hash: &[u8],
+        leaf: Hash,
+        padding: Padding,
+        hasher: &mut H,
+    ) -> bool
+    where
+        H: Digest,
+    {
+        match self.compute_root(leaf, padding, hasher) {
+            Some(root) => hashes_equal(&root, root_hash),
+            None => false,
+        }
+    }
+
+    /// Folds `leaf` up the path, or returns `None` if the siblings do not match
+    /// the shape of a tree with `count_leaves` leaves using `padding`.
+    pub(crate) fn compute_root<H>(
+        &self,
+        leaf: Hash,
+        padding: Padding,
+        hasher: &mut H,
+    ) -> Option<Hash>
+    where
+        H: Digest,
+    {
+        self.fold(leaf, false, padding, hasher)
+    }
+
+    /// Does the work of `compute_root`, failing on identical siblings too if
+    /// `strict` is set.
+    fn fold<H>(&self, leaf: Hash, strict: bool, padding: Padding, hasher: &mut H) -> Option<Hash>
+    where
+        H: Digest,
+    {
+        if self.position >= self.count_leaves
+            || self.path.len() != height(self.count_leaves)
+            || Limits::default().check_proof(self).is_err()
+        {
+            return None;
+        }
+
+        let mut node = leaf;
+        let mut j = self.position;
+        for (level, sibling) in self.path.iter().enumerate() {
+            let odd_last = j == count_at_level(self.count_leaves, level) - 1 && j.is_multiple_of(2);
+            node = match sibling {
+                Sibling::Left(h) | Sibling::Right(h) if strict && hashes_equal(h, &node) => {
+                    return None
+                }
+                Sibling::Left(left) if !j.is_multiple_of(2) => {
+                    hash_internal_node(left, Some(&node), padding, hasher)
+                }
+                Sibling::Right(right) if j.is_multiple_of(2) && !odd_last => {
+                    hash_internal_node(&node, Some(right), padding, hasher)
+                }
+                Sibling::Duplicate if odd_last && padding == Padding::Duplicate => {
+                    hash_internal_node(&node, None, padding, hasher)
+                }
+                Sibling::Empty if odd_last && padding == Padding::Empty => {
+                    hash_internal_node(&node, None, padding, hasher)
+                }
+                Sibling::Padding(pad) if odd_last && matches_sentinel(padding, pad) => {
+                    hash_internal_node(&node, Some(pad), padding, hasher)
+                }
+                Sibling::Promoted if odd_last && padding == Padding::Promote => node,
+                _ => return None,
+            };
+            j >>= 1;
+        }
+
+        Some(node)
+    }
+}
+
+/// Returns true if `pad` is the sentinel of `padding`.
+fn matches_sentinel(padding: Padding, pad: &[u8]) -> bool {
+    match padding {
+        Padding::Constant(sentinel) => hashes_equal(sentinel.as_bytes(), pad),
+        _ => false,
+    }
+}
+
+/// Builds the inclusion proof of `position` from the `levels` of an up to
+/// date tree.
+pub(crate) fn proof_in(levels: &[Vec<Hash>], position: usize, padding: Padding) -> Proof {
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_node_reads(levels.len() - 1);
+
+    let path = Path::new(levels, position, padding)
+        .map(|(_, sibling)| sibling)
+        .collect();
+
+    Proof {
+        position,
+        count_leaves: levels[0].len(),
+        path,
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the inclusion proof of the leaf at `position`.
+    pub fn gen_proof(&self, position: usize) -> Proof {
+        self.try_gen_proof(position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `gen_proof`, but fails instead of panicking if `position` does not
+    /// relate to any leaf or the tree has deferred changes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn try_gen_proof(&self, position: usize) -> Result<Proof, Error> {
+        self.check_position(position)?;
+        self.check_clean()?;
+
+        Ok(proof_in(&self.levels, position, self.padding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{hex, Sentinel};
+
+    const VALUES: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+    fn tree(count: usize, padding: Padding) -> MerkleTree<Sha256> {
+        let mut tree = MerkleTree::build_with_hasher(&VALUES[..count], Sha256::default());
+        tree.set_padding(padding);
+        tree
+    }
+
+    fn leaf(value: &str) -> Hash {
+        hash_leaf(&value, &mut Sha256::default())
+    }
+
+    #[test]
+    fn proofs_validate_under_their_own_padding_only() {
+        let sentinel = Padding::Constant(Sentinel::new(&[7u8; 32]));
+        let paddings = [Padding::Duplicate, Padding::Empty, sentinel];
+        for &padding in &paddings {
+            let tree = tree(5, padding);
+            for (position, value) in VALUES.iter().enumerate() {
+                let proof = tree.gen_proof(position);
+                for &other in &paddings {
+                    let valid = proof.validate_with_padding(
+                        tree.root_hash(),
+                        value,
+                        other,
+                        Sha256::default(),
+                    );
+                    assert_eq!(valid, other == padding || position < 4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn right_sibling_cannot_pass_as_padding() {
+        let tree = tree(4, Padding::Duplicate);
+        let ab = tree.gen_proof(2).path()[1].clone();
+        let forged = Proof::new(2, 3, vec![Sibling::Padding(leaf("d")), ab]);
+
+        assert!(!forged.validate_with_hasher(tree.root_hash(), &"c", Sha256::default()));
+        assert!(!forged.validate_strict_with_hasher(tree.root_hash(), &"c", Sha256::default()));
+        let sentinel = Padding::Constant(Sentinel::new(&[7u8; 32]));
+        assert!(!forged.validate_with_padding(tree.root_hash(), &"c", sentinel, Sha256::default()));
+    }
+
+    #[test]
+    fn paddings_cannot_be_mixed_across_levels() {
+        let empty = tree(5, Padding::Empty);
+        let abcd = empty.gen_proof(4).path()[2].clone();
+        let abcd_hash = match &abcd {
+            Sibling::Left(h) => h.clone(),
+            sibling => panic!("unexpected sibling {:?}", sibling),
+        };
+
+        let mut hasher = Sha256::default();
+        let ee = hash_internal_node(&leaf("e"), None, Padding::Duplicate, &mut hasher);
+        let right = hash_internal_node(&ee, None, Padding::Empty, &mut hasher);
+        let root = hash_internal_node(&abcd_hash, Some(&right), Padding::Duplicate, &mut hasher);
+
+        let mixed = Proof::new(4, 5, vec![Sibling::Duplicate, Sibling::Empty, abcd]);
+        for &padding in &[Padding::Duplicate, Padding::Empty] {
+            assert!(!mixed.validate_with_padding(&root, &"e", padding, Sha256::default()));
+        }
+    }
+
+    #[test]
+    fn reject_padding_validates_complete_trees_only() {
+        let tree = tree(4, Padding::Reject);
+        assert!(tree.gen_proof(3).validate_with_padding(
+            tree.root_hash(),
+            &"d",
+            Padding::Reject,
+            Sha256::default()
+        ));
+
+        let duplicate = self::tree(3, Padding::Duplicate);
+        let proof = duplicate.gen_proof(2);
+        assert!(!proof.validate_with_padding(
+            duplicate.root_hash(),
+            &"c",
+            Padding::Reject,
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    fn strict_validation_rejects_duplicated_last_leaf() {
+        let three = tree(3, Padding::Duplicate);
+        let c = leaf("c");
+        let ab = three.gen_proof(2).path()[1].clone();
+        let forged = Proof::new(3, 4, vec![Sibling::Left(c), ab]);
+
+        assert!(forged.validate_with_hasher(three.root_hash(), &"c", Sha256::default()));
+        assert!(!forged.validate_strict_with_hasher(three.root_hash(), &"c", Sha256::default()));
+        assert!(three.gen_proof(2).validate_strict_with_hasher(
+            three.root_hash(),
+            &"c",
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    fn empty_padding_distinguishes_duplicated_last_leaf() {
+        let three = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let four = MerkleTree::build_with_hasher(&["a", "b", "c", "c"], Sha256::default());
+        assert_eq!(three.root_hash(), four.root_hash());
+
+        let mut three = three;
+        let mut four = four;
+        three.set_padding(Padding::Empty);
+        four.set_padding(Padding::Empty);
+        assert_ne!(three.root_hash(), four.root_hash());
+    }
+
+    #[test]
+    fn strict_validation_rejects_sibling_equal_to_node() {
+        let tree = MerkleTree::build_with_hasher(&["a", "a", "b"], Sha256::default());
+        for position in 0..2 {
+            let proof = tree.gen_proof(position);
+            assert!(proof.validate_with_hasher(tree.root_hash(), &"a", Sha256::default()));
+            assert!(!proof.validate_strict_with_hasher(tree.root_hash(), &"a", Sha256::default()));
+        }
+        assert!(tree.gen_proof(2).validate_strict_with_hasher(
+            tree.root_hash(),
+            &"b",
+            Sha256::default()
+        ));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_round_trips() {
+        let tree = tree(5, Padding::Empty);
+        for position in 0..5 {
+            let proof = tree.gen_proof(position);
+            assert_eq!(Proof::from_json(&proof.to_json()), Ok(proof));
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_rejects_loose_hex() {
+        let json = serde_json::json!({
+            "position": 1,
+            "count_leaves": 2,
+            "path": [{ "left": "+f" }],
+        });
+        assert_eq!(Proof::from_json(&json), Err(Error::InvalidEncoding));
+    }
+
+    #[test]
+    fn golden_root_and_encoding() {
+        let tree = tree(3, Padding::Duplicate);
+        assert_eq!(
+            hex::encode(tree.root_hash()),
+            "e9636069c740c9ff51625b01a0b040396d265a9b920cc6febdfa5ecc9f58ecce"
+        );
+
+        // Sizes are little-endian u64s whatever the pointer width.
+        let bytes = tree.gen_proof(2).to_bytes();
+        assert_eq!(
+            hex::encode(&bytes),
+            "0200000000000000030000000000000002000000000000000200200000000000\
+             0000b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb"
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_number_above_u32_max_is_not_truncated() {
+        let position = u64::from(u32::MAX) + 1;
+        let json = serde_json::json!({
+            "position": position,
+            "count_leaves": position + 1,
+            "path": [],
+        });
+        let limits = Limits {
+            max_depth: 64,
+            max_leaves: usize::MAX,
+            max_proof_len: 129,
+        };
+
+        let proof = Proof::from_json_with_limits(&json, &limits);
+        match usize::try_from(position) {
+            Ok(position) => assert_eq!(proof.map(|p| p.position()), Ok(position)),
+            Err(_) => assert_eq!(proof, Err(Error::InvalidEncoding)),
+        }
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn proof_deeper_than_default_limits_is_rejected() {
+        let depth = Limits::default().max_depth + 1;
+        let mut hasher = Sha256::default();
+        let sibling = leaf("b");
+        let mut root = leaf("a");
+        for _ in 0..depth {
+            root = hash_internal_node(&root, Some(&sibling), Padding::Duplicate, &mut hasher);
+        }
+
+        let proof = Proof::new(0, 1 << depth, vec![Sibling::Right(sibling); depth]);
+        assert!(!proof.validate_with_hasher(&root, &"a", Sha256::default()));
+    }
+
+    #[test]
+    fn proofs_round_trip_through_bytes_in_odd_trees() {
+        let values: Vec<Vec<u8>> = (0..17u8).map(|i| vec![i]).collect();
+        for count in (1..=17).step_by(2) {
+            let tree = MerkleTree::build_with_hasher(&values[..count], Sha256::default());
+            for (position, value) in values[..count].iter().enumerate() {
+                let proof = Proof::from_bytes(&tree.gen_proof(position).to_bytes()).unwrap();
+                assert_eq!(proof, tree.gen_proof(position));
+                assert!(proof.validate::<_, Sha256>(tree.root_hash(), value));
+                assert!(!proof.validate::<_, Sha256>(tree.root_hash(), &vec![0xff]));
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Python bindings for SHA-256 trees, built as the `merkle_rs` extension
+//! module.
+
+use std::borrow::Cow;
+
+use digest::Digest;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use sha2::Sha256;
+
+use crate::Error;
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> PyErr {
+        PyValueError::new_err(e.to_string())
+    }
+}
+
+/// A tree of SHA-256 hashes over byte strings.
+#[pyclass(name = "MerkleTree", unsendable)]
+struct PyTree {
+    inner: crate::MerkleTree<Sha256>,
+}
+
+#[pymethods]
+impl PyTree {
+    #[new]
+    fn new(values: Vec<Vec<u8>>) -> PyResult<PyTree> {
+        if values.is_empty() {
+            return Err(PyValueError::new_err("expected at least 1 value"));
+        }
+
+        Ok(PyTree {
+            inner: crate::MerkleTree::build_with_hasher(&values, Sha256::new()),
+        })
+    }
+
+    fn root(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.inner.root_hash())
+    }
+
+    fn count_leaves(&self) -> usize {
+        self.inner.count_leaves()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.count_leaves()
+    }
+
+    fn push(&mut self, value: Vec<u8>) {
+        self.inner.push(&value);
+    }
+
+    fn update(&mut self, position: usize, value: Vec<u8>) -> Result<(), Error> {
+        self.inner.try_update(position, &value)
+    }
+
+    fn verify(&mut self, position: usize, value: Vec<u8>) -> Result<bool, Error> {
+        self.inner.try_verify(position, &value)
+    }
+
+    fn gen_proof(&self, position: usize) -> Result<PyProof, Error> {
+        let inner = self.inner.try_gen_proof(position)?;
+        Ok(PyProof { inner })
+    }
+}
+
+/// Proves that a leaf is part of a tree with a given root.
+#[pyclass(name = "Proof")]
+struct PyProof {
+    inner: crate::Proof,
+}
+
+#[pymethods]
+impl PyProof {
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> Result<PyProof, Error> {
+        let inner = crate::Proof::from_bytes(bytes)?;
+        Ok(PyProof { inner })
+    }
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.inner.to_bytes())
+    }
+
+    #[getter]
+    fn position(&self) -> usize {
+        self.inner.position()
+    }
+
+    #[getter]
+    fn count_leaves(&self) -> usize {
+        self.inner.count_leaves()
+    }
+
+    /// Returns true if `value` is the proven leaf of the tree with `root`.
+    fn validate(&self, root: &[u8], value: Vec<u8>) -> bool {
+        self.inner.validate::<_, Sha256>(root, &value)
+    }
+}
+
+#[pymodule]
+fn merkle_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyTree>()?;
+    m.add_class::<PyProof>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Vec<Vec<u8>> {
+        vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+    }
+
+    #[test]
+    fn tree_matches_the_rust_tree() {
+        let mut tree = PyTree::new(values()).unwrap();
+        tree.push(b"d".to_vec());
+        tree.update(0, b"x".to_vec()).unwrap();
+
+        let expected = crate::MerkleTree::build_with_hasher(&["x", "b", "c", "d"], Sha256::new());
+        assert_eq!(&tree.root()[..], &expected.root_hash()[..]);
+        assert_eq!(tree.__len__(), 4);
+        assert_eq!(tree.verify(3, b"d".to_vec()), Ok(true));
+        assert_eq!(
+            tree.verify(4, b"d".to_vec()),
+            Err(Error::InvalidPosition(4))
+        );
+        assert!(PyTree::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn proof_round_trips_and_validates() {
+        let tree = PyTree::new(values()).unwrap();
+        let proof = PyProof::from_bytes(&tree.gen_proof(2).unwrap().to_bytes()).unwrap();
+
+        assert_eq!((proof.position(), proof.count_leaves()), (2, 3));
+        assert!(proof.validate(&tree.root(), b"c".to_vec()));
+        assert!(!proof.validate(&tree.root(), b"b".to_vec()));
+        assert!(tree.gen_proof(3).is_err());
+    }
+}
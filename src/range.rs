@@ -0,0 +1,323 @@
+use std::ops::Range;
+
+use digest::Digest;
+
+use crate::{
+    count_at_level, hash_internal_node, hash_leaf, hash_odd_node, hashes_equal, height, AsBytes,
+    Error, Hash, Limits, MerkleTree, Padding,
+};
+
+/// Proves that a run of consecutive leaves is part of a tree with a given
+/// root.
+///
+/// Holds, level by level, the node just left of the run when the run starts
+/// on a right child and the node just right of it when the run ends on a
+/// left child that has a sibling. Everything in between is recomputed from
+/// the leaves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeProof {
+    start: usize,
+    count_leaves: usize,
+    hashes: Vec<Hash>,
+    padding: Padding,
+}
+
+impl RangeProof {
+    /// Position of the first leaf of the run.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    /// How the tree pairs the odd last node of a level.
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Returns true if `values` are the leaves from `start` on of the tree
+    /// with `root_hash`, a tree using `Padding::Duplicate`.
+    pub fn validate_with_hasher<T, H>(&self, root_hash: &[u8], values: &[T], hasher: H) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.validate_with_padding(root_hash, values, Padding::Duplicate, hasher)
+    }
+
+    /// Like `validate_with_hasher`, for a tree using `padding`. A proof made
+    /// for a tree using another padding is rejected.
+    pub fn validate_with_padding<T, H>(
+        &self,
+        root_hash: &[u8],
+        values: &[T],
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        let leaves = values.iter().map(|v| hash_leaf(v, &mut hasher)).collect();
+        self.validate_leaves(root_hash, leaves, padding, &mut hasher)
+    }
+
+    /// Returns true if `leaves` are the leaf hashes from `start` on of the
+    /// tree with `root_hash`, a tree using `Padding::Duplicate`.
+    pub fn validate_leaves_with_hasher<H>(
+        &self,
+        root_hash: &[u8],
+        leaves: &[Hash],
+        hasher: H,
+    ) -> bool
+    where
+        H: Digest,
+    {
+        self.validate_leaves_with_padding(root_hash, leaves, Padding::Duplicate, hasher)
+    }
+
+    /// Like `validate_leaves_with_hasher`, for a tree using `padding`.
+    pub fn validate_leaves_with_padding<H>(
+        &self,
+        root_hash: &[u8],
+        leaves: &[Hash],
+        padding: Padding,
+        mut hasher: H,
+    ) -> bool
+    where
+        H: Digest,
+    {
+        self.validate_leaves(root_hash, leaves.to_vec(), padding, &mut hasher)
+    }
+
+    fn validate_leaves<H>(
+        &self,
+        root_hash: &[u8],
+        leaves: Vec<Hash>,
+        padding: Padding,
+        hasher: &mut H,
+    ) -> bool
+    where
+        H: Digest,
+    {
+        if self.padding != padding || Limits::default().check_range_proof(self).is_err() {
+            return false;
+        }
+        match self.compute_root(leaves, hasher) {
+            Some(root) => hashes_equal(&root, root_hash),
+            None => false,
+        }
+    }
+
+    /// Folds the run of `nodes` up to the root, or returns `None` if the
+    /// hashes do not match the shape of the run.
+    fn compute_root<H>(&self, mut nodes: Vec<Hash>, hasher: &mut H) -> Option<Hash>
+    where
+        H: Digest,
+    {
+        let n = self.count_leaves;
+        if nodes.is_empty() || self.start >= n || nodes.len() > n - self.start {
+            return None;
+        }
+
+        let mut hashes = self.hashes.iter();
+        let mut lo = self.start;
+        for level in 0..height(n) {
+            let last = count_at_level(n, level) - 1;
+            let hi = lo + nodes.len() - 1;
+
+            if lo % 2 == 1 {
+                nodes.insert(0, hashes.next()?.clone());
+            }
+            if hi.is_multiple_of(2) && hi < last {
+                nodes.push(hashes.next()?.clone());
+            }
+
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| match pair.get(1) {
+                    Some(right) => Some(hash_internal_node(
+                        &pair[0],
+                        Some(right),
+                        self.padding,
+                        hasher,
+                    )),
+                    None => hash_odd_node(&pair[0], self.padding, hasher),
+                })
+                .collect::<Option<_>>()?;
+            lo >>= 1;
+        }
+
+        if hashes.next().is_some() {
+            return None;
+        }
+        nodes.pop()
+    }
+}
+
+/// Retained values of a run of leaves and the proof covering them.
+type ValuesWithProof<'a> = (Vec<&'a [u8]>, RangeProof);
+
+/// Builds the proof of `range` from the `levels` of an up to date tree.
+fn range_proof_in(levels: &[Vec<Hash>], range: Range<usize>, padding: Padding) -> RangeProof {
+    let count_leaves = levels[0].len();
+    let mut hashes = Vec::new();
+    let (mut lo, mut hi) = (range.start, range.end - 1);
+
+    for nodes in &levels[..height(count_leaves)] {
+        if lo % 2 == 1 {
+            hashes.push(nodes[lo - 1].clone());
+        }
+        if hi.is_multiple_of(2) && hi + 1 < nodes.len() {
+            hashes.push(nodes[hi + 1].clone());
+        }
+        lo >>= 1;
+        hi >>= 1;
+    }
+
+    RangeProof {
+        start: range.start,
+        count_leaves,
+        hashes,
+        padding,
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the leaf hashes of `range` together with one proof covering
+    /// all of them.
+    pub fn get_range_with_proof(&self, range: Range<usize>) -> (Vec<Hash>, RangeProof) {
+        self.try_get_range_with_proof(range)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `get_range_with_proof`, but fails instead of panicking if `range`
+    /// does not relate to any leaves or the tree has deferred changes.
+    pub fn try_get_range_with_proof(
+        &self,
+        range: Range<usize>,
+    ) -> Result<(Vec<Hash>, RangeProof), Error> {
+        if range.start >= range.end || range.end > self.count_leaves() {
+            return Err(Error::InvalidRange {
+                start: range.start,
+                end: range.end,
+            });
+        }
+        self.check_clean()?;
+
+        let leaves = self.levels[0][range.clone()].to_vec();
+        Ok((leaves, range_proof_in(&self.levels, range, self.padding)))
+    }
+
+    /// Returns the retained values of `range` together with one proof
+    /// covering all of them, or `None` if some value is not retained.
+    pub fn get_values_with_proof(&self, range: Range<usize>) -> Option<ValuesWithProof<'_>> {
+        self.try_get_values_with_proof(range)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `get_values_with_proof`, but fails instead of panicking if
+    /// `range` does not relate to any leaves or the tree has deferred
+    /// changes.
+    pub fn try_get_values_with_proof(
+        &self,
+        range: Range<usize>,
+    ) -> Result<Option<ValuesWithProof<'_>>, Error> {
+        let (_, proof) = self.try_get_range_with_proof(range.clone())?;
+        let values = range.map(|i| self.value(i)).collect::<Option<Vec<_>>>();
+        Ok(values.map(|values| (values, proof)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    const VALUES: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+    #[test]
+    fn range_proof_validates_under_its_padding_only() {
+        let mut tree = MerkleTree::build_with_hasher(&VALUES, Sha256::default());
+        tree.set_padding(Padding::Empty);
+        let (_, proof) = tree.get_range_with_proof(3..5);
+
+        assert!(proof.validate_with_padding(
+            tree.root_hash(),
+            &VALUES[3..],
+            Padding::Empty,
+            Sha256::default()
+        ));
+        assert!(!proof.validate_with_hasher(tree.root_hash(), &VALUES[3..], Sha256::default()));
+    }
+
+    #[test]
+    fn reject_padding_on_odd_level_fails_without_panicking() {
+        let tree = MerkleTree::build_with_hasher(&VALUES, Sha256::default());
+        let (_, proof) = tree.get_range_with_proof(3..5);
+        let proof = RangeProof {
+            padding: Padding::Reject,
+            ..proof
+        };
+
+        assert!(!proof.validate_with_padding(
+            tree.root_hash(),
+            &VALUES[3..],
+            Padding::Reject,
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    fn try_get_values_with_proof_fails_on_bad_range() {
+        let tree = MerkleTree::build_retaining_values(&VALUES, Sha256::default());
+        assert_eq!(
+            tree.try_get_values_with_proof(4..6),
+            Err(Error::InvalidRange { start: 4, end: 6 })
+        );
+
+        let (values, proof) = tree.try_get_values_with_proof(1..3).unwrap().unwrap();
+        assert_eq!(values, vec![&b"b"[..], &b"c"[..]]);
+        assert!(proof.validate_with_hasher(tree.root_hash(), &VALUES[1..3], Sha256::default()));
+    }
+
+    #[test]
+    fn every_range_validates_and_rejects_altered_values() {
+        let tree = MerkleTree::build_with_hasher(&VALUES, Sha256::default());
+        for start in 0..VALUES.len() {
+            for end in start + 1..=VALUES.len() {
+                let (leaves, proof) = tree.get_range_with_proof(start..end);
+                assert_eq!(
+                    leaves,
+                    tree.leaves().cloned().collect::<Vec<_>>()[start..end]
+                );
+                assert!(
+                    proof.validate_with_hasher(
+                        tree.root_hash(),
+                        &VALUES[start..end],
+                        Sha256::default()
+                    ),
+                    "{}..{}",
+                    start,
+                    end
+                );
+
+                let mut altered = VALUES[start..end].to_vec();
+                altered[0] = "z";
+                assert!(!proof.validate_with_hasher(tree.root_hash(), &altered, Sha256::default()));
+                assert!(!proof.validate_with_hasher(
+                    tree.root_hash(),
+                    &VALUES[start..end - 1],
+                    Sha256::default()
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use digest::Digest;
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+use crate::codec::Writer;
+use crate::{hash_leaf, hashes_equal, AsBytes, Error, Hash, MerkleTree, Padding, Proof};
+
+/// Length of the salts `Record::with_random_salts` draws.
+#[cfg(feature = "rand")]
+const SALT_LEN: usize = 32;
+
+/// Names the fields of a record, in the order they are committed to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldLayout {
+    names: Vec<String>,
+}
+
+impl FieldLayout {
+    pub fn new<S: AsRef<str>>(names: &[S]) -> FieldLayout {
+        FieldLayout::try_new(names).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `names` is empty, or `Error::DuplicateField` if it names a field
+    /// twice.
+    pub fn try_new<S: AsRef<str>>(names: &[S]) -> Result<FieldLayout, Error> {
+        if names.is_empty() {
+            return Err(Error::InvalidSize(0));
+        }
+        let names: Vec<String> = names.iter().map(|n| n.as_ref().to_string()).collect();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(Error::DuplicateField(name.clone()));
+            }
+        }
+        Ok(FieldLayout { names })
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn count_fields(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns the position of the field called `name`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+}
+
+/// Encodes what the leaf of a field commits to. The name ties the value to
+/// its field, and the salt keeps the hashes revealed next to a disclosed
+/// field from giving away guessable values of the others.
+fn field_leaf(name: &str, salt: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(name.as_bytes());
+    w.bytes(salt);
+    w.bytes(value);
+    w.into_bytes()
+}
+
+/// The values of a record, each with its salt, committed to as a small tree
+/// with one leaf per field so that fields can be disclosed one at a time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    layout: FieldLayout,
+    values: Vec<Vec<u8>>,
+    salts: Vec<Vec<u8>>,
+}
+
+impl Record {
+    /// Creates a record with `values` and `salts` in the order of `layout`.
+    ///
+    /// Salts should be random and at least 16 bytes long, or the sibling
+    /// hashes in a field proof let a verifier check guesses of the other
+    /// fields.
+    pub fn new(layout: &FieldLayout, values: Vec<Vec<u8>>, salts: Vec<Vec<u8>>) -> Record {
+        Record::try_new(layout, values, salts)
+            .expect("record does not have a value and a salt per field")
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if there is not a value and a salt per field.
+    pub fn try_new(
+        layout: &FieldLayout,
+        values: Vec<Vec<u8>>,
+        salts: Vec<Vec<u8>>,
+    ) -> Result<Record, Error> {
+        for len in [values.len(), salts.len()] {
+            if len != layout.count_fields() {
+                return Err(Error::InvalidSize(len));
+            }
+        }
+
+        Ok(Record {
+            layout: layout.clone(),
+            values,
+            salts,
+        })
+    }
+
+    /// Creates a record with `values`, salting every field with bytes drawn
+    /// from `rng`.
+    #[cfg(feature = "rand")]
+    pub fn with_random_salts<R>(layout: &FieldLayout, values: Vec<Vec<u8>>, rng: &mut R) -> Record
+    where
+        R: Rng + ?Sized,
+    {
+        let salts = (0..layout.count_fields())
+            .map(|_| {
+                let mut salt = vec![0u8; SALT_LEN];
+                rng.fill(&mut salt[..]);
+                salt
+            })
+            .collect();
+        Record::new(layout, values, salts)
+    }
+
+    pub fn layout(&self) -> &FieldLayout {
+        &self.layout
+    }
+
+    /// Returns the value of the field called `name`.
+    pub fn value(&self, name: &str) -> Option<&[u8]> {
+        self.layout.position(name).map(|i| &self.values[i][..])
+    }
+
+    fn tree<H: Digest>(&self, hasher: H) -> MerkleTree<H> {
+        let leaves: Vec<Vec<u8>> = (0..self.layout.count_fields())
+            .map(|i| field_leaf(&self.layout.names[i], &self.salts[i], &self.values[i]))
+            .collect();
+        MerkleTree::build_with_hasher(&leaves, hasher)
+    }
+
+    /// Returns the root committing to every field, the value a tree of
+    /// records stores for this one.
+    pub fn root_with_hasher<H: Digest>(&self, hasher: H) -> Hash {
+        self.tree(hasher).root_hash().clone()
+    }
+
+    /// Returns a proof disclosing the field called `name` against the root
+    /// of this record.
+    pub fn gen_field_proof<H: Digest>(&self, name: &str, hasher: H) -> FieldProof {
+        self.try_gen_field_proof(name, hasher)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `gen_field_proof`, but fails with `Error::UnknownField` instead
+    /// of panicking if the record has no field called `name`.
+    pub fn try_gen_field_proof<H: Digest>(
+        &self,
+        name: &str,
+        hasher: H,
+    ) -> Result<FieldProof, Error> {
+        let position = self
+            .layout
+            .position(name)
+            .ok_or_else(|| Error::UnknownField(name.to_string()))?;
+
+        Ok(FieldProof {
+            name: name.to_string(),
+            salt: self.salts[position].clone(),
+            inner: self.tree(hasher).gen_proof(position),
+        })
+    }
+}
+
+/// Discloses one field of a record against the root of the record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldProof {
+    name: String,
+    salt: Vec<u8>,
+    inner: Proof,
+}
+
+impl FieldProof {
+    pub fn new(name: String, salt: Vec<u8>, inner: Proof) -> FieldProof {
+        FieldProof { name, salt, inner }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn inner(&self) -> &Proof {
+        &self.inner
+    }
+
+    /// Chains this proof with `outer`, the proof of the record root within
+    /// a tree of records.
+    pub fn with_outer(self, outer: Proof) -> RecordProof {
+        RecordProof { field: self, outer }
+    }
+
+    /// Returns the record root this proof and `value` lead to, if the proof
+    /// fits `layout`.
+    fn record_root<T, H>(&self, layout: &FieldLayout, value: &T, hasher: &mut H) -> Option<Hash>
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        if layout.position(&self.name) != Some(self.inner.position())
+            || layout.count_fields() != self.inner.count_leaves()
+        {
+            return None;
+        }
+
+        let leaf = hash_leaf(
+            &field_leaf(&self.name, &self.salt, value.as_bytes()),
+            hasher,
+        );
+        self.inner.compute_root(leaf, Padding::Duplicate, hasher)
+    }
+
+    /// Returns true if `value` is the field this proof names, of a record
+    /// laid out as `layout` whose root is `record_root`.
+    pub fn validate_with_hasher<T, H>(
+        &self,
+        layout: &FieldLayout,
+        record_root: &[u8],
+        value: &T,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        self.record_root(layout, value, &mut hasher)
+            .is_some_and(|root| hashes_equal(&root, record_root))
+    }
+}
+
+/// Discloses one field of a record stored as a leaf of a tree, against the
+/// root of that tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordProof {
+    field: FieldProof,
+    outer: Proof,
+}
+
+impl RecordProof {
+    pub fn new(field: FieldProof, outer: Proof) -> RecordProof {
+        RecordProof { field, outer }
+    }
+
+    pub fn field(&self) -> &FieldProof {
+        &self.field
+    }
+
+    pub fn outer(&self) -> &Proof {
+        &self.outer
+    }
+
+    /// Returns true if `value` is the field this proof names, of a record
+    /// laid out as `layout` stored as a leaf of the tree with `root_hash`.
+    pub fn validate_with_hasher<T, H>(
+        &self,
+        layout: &FieldLayout,
+        root_hash: &[u8],
+        value: &T,
+        mut hasher: H,
+    ) -> bool
+    where
+        T: AsBytes,
+        H: Digest,
+    {
+        match self.field.record_root(layout, value, &mut hasher) {
+            Some(record_root) => {
+                let leaf = hash_leaf(&record_root, &mut hasher);
+                self.outer
+                    .validate_leaf(root_hash, leaf, Padding::Duplicate, &mut hasher)
+            }
+            None => false,
+        }
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Appends the root of `record` as a new leaf and returns its position.
+    pub fn push_record(&mut self, record: &Record) -> usize {
+        self.push(&record.root_with_hasher(H::new()));
+        self.count_leaves() - 1
+    }
+
+    /// Returns a proof of the field called `name` of `record`, stored at
+    /// `position` of this tree, against the root of this tree.
+    pub fn gen_record_proof(
+        &mut self,
+        position: usize,
+        record: &Record,
+        name: &str,
+    ) -> RecordProof {
+        self.try_gen_record_proof(position, record, name)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `gen_record_proof`, but fails instead of panicking with
+    /// `Error::InvalidPosition` if `record` is not stored at `position`,
+    /// or `Error::UnknownField` if it has no field called `name`.
+    pub fn try_gen_record_proof(
+        &mut self,
+        position: usize,
+        record: &Record,
+        name: &str,
+    ) -> Result<RecordProof, Error> {
+        let field = record.try_gen_field_proof(name, H::new())?;
+        if !self.try_verify(position, &record.root_with_hasher(H::new()))? {
+            return Err(Error::InvalidPosition(position));
+        }
+
+        Ok(field.with_outer(self.try_gen_proof(position)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn layout() -> FieldLayout {
+        FieldLayout::new(&["name", "email", "age"])
+    }
+
+    fn record(name: &str) -> Record {
+        let values = vec![
+            name.as_bytes().to_vec(),
+            b"a@example.com".to_vec(),
+            b"42".to_vec(),
+        ];
+        let salts = (0..3).map(|i| vec![i; 16]).collect();
+        Record::new(&layout(), values, salts)
+    }
+
+    #[test]
+    fn field_proofs_validate_against_the_record_root() {
+        let record = record("alice");
+        let root = record.root_with_hasher(Sha256::default());
+        for name in layout().names() {
+            let proof = record.gen_field_proof(name, Sha256::default());
+            let value = record.value(name).unwrap().to_vec();
+            assert!(proof.validate_with_hasher(&layout(), &root, &value, Sha256::default()));
+            assert!(!proof.validate_with_hasher(
+                &layout(),
+                &root,
+                &b"x".to_vec(),
+                Sha256::default()
+            ));
+        }
+    }
+
+    #[test]
+    fn field_proofs_are_bound_to_their_field() {
+        let record = record("alice");
+        let root = record.root_with_hasher(Sha256::default());
+        let proof = record.gen_field_proof("email", Sha256::default());
+
+        let renamed = FieldProof::new(
+            "age".to_string(),
+            proof.salt().to_vec(),
+            proof.inner().clone(),
+        );
+        let value = b"a@example.com".to_vec();
+        assert!(!renamed.validate_with_hasher(&layout(), &root, &value, Sha256::default()));
+        let other = FieldLayout::new(&["email", "name", "age"]);
+        assert!(!proof.validate_with_hasher(&other, &root, &value, Sha256::default()));
+    }
+
+    #[test]
+    fn record_proofs_validate_against_the_tree_root() {
+        let mut tree = MerkleTree::build_with_hasher(&["x"], Sha256::default());
+        let records = [record("alice"), record("bob")];
+        let positions: Vec<usize> = records.iter().map(|r| tree.push_record(r)).collect();
+        assert_eq!(positions, [1, 2]);
+
+        let proof = tree.gen_record_proof(2, &records[1], "name");
+        let root = tree.root_hash().clone();
+        assert!(proof.validate_with_hasher(&layout(), &root, &b"bob".to_vec(), Sha256::default()));
+        assert!(!proof.validate_with_hasher(
+            &layout(),
+            &root,
+            &b"alice".to_vec(),
+            Sha256::default()
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "position does not relate to any leaf")]
+    fn record_proof_of_another_position_panics() {
+        let mut tree = MerkleTree::build_with_hasher(&["x"], Sha256::default());
+        tree.push_record(&record("alice"));
+        tree.gen_record_proof(0, &record("alice"), "name");
+    }
+
+    #[test]
+    #[should_panic(expected = "field name is named twice")]
+    fn duplicate_field_names_panic() {
+        FieldLayout::new(&["name", "name"]);
+    }
+
+    #[test]
+    fn try_forms_fail_on_bad_input() {
+        let empty: [&str; 0] = [];
+        assert_eq!(FieldLayout::try_new(&empty), Err(Error::InvalidSize(0)));
+        assert_eq!(
+            FieldLayout::try_new(&["a", "b", "a"]),
+            Err(Error::DuplicateField("a".to_string()))
+        );
+
+        let values = vec![b"alice".to_vec(), b"a@example.com".to_vec()];
+        let salts = (0..3).map(|i| vec![i; 16]).collect();
+        assert_eq!(
+            Record::try_new(&layout(), values, salts),
+            Err(Error::InvalidSize(2))
+        );
+
+        let record = record("alice");
+        assert_eq!(
+            record.try_gen_field_proof("phone", Sha256::default()),
+            Err(Error::UnknownField("phone".to_string()))
+        );
+
+        let mut tree = MerkleTree::build_with_hasher(&["x"], Sha256::default());
+        let position = tree.push_record(&record);
+        assert_eq!(
+            tree.try_gen_record_proof(0, &record, "name"),
+            Err(Error::InvalidPosition(0))
+        );
+        assert_eq!(
+            tree.try_gen_record_proof(2, &record, "name"),
+            Err(Error::InvalidPosition(2))
+        );
+        assert_eq!(
+            tree.try_gen_record_proof(position, &record, "phone"),
+            Err(Error::UnknownField("phone".to_string()))
+        );
+        assert!(tree.try_gen_record_proof(position, &record, "name").is_ok());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random_salts_differ() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let values = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        let a = Record::with_random_salts(&layout(), values.clone(), &mut rng);
+        let b = Record::with_random_salts(&layout(), values, &mut rng);
+        assert_ne!(
+            a.root_with_hasher(Sha256::default()),
+            b.root_with_hasher(Sha256::default())
+        );
+    }
+}
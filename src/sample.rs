@@ -0,0 +1,166 @@
+use digest::Digest;
+use rand::seq::index;
+use rand::Rng;
+
+use crate::{Error, MerkleTree, Proof};
+
+impl<H: Digest> MerkleTree<H> {
+    /// Picks `k` distinct leaf positions uniformly at random and returns them
+    /// in increasing order together with their inclusion proofs.
+    pub fn sample_leaves<R>(&self, rng: &mut R, k: usize) -> Vec<(usize, Proof)>
+    where
+        R: Rng + ?Sized,
+    {
+        self.try_sample_leaves(rng, k)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `sample_leaves`, but fails instead of panicking with
+    /// `Error::InvalidSize` if `k` is larger than the leaf count, or if the
+    /// tree has deferred changes.
+    pub fn try_sample_leaves<R>(&self, rng: &mut R, k: usize) -> Result<Vec<(usize, Proof)>, Error>
+    where
+        R: Rng + ?Sized,
+    {
+        self.check_clean()?;
+        if k > self.count_leaves() {
+            return Err(Error::InvalidSize(k));
+        }
+
+        let mut positions = index::sample(rng, self.count_leaves(), k).into_vec();
+        positions.sort_unstable();
+        positions
+            .into_iter()
+            .map(|position| Ok((position, self.try_gen_proof(position)?)))
+            .collect()
+    }
+
+    /// Checks `k` nodes picked uniformly at random among all nodes, leaves
+    /// included, the way `validate` checks every one of them.
+    ///
+    /// Catches each corrupt node with probability `k` over the node count,
+    /// for when a full pass is too slow. Fails with `Error::InvalidSize` if
+    /// `k` is larger than the node count.
+    pub fn validate_sample<R>(&self, rng: &mut R, k: usize) -> Result<(), Error>
+    where
+        R: Rng + ?Sized,
+    {
+        self.check_clean()?;
+
+        let stored = self.stored();
+        stored.check_shape()?;
+        let count_nodes = stored.levels.iter().map(|nodes| nodes.len()).sum();
+        if k > count_nodes {
+            return Err(Error::InvalidSize(k));
+        }
+
+        let mut picks = index::sample(rng, count_nodes, k).into_vec();
+        picks.sort_unstable();
+        let mut hasher = H::new();
+        let (mut level, mut first) = (0, 0);
+        for pick in picks {
+            while pick >= first + stored.levels[level].len() {
+                first += stored.levels[level].len();
+                level += 1;
+            }
+            let index = pick - first;
+            let matches = if level == 0 {
+                stored.leaf_matches(index, &mut hasher)
+            } else {
+                stored.node_matches(level, index, &mut hasher)
+            };
+            if !matches {
+                return Err(Error::CorruptNode { level, index });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn values(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i as u8]).collect()
+    }
+
+    #[test]
+    fn sampled_leaves_are_distinct_and_proven() {
+        let values = values(20);
+        let tree = MerkleTree::build_with_hasher(&values, Sha256::default());
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let samples = tree.sample_leaves(&mut rng, 8);
+        assert_eq!(samples.len(), 8);
+        assert!(samples.windows(2).all(|w| w[0].0 < w[1].0));
+        for (position, proof) in &samples {
+            assert_eq!(proof.position(), *position);
+            assert!(proof.validate_with_hasher(
+                tree.root_hash(),
+                &values[*position],
+                Sha256::default()
+            ));
+        }
+        assert_eq!(tree.sample_leaves(&mut rng, 20).len(), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "size does not relate to the tree")]
+    fn sampling_more_leaves_than_the_tree_has_panics() {
+        let tree = MerkleTree::build_with_hasher(&values(3), Sha256::default());
+        tree.sample_leaves(&mut StdRng::seed_from_u64(0), 4);
+    }
+
+    #[test]
+    fn try_sample_leaves_fails_instead_of_panicking() {
+        let mut tree = MerkleTree::build_with_hasher(&values(3), Sha256::default());
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            tree.try_sample_leaves(&mut rng, 4),
+            Err(Error::InvalidSize(4))
+        );
+        assert_eq!(tree.try_sample_leaves(&mut rng, 3).unwrap().len(), 3);
+
+        tree.defer(&[crate::Mutation::Set(1, vec![9])]);
+        assert_eq!(
+            tree.try_sample_leaves(&mut rng, 1),
+            Err(Error::DeferredChanges)
+        );
+    }
+
+    #[test]
+    fn sampling_every_node_finds_corruption() {
+        let mut tree = MerkleTree::build_with_hasher(&values(5), Sha256::default());
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(tree.validate_sample(&mut rng, 11), Ok(()));
+        assert_eq!(tree.validate_sample(&mut rng, 2), Ok(()));
+
+        std::sync::Arc::make_mut(&mut tree.levels)[2][1][0] ^= 1;
+        assert_eq!(
+            tree.validate_sample(&mut rng, 11),
+            Err(Error::CorruptNode { level: 2, index: 1 })
+        );
+    }
+
+    #[test]
+    fn validate_sample_fails_instead_of_panicking() {
+        let mut tree = MerkleTree::build_with_hasher(&values(5), Sha256::default());
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(
+            tree.validate_sample(&mut rng, 12),
+            Err(Error::InvalidSize(12))
+        );
+
+        tree.defer(&[crate::Mutation::Set(1, vec![9])]);
+        assert_eq!(
+            tree.validate_sample(&mut rng, 2),
+            Err(Error::DeferredChanges)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use digest::Digest;
+use subtle::ConstantTimeEq;
+
+use crate::codec::{Reader, Writer};
+use crate::{Error, Hash, MerkleTree};
+
+/// Prefix of every signed message, so a signature over a root cannot be
+/// passed off as one over anything else the key signs.
+const SIGNED_ROOT_DOMAIN: &[u8] = b"merkle-rs signed root v1\0";
+
+/// A signature scheme publishers sign roots with.
+pub trait RootSigner {
+    fn sign_message(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// The verifying half of a `RootSigner`, such as a public key.
+pub trait RootVerifier {
+    fn verify_message(&self, message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// A root, the number of leaves under it and when it was published, signed
+/// by the publisher so readers can tell the root is authentic and not just
+/// consistent with the proofs they receive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignedRoot {
+    root: Hash,
+    count_leaves: usize,
+    timestamp: u64,
+    signature: Vec<u8>,
+}
+
+/// Encodes what a signature covers.
+fn message(root: &[u8], count_leaves: usize, timestamp: u64) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(root);
+    w.usize(count_leaves);
+    w.u64(timestamp);
+    [SIGNED_ROOT_DOMAIN, &w.into_bytes()].concat()
+}
+
+impl SignedRoot {
+    /// Signs `root` of a tree with `count_leaves` leaves, published at
+    /// `timestamp`, in whatever unit the publisher and readers agree on.
+    pub fn sign<S>(root: &[u8], count_leaves: usize, timestamp: u64, signer: &S) -> SignedRoot
+    where
+        S: RootSigner + ?Sized,
+    {
+        SignedRoot {
+            root: root.to_vec(),
+            count_leaves,
+            timestamp,
+            signature: signer.sign_message(&message(root, count_leaves, timestamp)),
+        }
+    }
+
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Returns true if the signature was made by the key behind `verifier`.
+    pub fn verify<V>(&self, verifier: &V) -> bool
+    where
+        V: RootVerifier + ?Sized,
+    {
+        verifier.verify_message(
+            &message(&self.root, self.count_leaves, self.timestamp),
+            &self.signature,
+        )
+    }
+
+    /// Like `verify`, but only for a signature over `root`.
+    pub fn verify_root<V>(&self, root: &[u8], verifier: &V) -> bool
+    where
+        V: RootVerifier + ?Sized,
+    {
+        bool::from(self.root.ct_eq(root)) && self.verify(verifier)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(&self.root);
+        w.usize(self.count_leaves);
+        w.u64(self.timestamp);
+        w.bytes(&self.signature);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignedRoot, Error> {
+        let mut r = Reader::new(bytes);
+        let root = r.bytes()?.to_vec();
+        let count_leaves = r.usize()?;
+        let timestamp = r.u64()?;
+        let signature = r.bytes()?.to_vec();
+        r.finish()?;
+
+        Ok(SignedRoot {
+            root,
+            count_leaves,
+            timestamp,
+            signature,
+        })
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Signs the current root and number of leaves, as published at
+    /// `timestamp`.
+    pub fn sign_root<S>(&self, timestamp: u64, signer: &S) -> SignedRoot
+    where
+        S: RootSigner + ?Sized,
+    {
+        SignedRoot::sign(self.root_hash(), self.count_leaves(), timestamp, signer)
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl RootSigner for ed25519_dalek::SigningKey {
+    fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+        ed25519_dalek::Signer::sign(self, message).to_vec()
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl RootVerifier for ed25519_dalek::VerifyingKey {
+    fn verify_message(&self, message: &[u8], signature: &[u8]) -> bool {
+        match ed25519_dalek::Signature::from_slice(signature) {
+            Ok(signature) => self.verify_strict(message, &signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(feature = "p256")]
+impl RootSigner for p256::ecdsa::SigningKey {
+    fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+        let signature: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::sign(self, message);
+        signature.to_vec()
+    }
+}
+
+#[cfg(feature = "p256")]
+impl RootVerifier for p256::ecdsa::VerifyingKey {
+    fn verify_message(&self, message: &[u8], signature: &[u8]) -> bool {
+        match p256::ecdsa::Signature::from_slice(signature) {
+            Ok(signature) => {
+                p256::ecdsa::signature::Verifier::verify(self, message, &signature).is_ok()
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default())
+    }
+
+    /// Checks that a signature over a tampered field no longer verifies.
+    #[cfg(any(feature = "ed25519-dalek", feature = "p256"))]
+    fn check_tampering<V: RootVerifier>(signed: &SignedRoot, verifier: &V) {
+        assert!(signed.verify(verifier));
+
+        let mut other = signed.clone();
+        other.count_leaves += 1;
+        assert!(!other.verify(verifier));
+        let mut other = signed.clone();
+        other.timestamp += 1;
+        assert!(!other.verify(verifier));
+        let mut other = signed.clone();
+        other.signature[0] ^= 1;
+        assert!(!other.verify(verifier));
+    }
+
+    #[test]
+    fn signed_root_round_trips() {
+        struct Echo;
+        impl RootSigner for Echo {
+            fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+                message.to_vec()
+            }
+        }
+
+        let signed = tree().sign_root(42, &Echo);
+        assert!(signed.signature().starts_with(SIGNED_ROOT_DOMAIN));
+        assert_eq!(SignedRoot::from_bytes(&signed.to_bytes()), Ok(signed));
+    }
+
+    #[cfg(feature = "ed25519-dalek")]
+    #[test]
+    fn ed25519_signatures_verify() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let tree = tree();
+        let signed = tree.sign_root(42, &key);
+
+        check_tampering(&signed, &key.verifying_key());
+        assert!(signed.verify_root(tree.root_hash(), &key.verifying_key()));
+        assert!(!signed.verify_root(&[0; 32], &key.verifying_key()));
+        let other = ed25519_dalek::SigningKey::from_bytes(&[8u8; 32]);
+        assert!(!signed.verify(&other.verifying_key()));
+    }
+
+    #[cfg(feature = "p256")]
+    #[test]
+    fn p256_signatures_verify() {
+        let key = p256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let signed = tree().sign_root(42, &key);
+
+        check_tampering(&signed, key.verifying_key());
+        let other = p256::ecdsa::SigningKey::from_slice(&[8u8; 32]).unwrap();
+        assert!(!signed.verify(other.verifying_key()));
+    }
+}
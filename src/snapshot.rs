@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use digest::Digest;
+
+use crate::consistency::consistency_proof_in;
+use crate::proof::proof_in;
+use crate::{ConsistencyProof, Error, Hash, MerkleTree, Padding, Proof};
+
+/// A read-only view of a tree at the moment it was taken.
+///
+/// Snapshots share the node storage of their tree and are cheap to clone and
+/// send to other threads. The tree copies its storage on the first write after
+/// a snapshot was taken, so readers never observe a partial update and never
+/// block the writer.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    levels: Arc<Vec<Vec<Hash>>>,
+    padding: Padding,
+}
+
+impl Snapshot {
+    pub fn root_hash(&self) -> &Hash {
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns the inclusion proof of the leaf at `position`.
+    pub fn gen_proof(&self, position: usize) -> Proof {
+        self.try_gen_proof(position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `gen_proof`, but fails instead of panicking if `position` does not
+    /// relate to any leaf.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn try_gen_proof(&self, position: usize) -> Result<Proof, Error> {
+        if position >= self.count_leaves() {
+            return Err(Error::InvalidPosition(position));
+        }
+
+        Ok(proof_in(&self.levels, position, self.padding))
+    }
+
+    /// Returns a proof that this snapshot extends its first `old_count_leaves`
+    /// leaves.
+    pub fn consistency_proof(&self, old_count_leaves: usize) -> ConsistencyProof {
+        self.try_consistency_proof(old_count_leaves)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `consistency_proof`, but fails instead of panicking if
+    /// `old_count_leaves` does not relate to any earlier tree.
+    pub fn try_consistency_proof(
+        &self,
+        old_count_leaves: usize,
+    ) -> Result<ConsistencyProof, Error> {
+        if old_count_leaves == 0 || old_count_leaves > self.count_leaves() {
+            return Err(Error::InvalidSize(old_count_leaves));
+        }
+
+        Ok(consistency_proof_in(
+            &self.levels,
+            old_count_leaves,
+            self.padding,
+        ))
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns a consistent read-only view of the current tree.
+    pub fn snapshot(&self) -> Snapshot {
+        self.try_snapshot().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `snapshot`, but fails instead of panicking if the tree has
+    /// deferred changes.
+    pub fn try_snapshot(&self) -> Result<Snapshot, Error> {
+        self.check_clean()?;
+
+        Ok(Snapshot {
+            levels: Arc::clone(&self.levels),
+            padding: self.padding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn try_snapshot_fails_on_deferred_changes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.defer(&[Mutation::Push("c")]);
+        assert!(matches!(tree.try_snapshot(), Err(Error::DeferredChanges)));
+
+        let root = tree.root().clone();
+        assert_eq!(tree.try_snapshot().unwrap().root_hash(), &root);
+    }
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_writes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let snapshot = tree.snapshot();
+        let root = tree.root_hash().clone();
+        let proof = tree.gen_proof(2);
+
+        tree.apply(&[Mutation::Set(2, "d"), Mutation::Push("e")]);
+        assert_ne!(tree.root_hash(), &root);
+        assert_eq!(snapshot.root_hash(), &root);
+        assert_eq!(snapshot.count_leaves(), 3);
+        assert_eq!(snapshot.gen_proof(2), proof);
+        assert!(proof.validate_with_hasher(snapshot.root_hash(), &"c", Sha256::default()));
+    }
+
+    #[test]
+    fn snapshot_consistency_proof_matches_tree() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        let snapshot = tree.snapshot();
+        assert_eq!(snapshot.consistency_proof(2), tree.consistency_proof(2));
+        assert!(matches!(
+            snapshot.try_consistency_proof(6),
+            Err(Error::InvalidSize(6))
+        ));
+        assert!(matches!(
+            snapshot.try_gen_proof(5),
+            Err(Error::InvalidPosition(5))
+        ));
+    }
+}
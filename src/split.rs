@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use digest::Digest;
+
+use crate::{
+    build_levels, build_levels_reusing, count_at_level, height, padding, Error, MerkleTree,
+};
+
+impl<H: Digest + Clone> MerkleTree<H> {
+    /// Splits the tree into one over the leaves before `index` and one over the
+    /// leaves from `index` on.
+    ///
+    /// The prefix keeps all its complete subtrees. The suffix keeps the complete
+    /// subtrees that stay aligned once shifted down by `index`.
+    pub fn split_at(self, index: usize) -> (MerkleTree<H>, MerkleTree<H>) {
+        self.try_split_at(index).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `split_at`, but fails instead of panicking with
+    /// `Error::InvalidPosition` if `index` does not leave leaves on both
+    /// sides, or with `Error::PaddingRejected` if the padding rejects the
+    /// count of either side.
+    pub fn try_split_at(mut self, index: usize) -> Result<(MerkleTree<H>, MerkleTree<H>), Error> {
+        if index == 0 || index >= self.count_leaves() {
+            return Err(Error::InvalidPosition(index));
+        }
+        padding::check_count(self.padding, index)?;
+        padding::check_count(self.padding, self.count_leaves() - index)?;
+        self.flush();
+
+        let count_leaves = self.count_leaves();
+        let levels = Arc::make_mut(&mut self.levels);
+        let suffix_leaves = levels[0].split_off(index);
+        let prefix_leaves = std::mem::take(&mut levels[0]);
+
+        let prefix =
+            build_levels_reusing(prefix_leaves, self.padding, &mut self.hasher, |level, i| {
+                let width = 1 << level;
+                if (i + 1) * width <= index {
+                    Some(std::mem::take(&mut levels[level][i]))
+                } else {
+                    None
+                }
+            });
+
+        let suffix =
+            build_levels_reusing(suffix_leaves, self.padding, &mut self.hasher, |level, i| {
+                let width = 1 << level;
+                let start = index + i * width;
+                if index.is_multiple_of(width) && start + width <= count_leaves {
+                    Some(std::mem::take(&mut levels[level][start / width]))
+                } else {
+                    None
+                }
+            });
+
+        let mut prefix = MerkleTree::from_levels(prefix, self.padding, self.hasher.clone());
+        let mut suffix = MerkleTree::from_levels(suffix, self.padding, self.hasher.clone());
+        if !self.metadata.is_empty() {
+            suffix.metadata = self.metadata.split_off(index);
+            prefix.metadata = std::mem::take(&mut self.metadata);
+        }
+        if self.retains_values {
+            suffix.values = self.values.split_off(index);
+            prefix.values = std::mem::take(&mut self.values);
+            prefix.retains_values = true;
+            suffix.retains_values = true;
+        }
+        if self.index.is_some() {
+            prefix.set_indexed(true);
+            suffix.set_indexed(true);
+        }
+        #[cfg(feature = "zeroize")]
+        if self.wipe.is_some() {
+            prefix.set_zeroize_on_drop(true);
+            suffix.set_zeroize_on_drop(true);
+        }
+
+        Ok((prefix, suffix))
+    }
+
+    /// Returns a tree over the leaves below the node at `index` of `level`,
+    /// where level 0 holds the leaves.
+    ///
+    /// The nodes are copied, not hashed again. The root of the new tree is the
+    /// hash of that node, except for nodes whose leaves do not fill them: a
+    /// node on the right edge that was paired with itself on the way up has a
+    /// shorter tree of its own, rooted at the node below it where its leaves
+    /// meet. A single leaf is hashed into a root of its own.
+    pub fn subtree(&self, level: usize, index: usize) -> MerkleTree<H> {
+        self.try_subtree(level, index)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `subtree`, but fails with `Error::InvalidHeight` or
+    /// `Error::InvalidPosition` instead of panicking if `level` and `index`
+    /// do not relate to any node, or if the tree has deferred changes.
+    pub fn try_subtree(&self, level: usize, index: usize) -> Result<MerkleTree<H>, Error> {
+        if level >= self.levels.len() {
+            return Err(Error::InvalidHeight(level));
+        }
+        if index >= self.levels[level].len() {
+            return Err(Error::InvalidPosition(index));
+        }
+        self.check_clean()?;
+
+        let mut hasher = self.hasher.clone();
+        let first = index << level;
+        let count_leaves = ((index + 1) << level).min(self.count_leaves()) - first;
+        let levels = if level == 0 {
+            build_levels(
+                vec![self.levels[0][index].clone()],
+                self.padding,
+                &mut hasher,
+            )
+        } else {
+            (0..=height(count_leaves))
+                .map(|k| {
+                    let start = index << (level - k);
+                    let end = (start + count_at_level(count_leaves, k)).min(self.levels[k].len());
+                    self.levels[k][start..end].to_vec()
+                })
+                .collect()
+        };
+
+        let mut tree = MerkleTree::from_levels(levels, self.padding, hasher);
+        let range = first..first + count_leaves;
+        if !self.metadata.is_empty() {
+            tree.metadata = self.metadata[range.clone()].to_vec();
+        }
+        if self.retains_values {
+            tree.retains_values = true;
+            tree.values = self.values[range].to_vec();
+        }
+        tree.set_indexed(self.index.is_some());
+        #[cfg(feature = "zeroize")]
+        tree.set_zeroize_on_drop(self.wipe.is_some());
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{Error, Padding};
+
+    #[test]
+    fn try_subtree_fails_on_missing_node() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        assert!(matches!(
+            tree.try_subtree(3, 0),
+            Err(Error::InvalidHeight(3))
+        ));
+        assert!(matches!(
+            tree.try_subtree(1, 2),
+            Err(Error::InvalidPosition(2))
+        ));
+        assert_eq!(
+            tree.try_subtree(2, 0).unwrap().root_hash(),
+            tree.root_hash()
+        );
+    }
+
+    fn values(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i as u8]).collect()
+    }
+
+    #[test]
+    fn split_at_matches_builds_of_both_halves() {
+        let all = values(11);
+        for index in 1..all.len() {
+            let tree = MerkleTree::build_with_hasher(&all, Sha256::default());
+            let (prefix, suffix) = tree.split_at(index);
+
+            let expected = MerkleTree::build_with_hasher(&all[..index], Sha256::default());
+            assert_eq!(
+                prefix.root_hash(),
+                expected.root_hash(),
+                "prefix of {}",
+                index
+            );
+            let expected = MerkleTree::build_with_hasher(&all[index..], Sha256::default());
+            assert_eq!(
+                suffix.root_hash(),
+                expected.root_hash(),
+                "suffix of {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn split_then_merge_round_trips() {
+        let tree = MerkleTree::build_with_hasher(&values(6), Sha256::default());
+        let root = tree.root_hash().clone();
+        let (prefix, suffix) = tree.split_at(4);
+        assert_eq!(MerkleTree::merge(prefix, suffix).root_hash(), &root);
+    }
+
+    #[test]
+    #[should_panic(expected = "position does not relate to any leaf")]
+    fn split_at_rejects_empty_side() {
+        let tree = MerkleTree::build_with_hasher(&values(3), Sha256::default());
+        tree.split_at(3);
+    }
+
+    #[test]
+    fn try_split_at_fails_instead_of_panicking() {
+        for index in [0, 3] {
+            let tree = MerkleTree::build_with_hasher(&values(3), Sha256::default());
+            assert_eq!(
+                tree.try_split_at(index).map(|_| ()),
+                Err(Error::InvalidPosition(index))
+            );
+        }
+
+        let rejecting = || {
+            let mut tree = MerkleTree::build_with_hasher(&values(4), Sha256::default());
+            tree.set_padding(Padding::Reject);
+            tree
+        };
+        assert_eq!(
+            rejecting().try_split_at(1).map(|_| ()),
+            Err(Error::PaddingRejected(1))
+        );
+        assert!(rejecting().try_split_at(2).is_ok());
+    }
+
+    #[test]
+    fn subtree_matches_build_over_its_leaves() {
+        let all = values(6);
+        let tree = MerkleTree::build_retaining_values(&all, Sha256::default());
+        for (level, index, range) in [(0, 5, 5..6), (1, 1, 2..4), (2, 0, 0..4), (2, 1, 4..6)] {
+            let subtree = tree.subtree(level, index);
+            let expected = MerkleTree::build_with_hasher(&all[range.clone()], Sha256::default());
+            assert_eq!(
+                subtree.root_hash(),
+                expected.root_hash(),
+                "node {} at level {}",
+                index,
+                level
+            );
+            assert_eq!(subtree.value(0), Some(&all[range.start][..]));
+        }
+        assert_eq!(tree.subtree(1, 1).root_hash(), &tree.levels[1][1]);
+    }
+}
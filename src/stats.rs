@@ -0,0 +1,106 @@
+use std::mem::size_of;
+
+use digest::Digest;
+
+use crate::metadata::StoredMetadata;
+use crate::{Error, Hash, MerkleTree};
+
+/// Shape and memory use of a tree, as reported by `MerkleTree::stats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of levels above the leaves.
+    pub height: usize,
+    pub count_leaves: usize,
+    /// Number of stored nodes, leaves and root included.
+    pub count_nodes: usize,
+    /// Number of nodes paired with themselves for lack of a right sibling.
+    pub count_duplicates: usize,
+    /// Total length of the retained leaf values.
+    pub value_bytes: usize,
+    /// Approximate heap memory held by the nodes, metadata, values and leaf
+    /// index, not counting retained history.
+    pub heap_bytes: usize,
+}
+
+fn hashes_heap_bytes(hashes: &[Hash]) -> usize {
+    hashes.iter().map(|h| h.capacity()).sum()
+}
+
+impl<H: Digest> MerkleTree<H> {
+    pub fn stats(&self) -> Stats {
+        self.try_stats().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `stats`, but fails instead of panicking if the tree has deferred
+    /// changes.
+    pub fn try_stats(&self) -> Result<Stats, Error> {
+        self.check_clean()?;
+
+        let levels = &self.levels;
+        let count_duplicates = levels[..levels.len() - 1]
+            .iter()
+            .filter(|nodes| nodes.len() % 2 == 1)
+            .count();
+        let value_bytes = self.values.iter().flatten().map(|v| v.len()).sum();
+
+        let mut heap_bytes = levels.capacity() * size_of::<Vec<Hash>>();
+        for nodes in levels.iter() {
+            heap_bytes += nodes.capacity() * size_of::<Hash>() + hashes_heap_bytes(nodes);
+        }
+        heap_bytes += self.metadata.capacity() * size_of::<Option<StoredMetadata>>();
+        for stored in self.metadata.iter().flatten() {
+            heap_bytes += stored.plain.capacity() + stored.leaf.metadata.source.capacity();
+        }
+        heap_bytes += self.values.capacity() * size_of::<Option<Vec<u8>>>();
+        heap_bytes += self
+            .values
+            .iter()
+            .flatten()
+            .map(|v| v.capacity())
+            .sum::<usize>();
+        if let Some(index) = &self.index {
+            heap_bytes += index.heap_bytes();
+        }
+
+        Ok(Stats {
+            height: levels.len() - 1,
+            count_leaves: self.count_leaves(),
+            count_nodes: levels.iter().map(|nodes| nodes.len()).sum(),
+            count_duplicates,
+            value_bytes,
+            heap_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn try_stats_fails_on_deferred_changes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        tree.defer(&[Mutation::Push("d")]);
+        assert!(matches!(tree.try_stats(), Err(Error::DeferredChanges)));
+
+        tree.flush();
+        assert_eq!(tree.try_stats().unwrap().count_leaves, 4);
+    }
+
+    #[test]
+    fn stats_describe_the_shape() {
+        let tree =
+            MerkleTree::build_retaining_values(&["a", "b", "c", "d", "ee"], Sha256::default());
+        let stats = tree.stats();
+
+        assert_eq!(stats.height, 3);
+        assert_eq!(stats.count_leaves, 5);
+        assert_eq!(stats.count_nodes, 5 + 3 + 2 + 1);
+        assert_eq!(stats.count_duplicates, 2);
+        assert_eq!(stats.value_bytes, 6);
+        assert!(stats.heap_bytes >= stats.count_nodes * 32 + stats.value_bytes);
+    }
+}
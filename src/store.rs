@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use digest::Digest;
+use tokio::sync::RwLock;
+
+use crate::{
+    build_levels, count_at_level, hashes_equal, height, padding, Error, Hash, MerkleTree, Padding,
+    Proof, Sibling,
+};
+
+/// Node storage reached over the network or another slow medium, addressed
+/// by `(level, index)` with the leaves at level 0 as in `subtree`.
+///
+/// The batch methods fall back to one call per node. Stores that can fetch
+/// or write many nodes in a round trip should override them.
+pub trait AsyncNodeStore: Sync {
+    /// Failures of the store itself. Nodes that should be stored but are
+    /// missing are reported as `Error::CorruptNode`.
+    type Error: From<Error> + Send;
+
+    fn get(
+        &self,
+        level: usize,
+        index: usize,
+    ) -> impl Future<Output = Result<Option<Hash>, Self::Error>> + Send;
+
+    fn put(
+        &self,
+        level: usize,
+        index: usize,
+        hash: Hash,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Returns the node at each of `keys`, in the same order.
+    fn get_batch(
+        &self,
+        keys: &[(usize, usize)],
+    ) -> impl Future<Output = Result<Vec<Option<Hash>>, Self::Error>> + Send {
+        async move {
+            let mut nodes = Vec::with_capacity(keys.len());
+            for &(level, index) in keys {
+                nodes.push(self.get(level, index).await?);
+            }
+            Ok(nodes)
+        }
+    }
+
+    fn put_batch(
+        &self,
+        nodes: Vec<((usize, usize), Hash)>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            for ((level, index), hash) in nodes {
+                self.put(level, index, hash).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// An `AsyncNodeStore` in memory, mostly for tests of code written against
+/// remote stores.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    nodes: RwLock<HashMap<(usize, usize), Hash>>,
+}
+
+impl AsyncNodeStore for MemoryStore {
+    type Error = Error;
+
+    async fn get(&self, level: usize, index: usize) -> Result<Option<Hash>, Error> {
+        Ok(self.nodes.read().await.get(&(level, index)).cloned())
+    }
+
+    async fn put(&self, level: usize, index: usize, hash: Hash) -> Result<(), Error> {
+        self.nodes.write().await.insert((level, index), hash);
+        Ok(())
+    }
+
+    async fn get_batch(&self, keys: &[(usize, usize)]) -> Result<Vec<Option<Hash>>, Error> {
+        let nodes = self.nodes.read().await;
+        Ok(keys.iter().map(|key| nodes.get(key).cloned()).collect())
+    }
+
+    async fn put_batch(&self, batch: Vec<((usize, usize), Hash)>) -> Result<(), Error> {
+        self.nodes.write().await.extend(batch);
+        Ok(())
+    }
+}
+
+/// Unwraps the nodes fetched for `keys`, failing at the first missing one.
+fn expect_nodes<E>(keys: &[(usize, usize)], nodes: Vec<Option<Hash>>) -> Result<Vec<Hash>, E>
+where
+    E: From<Error>,
+{
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_node_reads(keys.len());
+
+    keys.iter()
+        .zip(nodes)
+        .map(|(&(level, index), node)| node.ok_or(Error::CorruptNode { level, index }.into()))
+        .collect()
+}
+
+/// Returns the inclusion proof of the leaf at `position` of the tree of
+/// `count_leaves` leaves kept in `store`, fetching its siblings in one batch.
+pub async fn gen_proof_async<S>(
+    store: &S,
+    count_leaves: usize,
+    position: usize,
+    padding: Padding,
+) -> Result<Proof, S::Error>
+where
+    S: AsyncNodeStore,
+{
+    if position >= count_leaves {
+        return Err(Error::InvalidPosition(position).into());
+    }
+
+    let mut keys = Vec::new();
+    let mut j = position;
+    for level in 0..height(count_leaves) {
+        if j % 2 == 1 {
+            keys.push((level, j - 1));
+        } else if j + 1 < count_at_level(count_leaves, level) {
+            keys.push((level, j + 1));
+        }
+        j >>= 1;
+    }
+    let mut siblings = expect_nodes::<S::Error>(&keys, store.get_batch(&keys).await?)?.into_iter();
+
+    let mut path = Vec::with_capacity(height(count_leaves));
+    let mut j = position;
+    for level in 0..height(count_leaves) {
+        let sibling = if j % 2 == 1 {
+            siblings.next().map(Sibling::Left)
+        } else if j + 1 < count_at_level(count_leaves, level) {
+            siblings.next().map(Sibling::Right)
+        } else {
+            match padding {
+                Padding::Duplicate => Some(Sibling::Duplicate),
+                Padding::Empty => Some(Sibling::Empty),
+                Padding::Constant(sentinel) => Some(Sibling::Padding(sentinel.as_bytes().to_vec())),
+                Padding::Reject => return Err(Error::PaddingRejected(count_leaves).into()),
+                Padding::Promote => Some(Sibling::Promoted),
+            }
+        };
+        path.extend(sibling);
+        j >>= 1;
+    }
+
+    Ok(Proof::new(position, count_leaves, path))
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Writes every node of the tree to `store` in one batch.
+    pub async fn save_async<S>(&self, store: &S) -> Result<(), S::Error>
+    where
+        S: AsyncNodeStore,
+    {
+        self.check_clean()?;
+
+        let nodes = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, nodes)| {
+                nodes
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, hash)| ((level, index), hash.clone()))
+            })
+            .collect();
+        store.put_batch(nodes).await
+    }
+
+    /// Rebuilds the tree of `count_leaves` leaves kept in `store` from its
+    /// leaves, failing if the result does not have the stored root.
+    pub async fn load_async<S>(
+        store: &S,
+        count_leaves: usize,
+        padding: Padding,
+        mut hasher: H,
+    ) -> Result<MerkleTree<H>, S::Error>
+    where
+        S: AsyncNodeStore,
+    {
+        if count_leaves == 0 {
+            return Err(Error::InvalidSize(count_leaves).into());
+        }
+        padding::check_count(padding, count_leaves)?;
+
+        let root_key = (height(count_leaves), 0);
+        let mut keys: Vec<(usize, usize)> = (0..count_leaves).map(|i| (0, i)).collect();
+        keys.push(root_key);
+        let mut leaves = expect_nodes::<S::Error>(&keys, store.get_batch(&keys).await?)?;
+        let root = leaves.pop().unwrap();
+
+        let levels = build_levels(leaves, padding, &mut hasher);
+        if !hashes_equal(&levels[levels.len() - 1][0], &root) {
+            return Err(Error::CorruptNode {
+                level: root_key.0,
+                index: root_key.1,
+            }
+            .into());
+        }
+
+        Ok(MerkleTree::from_levels(levels, padding, hasher))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::test_util::block_on;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default())
+    }
+
+    #[test]
+    fn saved_tree_loads_back() {
+        let tree = tree();
+        let store = MemoryStore::default();
+        block_on(tree.save_async(&store)).unwrap();
+
+        let loaded = block_on(MerkleTree::load_async(
+            &store,
+            5,
+            Padding::Duplicate,
+            Sha256::default(),
+        ))
+        .unwrap();
+        assert_eq!(loaded.root_hash(), tree.root_hash());
+    }
+
+    #[test]
+    fn async_proofs_match_the_tree() {
+        let tree = tree();
+        let store = MemoryStore::default();
+        block_on(tree.save_async(&store)).unwrap();
+
+        for position in 0..5 {
+            let proof = block_on(gen_proof_async(&store, 5, position, Padding::Duplicate)).unwrap();
+            assert_eq!(proof, tree.gen_proof(position));
+        }
+        assert_eq!(
+            block_on(gen_proof_async(&store, 5, 5, Padding::Duplicate)),
+            Err(Error::InvalidPosition(5))
+        );
+    }
+
+    #[test]
+    fn missing_and_corrupt_nodes_fail_to_load() {
+        let tree = tree();
+        let store = MemoryStore::default();
+        block_on(tree.save_async(&store)).unwrap();
+
+        let load = |count_leaves| {
+            block_on(MerkleTree::load_async(
+                &store,
+                count_leaves,
+                Padding::Duplicate,
+                Sha256::default(),
+            ))
+            .map(|tree| tree.root_hash().clone())
+        };
+        assert_eq!(load(6), Err(Error::CorruptNode { level: 0, index: 5 }));
+        assert_eq!(load(0), Err(Error::InvalidSize(0)));
+
+        block_on(store.put(0, 2, vec![0; 32])).unwrap();
+        assert_eq!(load(5), Err(Error::CorruptNode { level: 3, index: 0 }));
+    }
+
+    #[test]
+    fn tree_with_deferred_changes_is_not_saved() {
+        let mut tree = tree();
+        tree.defer(&[crate::Mutation::Set(0, "x")]);
+        let store = MemoryStore::default();
+        assert_eq!(
+            block_on(tree.save_async(&store)),
+            Err(Error::DeferredChanges)
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::ops::Range;
+
+use digest::Digest;
+
+use crate::{hashes_equal, height, Error, Hash, MerkleTree, Mutation};
+
+/// The remote side of an anti-entropy exchange, usually a replica reached
+/// over the network.
+///
+/// Nodes are addressed by `(level, index)` with the leaves at level 0, as in
+/// `subtree`. Each method is one round trip.
+pub trait Peer {
+    /// Failures of the exchange itself. A remote tree that does not end up
+    /// with the root it announced is reported as `Error::RootMismatch`.
+    type Error: From<Error>;
+
+    fn count_leaves(&mut self) -> Result<usize, Self::Error>;
+
+    /// Returns the remote node at each of `keys`, or `None` where the remote
+    /// tree has no such node.
+    fn nodes(&mut self, keys: &[(usize, usize)]) -> Result<Vec<Option<Hash>>, Self::Error>;
+
+    /// Returns the values of the remote leaves at `positions`.
+    fn values(&mut self, positions: &[usize]) -> Result<Vec<Vec<u8>>, Self::Error>;
+}
+
+/// Returns the leaves covered by the node at `index` of `level` in a tree of
+/// `count_leaves` leaves.
+fn covered(count_leaves: usize, level: usize, index: usize) -> Range<usize> {
+    let start = index << level;
+    start..((index + 1) << level).min(count_leaves)
+}
+
+/// Sorts `ranges` and merges adjacent ones.
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Returns the ranges of leaves at which this tree and the tree of `peer`
+    /// differ, in increasing order, like `diff`.
+    ///
+    /// Walks both trees down from the root one level per round trip, asking
+    /// only for the children of nodes found to differ.
+    pub fn diff_peer<P>(&self, peer: &mut P) -> Result<Vec<Range<usize>>, P::Error>
+    where
+        P: Peer,
+    {
+        self.check_clean()?;
+
+        let local = self.count_leaves();
+        let remote = peer.count_leaves()?;
+        if remote == 0 {
+            return Err(Error::InvalidSize(remote).into());
+        }
+        let (shorter, longer) = (local.min(remote), local.max(remote));
+        let top = (self.levels.len() - 1).max(height(remote));
+
+        let mut differing = Vec::new();
+        let mut frontier = vec![(top, 0)];
+        while !frontier.is_empty() {
+            let mut keys = Vec::new();
+            for (level, index) in frontier.drain(..) {
+                let start = index << level;
+                if start >= longer {
+                    continue;
+                }
+                if start >= shorter {
+                    differing.push(covered(longer, level, index));
+                } else {
+                    keys.push((level, index));
+                }
+            }
+            if keys.is_empty() {
+                break;
+            }
+
+            let nodes = peer.nodes(&keys)?;
+            for (&(level, index), node) in keys.iter().zip(nodes) {
+                let same = match (self.levels.get(level).and_then(|l| l.get(index)), node) {
+                    (Some(ours), Some(theirs)) => {
+                        covered(local, level, index) == covered(remote, level, index)
+                            && hashes_equal(ours, &theirs)
+                    }
+                    _ => false,
+                };
+                if same {
+                    continue;
+                }
+                if level == 0 {
+                    differing.push(index..index + 1);
+                } else {
+                    frontier.push((level - 1, 2 * index));
+                    frontier.push((level - 1, 2 * index + 1));
+                }
+            }
+        }
+
+        Ok(merge_ranges(differing))
+    }
+
+    /// Makes this tree a copy of the tree of `peer`, fetching only the values
+    /// of the leaves that differ, and returns how many were fetched.
+    ///
+    /// Fails with `Error::RootMismatch` if the result does not have the root
+    /// `peer` reports, for instance because the remote leaves carry metadata.
+    /// The changes are then reverted, as `apply_patch` does, and the tree is
+    /// left unchanged on any other failure too.
+    pub fn sync_from<P>(&mut self, peer: &mut P) -> Result<usize, P::Error>
+    where
+        P: Peer,
+    {
+        let ranges = self.diff_peer(peer)?;
+        let local = self.count_leaves();
+        let remote = peer.count_leaves()?;
+
+        let positions: Vec<usize> = ranges
+            .into_iter()
+            .flatten()
+            .take_while(|&i| i < remote)
+            .collect();
+        let values = peer.values(&positions)?;
+        if values.len() != positions.len() {
+            return Err(Error::InvalidEncoding.into());
+        }
+        let expected = peer
+            .nodes(&[(height(remote), 0)])?
+            .pop()
+            .flatten()
+            .unwrap_or_default();
+
+        let mut mutations: Vec<Mutation<Vec<u8>>> = positions
+            .iter()
+            .zip(values)
+            .map(|(&i, value)| {
+                if i < local {
+                    Mutation::Set(i, value)
+                } else {
+                    Mutation::Push(value)
+                }
+            })
+            .collect();
+        mutations.extend((remote..local).rev().map(Mutation::Remove));
+        let mutations = self.hash_mutations(&mutations);
+        self.check_positions(&mutations)?;
+
+        let inverse = self.inverse_mutations(&mutations);
+        self.apply_leaves(mutations);
+
+        let actual = self.root_hash().clone();
+        if !hashes_equal(&actual, &expected) {
+            self.change_leaves(inverse);
+            self.flush();
+            self.forget_last_undo();
+            return Err(Error::RootMismatch { expected, actual }.into());
+        }
+
+        Ok(positions.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    /// A peer answering from a tree in memory, optionally lying about the
+    /// values it sends.
+    struct Local {
+        tree: MerkleTree<Sha256>,
+        corrupt: bool,
+    }
+
+    impl Peer for Local {
+        type Error = Error;
+
+        fn count_leaves(&mut self) -> Result<usize, Error> {
+            Ok(self.tree.count_leaves())
+        }
+
+        fn nodes(&mut self, keys: &[(usize, usize)]) -> Result<Vec<Option<Hash>>, Error> {
+            let levels = &self.tree.levels;
+            Ok(keys
+                .iter()
+                .map(|&(level, index)| levels.get(level).and_then(|l| l.get(index)).cloned())
+                .collect())
+        }
+
+        fn values(&mut self, positions: &[usize]) -> Result<Vec<Vec<u8>>, Error> {
+            Ok(positions
+                .iter()
+                .map(|&i| match self.tree.value(i) {
+                    Some(_) if self.corrupt => b"forged".to_vec(),
+                    value => value.unwrap_or_default().to_vec(),
+                })
+                .collect())
+        }
+    }
+
+    fn peer(values: &[&str], corrupt: bool) -> Local {
+        Local {
+            tree: MerkleTree::build_retaining_values(values, Sha256::default()),
+            corrupt,
+        }
+    }
+
+    #[test]
+    fn sync_from_copies_the_peer() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d", "e"], Sha256::default());
+        let mut peer = peer(&["a", "x", "c"], false);
+
+        assert_eq!(tree.diff_peer(&mut peer), Ok(vec![1..2, 3..5]));
+        assert_eq!(tree.sync_from(&mut peer), Ok(1));
+        assert_eq!(tree.root_hash(), peer.tree.root_hash());
+    }
+
+    #[test]
+    fn failed_sync_leaves_tree_unchanged() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let root = tree.root_hash().clone();
+        let mut peer = peer(&["a", "x", "c", "d"], true);
+
+        let result = tree.sync_from(&mut peer);
+        assert!(matches!(result, Err(Error::RootMismatch { .. })));
+        assert_eq!(tree.root_hash(), &root);
+        assert_eq!(tree.count_leaves(), 3);
+    }
+
+    #[test]
+    fn diff_peer_fails_on_deferred_changes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.defer(&[crate::Mutation::Set(0, "x")]);
+        let mut peer = peer(&["a", "b"], false);
+        assert_eq!(tree.diff_peer(&mut peer), Err(Error::DeferredChanges));
+    }
+}
@@ -0,0 +1,193 @@
+use digest::Digest;
+
+use crate::{hashes_equal, padding, Appender, AsBytes, ConsistencyProof, Error, Hash, Padding};
+
+/// Follows an append-only log from a root it trusts, checking every batch of
+/// new entries before moving on, as clients tailing a transparency log do.
+///
+/// Only the latest verified root and size are kept. Each batch comes with a
+/// consistency proof from that size, whose hashes on the old right edge are
+/// checked against the trusted root and then extended with the entries, so
+/// both the entries and the new root are verified without fetching anything
+/// else.
+#[derive(Clone, Debug)]
+pub struct TailVerifier<H> {
+    root: Hash,
+    count_leaves: usize,
+    padding: Padding,
+    hasher: H,
+}
+
+impl<H: Digest + Clone> TailVerifier<H> {
+    /// Starts from `root` of a log of `count_leaves` leaves hashed with
+    /// `padding`, such as the root of a verified signed checkpoint.
+    pub fn new(root: &[u8], count_leaves: usize, padding: Padding, hasher: H) -> TailVerifier<H> {
+        TailVerifier::try_new(root, count_leaves, padding, hasher)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but fails with `Error::InvalidSize` instead of panicking
+    /// if `count_leaves` is zero.
+    pub fn try_new(
+        root: &[u8],
+        count_leaves: usize,
+        padding: Padding,
+        hasher: H,
+    ) -> Result<TailVerifier<H>, Error> {
+        if count_leaves == 0 {
+            return Err(Error::InvalidSize(count_leaves));
+        }
+
+        Ok(TailVerifier {
+            root: root.to_vec(),
+            count_leaves,
+            padding,
+            hasher,
+        })
+    }
+
+    /// The last verified root.
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    /// Checks that appending `entries` to the verified log gives `new_root`,
+    /// using `proof` from the verified size to the new one, and advances to
+    /// it.
+    ///
+    /// Fails without advancing with `Error::InvalidSize` if the proof does
+    /// not start at the verified size or does not end after `entries`,
+    /// `Error::InvalidEncoding` if its hashes do not fit those sizes,
+    /// `Error::PaddingRejected` if the padding forbids either size, and
+    /// `Error::RootMismatch` if it does not lead back to the verified root or
+    /// the entries do not lead to `new_root`.
+    pub fn advance<T>(
+        &mut self,
+        entries: &[T],
+        new_root: &[u8],
+        proof: &ConsistencyProof,
+    ) -> Result<(), Error>
+    where
+        T: AsBytes,
+    {
+        if proof.old_count_leaves() != self.count_leaves {
+            return Err(Error::InvalidSize(proof.old_count_leaves()));
+        }
+        if proof.count_leaves() != self.count_leaves + entries.len() {
+            return Err(Error::InvalidSize(proof.count_leaves()));
+        }
+        padding::check_count(self.padding, self.count_leaves)?;
+        padding::check_count(self.padding, proof.count_leaves())?;
+
+        let frontier = proof.old_frontier().ok_or(Error::InvalidEncoding)?;
+        let mut appender = Appender::from_frontier(
+            self.hasher.clone(),
+            frontier,
+            self.count_leaves,
+            self.padding,
+        );
+        let old_root = appender.root().unwrap();
+        if !hashes_equal(&old_root, &self.root) {
+            return Err(Error::RootMismatch {
+                expected: self.root.clone(),
+                actual: old_root,
+            });
+        }
+
+        for entry in entries {
+            appender.append(entry);
+        }
+        let actual = appender.root().unwrap();
+        if !hashes_equal(&actual, new_root) {
+            return Err(Error::RootMismatch {
+                expected: new_root.to_vec(),
+                actual,
+            });
+        }
+
+        self.root = actual;
+        self.count_leaves = appender.count_leaves();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::MerkleTree;
+
+    const ENTRIES: [&str; 7] = ["a", "b", "c", "d", "e", "f", "g"];
+
+    fn tree(count: usize) -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&ENTRIES[..count], Sha256::default())
+    }
+
+    #[test]
+    fn advances_over_every_batch() {
+        let mut verifier = TailVerifier::new(
+            tree(1).root_hash(),
+            1,
+            Padding::Duplicate,
+            Sha256::default(),
+        );
+        for (from, to) in [(1, 2), (2, 5), (5, 5), (5, 7)] {
+            let new = tree(to);
+            verifier
+                .advance(
+                    &ENTRIES[from..to],
+                    new.root_hash(),
+                    &new.consistency_proof(from),
+                )
+                .unwrap();
+            assert_eq!(
+                (verifier.root(), verifier.count_leaves()),
+                (new.root_hash(), to)
+            );
+        }
+    }
+
+    #[test]
+    fn bad_batches_do_not_advance() {
+        let old = tree(3);
+        let new = tree(5);
+        let proof = new.consistency_proof(3);
+        let mut verifier =
+            TailVerifier::new(old.root_hash(), 3, Padding::Duplicate, Sha256::default());
+
+        assert_eq!(
+            verifier.advance(&ENTRIES[3..4], new.root_hash(), &proof),
+            Err(Error::InvalidSize(5))
+        );
+        assert!(matches!(
+            verifier.advance(&["x", "e"], new.root_hash(), &proof),
+            Err(Error::RootMismatch { .. })
+        ));
+        assert_eq!(
+            verifier.advance(&ENTRIES[2..5], new.root_hash(), &new.consistency_proof(2)),
+            Err(Error::InvalidSize(2))
+        );
+
+        let mut forged = TailVerifier::new(&[0; 32], 3, Padding::Duplicate, Sha256::default());
+        assert!(matches!(
+            forged.advance(&ENTRIES[3..5], new.root_hash(), &proof),
+            Err(Error::RootMismatch { .. })
+        ));
+        assert_eq!(
+            (verifier.root(), verifier.count_leaves()),
+            (old.root_hash(), 3)
+        );
+    }
+    #[test]
+    fn empty_log_is_refused() {
+        assert!(matches!(
+            TailVerifier::try_new(&[0; 32], 0, Padding::Duplicate, Sha256::default()),
+            Err(Error::InvalidSize(0))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+//! Helpers shared by the unit tests of several modules.
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+/// Runs `future` on this thread by polling it with a waker that does
+/// nothing, for futures that never wait on anything that would wake them,
+/// like the server methods and `MemoryStore` under test.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
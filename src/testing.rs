@@ -0,0 +1,263 @@
+//! A reference implementation and proptest strategies for checking code
+//! built on this crate against a known-good oracle.
+//!
+//! The reference functions hash from the definition of the tree, recursing
+//! over halves of the leaf range, and share no code with `MerkleTree`.
+
+use digest::Digest;
+use proptest::prelude::*;
+
+use crate::{hash_leaf, AsBytes, Hash, MerkleTree, Padding, Proof, Sentinel, Sibling};
+
+/// Number of levels above `count_leaves` leaves, at least one.
+fn reference_height(count_leaves: usize) -> usize {
+    let mut height = 1;
+    while 1 << height < count_leaves {
+        height += 1;
+    }
+    height
+}
+
+fn reference_leaf<H: Digest>(value: &[u8], hasher: &mut H) -> Hash {
+    hasher.update([0u8]);
+    hasher.update(value);
+    hasher.finalize_reset().to_vec()
+}
+
+fn reference_node<H: Digest>(
+    left: &[u8],
+    right: Option<&[u8]>,
+    padding: Padding,
+    hasher: &mut H,
+) -> Hash {
+    let right = match (right, padding) {
+        (Some(right), _) => right.to_vec(),
+        (None, Padding::Duplicate) => left.to_vec(),
+        (None, Padding::Empty) => {
+            hasher.update([3u8]);
+            hasher.finalize_reset().to_vec()
+        }
+        (None, Padding::Constant(sentinel)) => sentinel.as_bytes().to_vec(),
+        (None, Padding::Reject) => panic!("reference trees rejecting padding need 2^k leaves"),
+        (None, Padding::Promote) => return left.to_vec(),
+    };
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(&right);
+    hasher.finalize_reset().to_vec()
+}
+
+/// Hashes the node over the `2^level` leaves starting at `start`, or returns
+/// `None` if it holds none of `leaves`.
+fn reference_subtree<H: Digest>(
+    leaves: &[Hash],
+    start: usize,
+    level: usize,
+    padding: Padding,
+    hasher: &mut H,
+) -> Option<Hash> {
+    if start >= leaves.len() {
+        return None;
+    }
+    if level == 0 {
+        return Some(leaves[start].clone());
+    }
+
+    let half = 1 << (level - 1);
+    let left = reference_subtree(leaves, start, level - 1, padding, hasher)?;
+    let right = reference_subtree(leaves, start + half, level - 1, padding, hasher);
+    Some(reference_node(&left, right.as_deref(), padding, hasher))
+}
+
+/// Returns the root a tree over `values` has, computed from scratch.
+pub fn reference_root<T, H>(values: &[T], padding: Padding, mut hasher: H) -> Hash
+where
+    T: AsBytes,
+    H: Digest,
+{
+    assert!(!values.is_empty(), "expected at least 1 value");
+
+    let leaves: Vec<Hash> = values
+        .iter()
+        .map(|v| reference_leaf(v.as_bytes(), &mut hasher))
+        .collect();
+    let height = reference_height(leaves.len());
+    reference_subtree(&leaves, 0, height, padding, &mut hasher).unwrap()
+}
+
+/// Returns the inclusion proof of `position` in a tree over `values`,
+/// hashing every sibling subtree from scratch.
+pub fn reference_proof<T, H>(
+    values: &[T],
+    position: usize,
+    padding: Padding,
+    mut hasher: H,
+) -> Proof
+where
+    T: AsBytes,
+    H: Digest,
+{
+    assert!(
+        position < values.len(),
+        "position does not relate to any leaf"
+    );
+
+    let leaves: Vec<Hash> = values
+        .iter()
+        .map(|v| reference_leaf(v.as_bytes(), &mut hasher))
+        .collect();
+    let path = (0..reference_height(leaves.len()))
+        .map(|level| {
+            let index = position >> level;
+            let sibling = index ^ 1;
+            match reference_subtree(&leaves, sibling << level, level, padding, &mut hasher) {
+                Some(hash) if index % 2 == 1 => Sibling::Left(hash),
+                Some(hash) => Sibling::Right(hash),
+                None => match padding {
+                    Padding::Duplicate => Sibling::Duplicate,
+                    Padding::Empty => Sibling::Empty,
+                    Padding::Constant(sentinel) => Sibling::Padding(sentinel.as_bytes().to_vec()),
+                    Padding::Reject => panic!("reference trees rejecting padding need 2^k leaves"),
+                    Padding::Promote => Sibling::Promoted,
+                },
+            }
+        })
+        .collect();
+
+    Proof::new(position, leaves.len(), path)
+}
+
+/// Leaf values and the padding of a generated tree.
+#[derive(Clone, Debug)]
+pub struct TreeCase {
+    pub values: Vec<Vec<u8>>,
+    pub padding: Padding,
+}
+
+impl TreeCase {
+    /// Builds the tree with `MerkleTree`.
+    pub fn build<H: Digest>(&self, mut hasher: H) -> MerkleTree<H> {
+        let leaves = self
+            .values
+            .iter()
+            .map(|v| hash_leaf(v, &mut hasher))
+            .collect();
+        let mut tree = MerkleTree::build_from_leaves(leaves, hasher);
+        tree.set_padding(self.padding);
+        tree
+    }
+
+    /// Computes the root with the reference implementation.
+    pub fn reference_root<H: Digest>(&self, hasher: H) -> Hash {
+        reference_root(&self.values, self.padding, hasher)
+    }
+}
+
+/// A generated tree, a position in it and the reference proof and root.
+#[derive(Clone, Debug)]
+pub struct ProofCase {
+    pub tree: TreeCase,
+    pub position: usize,
+    pub proof: Proof,
+    pub root: Hash,
+}
+
+/// Generates 1 to `max_len` leaf values of up to 64 bytes, duplicates
+/// included.
+pub fn values(max_len: usize) -> impl Strategy<Value = Vec<Vec<u8>>> {
+    assert!(max_len > 0, "expected at least 1 leaf");
+
+    let value = prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..64),
+        // Few distinct short values, so equal neighbours come up often.
+        (0u8..4).prop_map(|b| vec![b]),
+    ];
+    prop::collection::vec(value, 1..=max_len)
+}
+
+/// Generates every padding that accepts any leaf count: duplicating, empty,
+/// promoting or a random constant.
+pub fn paddings() -> impl Strategy<Value = Padding> {
+    prop_oneof![
+        Just(Padding::Duplicate),
+        Just(Padding::Empty),
+        Just(Padding::Promote),
+        any::<[u8; 32]>().prop_map(|hash| Padding::Constant(Sentinel::new(&hash))),
+    ]
+}
+
+/// Generates trees of 1 to `max_len` leaves with any of those paddings.
+pub fn trees(max_len: usize) -> impl Strategy<Value = TreeCase> {
+    (values(max_len), paddings()).prop_map(|(values, padding)| TreeCase { values, padding })
+}
+
+/// Generates a position in a tree of up to `max_len` leaves with its
+/// reference proof and root under `H`.
+pub fn proofs<H: Digest>(max_len: usize) -> impl Strategy<Value = ProofCase> {
+    trees(max_len)
+        .prop_flat_map(|tree| {
+            let count_leaves = tree.values.len();
+            (Just(tree), 0..count_leaves)
+        })
+        .prop_map(|(tree, position)| ProofCase {
+            proof: reference_proof(&tree.values, position, tree.padding, H::new()),
+            root: tree.reference_root(H::new()),
+            tree,
+            position,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn reference_height_covers_every_leaf() {
+        assert_eq!(reference_height(1), 1);
+        assert_eq!(reference_height(2), 1);
+        assert_eq!(reference_height(3), 2);
+        assert_eq!(reference_height(4), 2);
+        assert_eq!(reference_height(5), 3);
+    }
+
+    #[test]
+    fn reference_matches_small_trees() {
+        let values = ["a", "b", "c", "d", "e"];
+        for len in 1..=values.len() {
+            let tree = MerkleTree::build_with_hasher(&values[..len], Sha256::default());
+            let root = reference_root(&values[..len], Padding::Duplicate, Sha256::default());
+            assert_eq!(tree.root_hash(), &root);
+            for position in 0..len {
+                let proof = reference_proof(
+                    &values[..len],
+                    position,
+                    Padding::Duplicate,
+                    Sha256::default(),
+                );
+                assert_eq!(tree.gen_proof(position), proof);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn tree_root_matches_reference(case in trees(20)) {
+            let tree = case.build(Sha256::default());
+            prop_assert_eq!(tree.root_hash(), &case.reference_root(Sha256::default()));
+        }
+
+        #[test]
+        fn tree_proof_matches_reference(case in proofs::<Sha256>(20)) {
+            let tree = case.tree.build(Sha256::default());
+            prop_assert_eq!(tree.gen_proof(case.position), case.proof.clone());
+            prop_assert!(case.proof.validate_with_padding(
+                &case.root,
+                &case.tree.values[case.position],
+                case.tree.padding,
+                Sha256::default()
+            ));
+        }
+    }
+}
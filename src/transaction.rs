@@ -0,0 +1,186 @@
+use digest::Digest;
+
+use crate::{AsBytes, Error, MerkleTree, Mutation};
+
+/// Mutations collected by `MerkleTree::transaction`.
+///
+/// Nothing reaches the tree until the transaction closure returns `Ok`.
+pub struct Transaction<T> {
+    mutations: Vec<Mutation<T>>,
+    count_leaves: usize,
+}
+
+impl<T> Transaction<T> {
+    /// Returns the number of leaves the tree will have once committed.
+    pub fn count_leaves(&self) -> usize {
+        self.count_leaves
+    }
+
+    pub fn set(&mut self, position: usize, value: T) {
+        self.try_set(position, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `set`, but fails instead of panicking if `position` does not
+    /// relate to any leaf the tree will have at that point.
+    pub fn try_set(&mut self, position: usize, value: T) -> Result<(), Error> {
+        if position >= self.count_leaves {
+            return Err(Error::InvalidPosition(position));
+        }
+        self.mutations.push(Mutation::Set(position, value));
+        Ok(())
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.count_leaves += 1;
+        self.mutations.push(Mutation::Push(value));
+    }
+
+    pub fn remove(&mut self, position: usize) {
+        self.try_remove(position)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `remove`, but fails instead of panicking if `position` does not
+    /// relate to any leaf the tree will have at that point or is its last
+    /// leaf.
+    pub fn try_remove(&mut self, position: usize) -> Result<(), Error> {
+        if position >= self.count_leaves {
+            return Err(Error::InvalidPosition(position));
+        }
+        if self.count_leaves == 1 {
+            return Err(Error::RemoveLastLeaf);
+        }
+        self.count_leaves -= 1;
+        self.mutations.push(Mutation::Remove(position));
+        Ok(())
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Runs `f` and applies the mutations it recorded as a single batch.
+    ///
+    /// If `f` returns an error or panics, the tree is left untouched. Panics
+    /// if the tree's padding rejects the leaf count the mutations leave.
+    pub fn transaction<T, F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        T: AsBytes,
+        F: FnOnce(&mut Transaction<T>) -> Result<R, E>,
+    {
+        let (result, tx) = self.record_transaction(f)?;
+        self.apply(&tx.mutations);
+
+        Ok(result)
+    }
+
+    /// Like `transaction`, but fails with `Error::PaddingRejected`, leaving
+    /// the tree untouched, instead of panicking if the tree's padding rejects
+    /// the leaf count the mutations leave.
+    pub fn try_transaction<T, F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        T: AsBytes,
+        F: FnOnce(&mut Transaction<T>) -> Result<R, E>,
+        E: From<Error>,
+    {
+        let (result, tx) = self.record_transaction(f)?;
+        self.try_apply(&tx.mutations)?;
+
+        Ok(result)
+    }
+
+    fn record_transaction<T, F, R, E>(&self, f: F) -> Result<(R, Transaction<T>), E>
+    where
+        F: FnOnce(&mut Transaction<T>) -> Result<R, E>,
+    {
+        let mut tx = Transaction {
+            mutations: Vec::new(),
+            count_leaves: self.count_leaves(),
+        };
+
+        let result = f(&mut tx)?;
+        Ok((result, tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Padding;
+
+    #[test]
+    fn try_set_and_try_remove_check_positions() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        let result: Result<(), Error> = tree.transaction(|tx| {
+            assert_eq!(tx.try_set(1, "b"), Err(Error::InvalidPosition(1)));
+            assert_eq!(tx.try_remove(0), Err(Error::RemoveLastLeaf));
+            tx.push("b");
+            tx.try_set(1, "c")?;
+            tx.try_remove(0)
+        });
+
+        assert_eq!(result, Ok(()));
+        let expected = MerkleTree::build_with_hasher(&["c"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+
+    #[test]
+    fn try_transaction_fails_on_rejected_padding_without_changing_tree() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.set_padding(Padding::Reject);
+        let root = tree.root_hash().clone();
+
+        let result: Result<(), Error> = tree.try_transaction(|tx| {
+            tx.push("c");
+            Ok(())
+        });
+        assert_eq!(result, Err(Error::PaddingRejected(3)));
+        assert_eq!(tree.root_hash(), &root);
+    }
+
+    #[test]
+    fn commits_every_mutation_on_ok() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let result: Result<usize, Error> = tree.transaction(|tx| {
+            tx.set(0, "c");
+            tx.push("d");
+            Ok(tx.count_leaves())
+        });
+
+        assert_eq!(result, Ok(3));
+        let expected = MerkleTree::build_with_hasher(&["c", "b", "d"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+
+    #[test]
+    fn rolls_back_on_error() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = tree.root_hash().clone();
+
+        let result: Result<(), &str> = tree.transaction(|tx| {
+            tx.set(0, "c");
+            tx.push("d");
+            Err("abort")
+        });
+        assert_eq!(result, Err("abort"));
+        assert_eq!(tree.root_hash(), &root);
+        assert_eq!(tree.count_leaves(), 2);
+    }
+
+    #[test]
+    fn rolls_back_on_panic() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        let root = tree.root_hash().clone();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _: Result<(), Error> = tree.transaction(|tx| {
+                tx.push("c");
+                tx.remove(5);
+                Ok(())
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(tree.root_hash(), &root);
+    }
+}
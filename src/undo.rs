@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+
+use digest::Digest;
+
+use crate::metadata::LeafMetadata;
+use crate::mutation::LeafMutation;
+use crate::{Hash, MerkleTree};
+
+/// A batch of leaf changes together with the changes that revert it.
+struct Entry {
+    forward: Vec<LeafMutation>,
+    inverse: Vec<LeafMutation>,
+    /// Whether the values kept in the mutations are zeroed on drop.
+    #[cfg(feature = "zeroize")]
+    wipes: bool,
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if self.wipes {
+            crate::wipe::wipe_mutations(&mut self.forward);
+            crate::wipe::wipe_mutations(&mut self.inverse);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct UndoLog {
+    limit: usize,
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl UndoLog {
+    #[cfg(feature = "zeroize")]
+    pub(crate) fn set_wipes(&mut self, wipes: bool) {
+        for entry in self.undo.iter_mut().chain(&mut self.redo) {
+            entry.wipes = wipes;
+        }
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Records up to `batches` batches of changes so they can be undone.
+    ///
+    /// Every call to `apply`, `defer`, `push` or `extend`, and every committed
+    /// transaction, counts as one batch. Zero, the default, records nothing.
+    pub fn set_undo_limit(&mut self, batches: usize) {
+        self.undo_log.limit = batches;
+        while self.undo_log.undo.len() > batches {
+            self.undo_log.undo.pop_front();
+        }
+    }
+
+    /// Reverts the last `n` batches of changes and returns how many were
+    /// reverted.
+    pub fn undo(&mut self, n: usize) -> usize {
+        let mut count = 0;
+        while count < n {
+            let entry = match self.undo_log.undo.pop_back() {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.change_leaves(entry.inverse.clone());
+            self.undo_log.redo.push(entry);
+            count += 1;
+        }
+
+        self.flush();
+        count
+    }
+
+    /// Reapplies the last `n` undone batches and returns how many were
+    /// reapplied.
+    pub fn redo(&mut self, n: usize) -> usize {
+        let mut count = 0;
+        while count < n {
+            let entry = match self.undo_log.redo.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.change_leaves(entry.forward.clone());
+            self.undo_log.undo.push_back(entry);
+            count += 1;
+        }
+
+        self.flush();
+        count
+    }
+
+    /// Remembers how to revert `mutations` before they are applied.
+    pub(crate) fn record_undo(&mut self, mutations: &[LeafMutation]) {
+        if self.undo_log.limit == 0 {
+            return;
+        }
+
+        let inverse = self.inverse_mutations(mutations);
+        self.undo_log.redo.clear();
+        self.undo_log.undo.push_back(Entry {
+            forward: mutations.to_vec(),
+            inverse,
+            #[cfg(feature = "zeroize")]
+            wipes: self.wipe.is_some(),
+        });
+        while self.undo_log.undo.len() > self.undo_log.limit {
+            self.undo_log.undo.pop_front();
+        }
+    }
+
+    /// Drops the last recorded batch, for changes that were reverted before
+    /// anyone could undo them.
+    pub(crate) fn forget_last_undo(&mut self) {
+        if self.undo_log.limit > 0 {
+            self.undo_log.undo.pop_back();
+        }
+    }
+
+    /// Returns the mutations that revert `mutations`, in the order they must
+    /// be applied.
+    pub(crate) fn inverse_mutations(&self, mutations: &[LeafMutation]) -> Vec<LeafMutation> {
+        let count_leaves = self.count_leaves();
+        let stable = mutations.iter().all(|m| match m {
+            LeafMutation::Set(i, _)
+            | LeafMutation::SetMetadata(i, _)
+            | LeafMutation::SetValue(i, _) => *i < count_leaves,
+            LeafMutation::Push(_) => true,
+            _ => false,
+        });
+
+        // The inverse is built in the order of `mutations` and reversed at the
+        // end, so the steps reverting one mutation are pushed backwards.
+        let mut inverse = Vec::with_capacity(mutations.len());
+        if stable {
+            // Positions do not move, so reverting every change to the state the
+            // leaf had before the batch is enough.
+            let mut len = count_leaves;
+            for m in mutations {
+                match m {
+                    LeafMutation::Set(i, _) => {
+                        if self.retains_values {
+                            inverse.push(LeafMutation::SetValue(*i, self.values[*i].clone()));
+                        }
+                        inverse.push(LeafMutation::Set(*i, self.plain_leaf(*i).clone()));
+                    }
+                    LeafMutation::SetMetadata(i, _) => inverse.push(LeafMutation::SetMetadata(
+                        *i,
+                        self.leaf_metadata(*i).cloned(),
+                    )),
+                    LeafMutation::SetValue(i, _) => inverse.push(LeafMutation::SetValue(
+                        *i,
+                        self.values.get(*i).cloned().flatten(),
+                    )),
+                    _ => {
+                        inverse.push(LeafMutation::Remove(len));
+                        len += 1;
+                    }
+                }
+            }
+        } else {
+            // Replay the batch on a copy of the leaves, to find what each
+            // mutation overwrites or removes.
+            let mut leaves: Vec<(Hash, Option<LeafMetadata>, Option<Vec<u8>>)> = (0..count_leaves)
+                .map(|i| {
+                    (
+                        self.plain_leaf(i).clone(),
+                        self.leaf_metadata(i).cloned(),
+                        self.values.get(i).cloned().flatten(),
+                    )
+                })
+                .collect();
+            for m in mutations {
+                match m {
+                    LeafMutation::Set(i, leaf) => {
+                        let (plain, _, value) = &mut leaves[*i];
+                        if let Some(value) = value.take() {
+                            inverse.push(LeafMutation::SetValue(*i, Some(value)));
+                        }
+                        let old = std::mem::replace(plain, leaf.clone());
+                        inverse.push(LeafMutation::Set(*i, old));
+                    }
+                    LeafMutation::Push(leaf) => {
+                        inverse.push(LeafMutation::Remove(leaves.len()));
+                        leaves.push((leaf.clone(), None, None));
+                    }
+                    LeafMutation::Remove(i) => {
+                        let (leaf, metadata, value) = leaves.remove(*i);
+                        if value.is_some() {
+                            inverse.push(LeafMutation::SetValue(*i, value));
+                        }
+                        inverse.push(LeafMutation::Insert(*i, leaf, metadata));
+                    }
+                    LeafMutation::Insert(i, leaf, metadata) => {
+                        inverse.push(LeafMutation::Remove(*i));
+                        leaves.insert(*i, (leaf.clone(), metadata.clone(), None));
+                    }
+                    LeafMutation::SetMetadata(i, metadata) => {
+                        let old = std::mem::replace(&mut leaves[*i].1, metadata.clone());
+                        inverse.push(LeafMutation::SetMetadata(*i, old));
+                    }
+                    LeafMutation::SetValue(i, value) => {
+                        let old = std::mem::replace(&mut leaves[*i].2, value.clone());
+                        inverse.push(LeafMutation::SetValue(*i, old));
+                    }
+                }
+            }
+            #[cfg(feature = "zeroize")]
+            if self.wipe.is_some() {
+                for (_, _, value) in &mut leaves {
+                    crate::wipe::wipe_value(value);
+                }
+            }
+        }
+        inverse.reverse();
+        inverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    fn roots_of(tree: &mut MerkleTree<Sha256>, batches: &[Vec<Mutation<&str>>]) -> Vec<Hash> {
+        let mut roots = vec![tree.root_hash().clone()];
+        for batch in batches {
+            tree.apply(batch);
+            roots.push(tree.root_hash().clone());
+        }
+        roots
+    }
+
+    #[test]
+    fn undo_and_redo_walk_back_and_forth() {
+        let mut tree = MerkleTree::build_retaining_values(&["a", "b", "c"], Sha256::default());
+        tree.set_undo_limit(10);
+        let roots = roots_of(
+            &mut tree,
+            &[
+                vec![Mutation::Set(0, "d"), Mutation::Push("e")],
+                vec![Mutation::Remove(1), Mutation::Set(0, "f")],
+                vec![Mutation::Push("g")],
+            ],
+        );
+
+        for expected in roots.iter().rev().skip(1) {
+            assert_eq!(tree.undo(1), 1);
+            assert_eq!(tree.root_hash(), expected);
+        }
+        assert_eq!(tree.undo(1), 0);
+        assert_eq!(tree.value(1), Some(&b"b"[..]));
+
+        assert_eq!(tree.redo(5), 3);
+        assert_eq!(tree.root_hash(), &roots[3]);
+        assert_eq!(tree.value(0), Some(&b"f"[..]));
+    }
+
+    #[test]
+    fn new_changes_clear_redo() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        tree.set_undo_limit(10);
+        tree.push(&"c");
+        assert_eq!(tree.undo(1), 1);
+        tree.push(&"d");
+        assert_eq!(tree.redo(1), 0);
+
+        let expected = MerkleTree::build_with_hasher(&["a", "b", "d"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+
+    #[test]
+    fn limit_drops_oldest_batches() {
+        let mut tree = MerkleTree::build_with_hasher(&["a"], Sha256::default());
+        tree.set_undo_limit(2);
+        for value in ["b", "c", "d"] {
+            tree.push(&value);
+        }
+
+        assert_eq!(tree.undo(3), 2);
+        let expected = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+}
@@ -0,0 +1,181 @@
+use digest::Digest;
+
+use crate::metadata::{commit_metadata, StoredMetadata};
+use crate::{
+    count_at_level, hash_internal_node, hash_leaf, hashes_equal, height, Error, Hash, MerkleTree,
+    Padding,
+};
+
+/// The stored data of a tree that validation compares, without the hasher
+/// so that it can be shared between threads.
+pub(crate) struct Stored<'a> {
+    pub(crate) levels: &'a [Vec<Hash>],
+    metadata: &'a [Option<StoredMetadata>],
+    values: &'a [Option<Vec<u8>>],
+    padding: Padding,
+}
+
+impl Stored<'_> {
+    /// Checks that every level has as many nodes as the leaf count calls for.
+    pub(crate) fn check_shape(&self) -> Result<(), Error> {
+        let count_leaves = self.levels[0].len();
+        let expected = height(count_leaves) + 1;
+        for (level, nodes) in self.levels.iter().enumerate().take(expected) {
+            let count = count_at_level(count_leaves, level);
+            if nodes.len() != count {
+                return Err(Error::CorruptNode {
+                    level,
+                    index: count.min(nodes.len()),
+                });
+            }
+        }
+        if self.levels.len() != expected {
+            return Err(Error::CorruptNode {
+                level: expected.min(self.levels.len()),
+                index: 0,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns true if the leaf at `position` is the hash of its retained
+    /// value and committed metadata, where the tree keeps them.
+    pub(crate) fn leaf_matches<H: Digest>(&self, position: usize, hasher: &mut H) -> bool {
+        let stored = self.metadata.get(position).and_then(|m| m.as_ref());
+        let plain = match stored {
+            Some(stored) => &stored.plain,
+            None => &self.levels[0][position],
+        };
+        if let Some(Some(value)) = self.values.get(position) {
+            if !hashes_equal(plain, &hash_leaf(value, hasher)) {
+                return false;
+            }
+        }
+        match stored {
+            Some(stored) if stored.leaf.committed => hashes_equal(
+                &self.levels[0][position],
+                &commit_metadata(plain, &stored.leaf.metadata, hasher),
+            ),
+            _ => true,
+        }
+    }
+
+    /// Returns true if the node at `index` of `level`, above the leaves, is
+    /// the hash of its children.
+    pub(crate) fn node_matches<H: Digest>(
+        &self,
+        level: usize,
+        index: usize,
+        hasher: &mut H,
+    ) -> bool {
+        let children = &self.levels[level - 1];
+        let node = hash_internal_node(
+            &children[2 * index],
+            children.get(2 * index + 1),
+            self.padding,
+            hasher,
+        );
+        hashes_equal(&self.levels[level][index], &node)
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    pub(crate) fn stored(&self) -> Stored<'_> {
+        Stored {
+            levels: &self.levels,
+            metadata: &self.metadata,
+            values: &self.values,
+            padding: self.padding,
+        }
+    }
+
+    /// Hashes every node again from the leaves up and checks it against the
+    /// stored one, along with retained values and committed metadata, so a
+    /// tree whose storage came from elsewhere can be trusted before serving
+    /// proofs from it.
+    ///
+    /// Fails with the lowest corrupt node found, leaves first.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.check_clean()?;
+
+        let mut hasher = H::new();
+        let stored = self.stored();
+        stored.check_shape()?;
+        for position in 0..stored.levels[0].len() {
+            if !stored.leaf_matches(position, &mut hasher) {
+                return Err(Error::CorruptNode {
+                    level: 0,
+                    index: position,
+                });
+            }
+        }
+        for level in 1..stored.levels.len() {
+            for index in 0..stored.levels[level].len() {
+                if !stored.node_matches(level, index, &mut hasher) {
+                    return Err(Error::CorruptNode { level, index });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_retaining_values(&["a", "b", "c", "d", "e"], Sha256::default())
+    }
+
+    #[test]
+    fn built_tree_is_valid() {
+        assert_eq!(tree().validate(), Ok(()));
+    }
+
+    #[test]
+    fn deferred_changes_fail_validation() {
+        let mut tree = tree();
+        tree.defer(&[crate::Mutation::Set(1, "x")]);
+        assert_eq!(tree.validate(), Err(Error::DeferredChanges));
+        tree.flush();
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn lowest_corrupt_node_is_reported() {
+        let mut tree = tree();
+        let levels = Arc::make_mut(&mut tree.levels);
+        levels[2][1][0] ^= 1;
+        levels[1][1][0] ^= 1;
+        assert_eq!(
+            tree.validate(),
+            Err(Error::CorruptNode { level: 1, index: 1 })
+        );
+    }
+
+    #[test]
+    fn leaf_not_matching_its_value_is_corrupt() {
+        let mut tree = tree();
+        tree.values[3] = Some(b"x".to_vec());
+        assert_eq!(
+            tree.validate(),
+            Err(Error::CorruptNode { level: 0, index: 3 })
+        );
+    }
+
+    #[test]
+    fn extra_level_is_corrupt() {
+        let mut tree = tree();
+        Arc::make_mut(&mut tree.levels).push(Vec::new());
+        assert_eq!(
+            tree.validate(),
+            Err(Error::CorruptNode { level: 4, index: 0 })
+        );
+    }
+}
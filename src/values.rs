@@ -0,0 +1,71 @@
+use digest::Digest;
+
+use crate::{AsBytes, Hash, MerkleTree};
+
+impl<H: Digest> MerkleTree<H> {
+    /// Builds a tree like `build_with_hasher` that also keeps a copy of every
+    /// leaf value, so the tree can be read back as a collection.
+    pub fn build_retaining_values<T>(values: &[T], hasher: H) -> MerkleTree<H>
+    where
+        T: AsBytes,
+    {
+        let mut tree = MerkleTree::build_with_hasher(values, hasher);
+        tree.retains_values = true;
+        tree.values = values.iter().map(|v| Some(v.as_bytes().to_vec())).collect();
+        tree
+    }
+
+    pub fn retains_values(&self) -> bool {
+        self.retains_values
+    }
+
+    /// Returns the retained value of the leaf at `position`.
+    ///
+    /// Values are only known for trees built with `build_retaining_values`, and
+    /// not for leaves that were changed through a patch without values.
+    pub fn value(&self, position: usize) -> Option<&[u8]> {
+        self.values.get(position).and_then(|v| v.as_deref())
+    }
+
+    /// Iterates over `(position, value, leaf hash)` of every leaf whose value
+    /// is retained, in position order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[u8], &Hash)> {
+        self.values
+            .iter()
+            .zip(self.leaves())
+            .enumerate()
+            .filter_map(|(i, (v, leaf))| v.as_deref().map(|v| (i, v, leaf)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn retained_values_follow_mutations() {
+        let mut tree = MerkleTree::build_retaining_values(&["a", "b", "c"], Sha256::default());
+        tree.apply(&[
+            Mutation::Set(0, "d"),
+            Mutation::Remove(1),
+            Mutation::Push("e"),
+        ]);
+
+        let values: Vec<(usize, &[u8])> = tree.iter().map(|(i, v, _)| (i, v)).collect();
+        assert_eq!(values, vec![(0, &b"d"[..]), (1, &b"c"[..]), (2, &b"e"[..])]);
+        for (i, _, leaf) in tree.iter() {
+            assert_eq!(tree.leaf(i), Some(leaf));
+        }
+    }
+
+    #[test]
+    fn trees_built_without_values_have_none() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b"], Sha256::default());
+        assert!(!tree.retains_values());
+        assert_eq!(tree.value(0), None);
+        assert_eq!(tree.iter().count(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+use std::ops::ControlFlow;
+
+use digest::Digest;
+
+use crate::{Error, Hash, MerkleTree};
+
+impl<H: Digest> MerkleTree<H> {
+    /// Calls `f` with `(level, index, hash)` of every node, depth first from
+    /// the root with left children before right ones, until `f` breaks.
+    ///
+    /// Levels count up from the leaves at 0, as in `subtree`. A node paired
+    /// with itself is only visited once.
+    pub fn visit<B, F>(&self, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(usize, usize, &Hash) -> ControlFlow<B>,
+    {
+        self.try_visit(f).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `visit`, but fails instead of panicking if the tree has deferred
+    /// changes.
+    pub fn try_visit<B, F>(&self, mut f: F) -> Result<ControlFlow<B>, Error>
+    where
+        F: FnMut(usize, usize, &Hash) -> ControlFlow<B>,
+    {
+        self.check_clean()?;
+
+        let mut stack = vec![(self.levels.len() - 1, 0)];
+        while let Some((level, index)) = stack.pop() {
+            if let ControlFlow::Break(b) = f(level, index, &self.levels[level][index]) {
+                return Ok(ControlFlow::Break(b));
+            }
+            if level > 0 {
+                let right = 2 * index + 1;
+                if right < self.levels[level - 1].len() {
+                    stack.push((level - 1, right));
+                }
+                stack.push((level - 1, 2 * index));
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Folds every node into an accumulator in the order of `visit`. `f` may
+    /// break with the final value to stop early.
+    pub fn fold<A, F>(&self, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, usize, usize, &Hash) -> ControlFlow<A, A>,
+    {
+        let mut acc = Some(init);
+        let flow = self.visit(|level, index, hash| {
+            acc = Some(f(acc.take().unwrap(), level, index, hash)?);
+            ControlFlow::Continue(())
+        });
+
+        match flow {
+            ControlFlow::Continue(()) => acc.unwrap(),
+            ControlFlow::Break(a) => a,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Mutation;
+
+    #[test]
+    fn try_visit_fails_on_deferred_changes() {
+        let mut tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        tree.defer(&[Mutation::Set(1, "z")]);
+        let flow = tree.try_visit(|_, _, _| ControlFlow::<()>::Continue(()));
+        assert!(matches!(flow, Err(Error::DeferredChanges)));
+
+        tree.flush();
+        let mut count = 0;
+        let flow = tree.try_visit(|_, _, _| {
+            count += 1;
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(flow, Ok(ControlFlow::Continue(())));
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn visit_goes_depth_first_left_to_right() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        let mut order = Vec::new();
+        let _ = tree.visit(|level, index, hash| {
+            assert_eq!(hash, &tree.levels[level][index]);
+            order.push((level, index));
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(order, vec![(2, 0), (1, 0), (0, 0), (0, 1), (1, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn fold_stops_at_break() {
+        let tree = MerkleTree::build_with_hasher(&["a", "b", "c", "d"], Sha256::default());
+        let leaves = tree.fold(0, |count, level, _, _| {
+            ControlFlow::Continue(count + (level == 0) as usize)
+        });
+        assert_eq!(leaves, 4);
+
+        let first_leaf = tree.fold(None, |_, level, index, _| {
+            if level == 0 {
+                ControlFlow::Break(Some(index))
+            } else {
+                ControlFlow::Continue(None)
+            }
+        });
+        assert_eq!(first_leaf, Some(0));
+    }
+}
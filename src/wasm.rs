@@ -0,0 +1,80 @@
+//! JavaScript bindings for SHA-256 trees, so browsers can check proofs made
+//! by a Rust backend.
+
+use digest::Digest;
+use js_sys::Uint8Array;
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+
+use crate::{leaf_hash_with_hasher, ConsistencyProof, MerkleTree, Proof};
+
+/// Returns the root of the tree over `values`.
+#[wasm_bindgen(js_name = rootOf)]
+pub fn root_of(values: Vec<Uint8Array>) -> Result<Vec<u8>, JsError> {
+    if values.is_empty() {
+        return Err(JsError::new("expected at least 1 value"));
+    }
+
+    let leaves = values
+        .iter()
+        .map(|v| leaf_hash_with_hasher(&v.to_vec(), Sha256::new()))
+        .collect();
+    let tree = MerkleTree::build_from_leaves(leaves, Sha256::new());
+    Ok(tree.root_hash().clone())
+}
+
+/// Returns the hash a tree stores for the leaf `value`.
+#[wasm_bindgen(js_name = leafHash)]
+pub fn leaf_hash(value: &[u8]) -> Vec<u8> {
+    leaf_hash_with_hasher(&value.to_vec(), Sha256::new())
+}
+
+/// Returns true if `value` is the leaf proven by `proof`, as written by
+/// `Proof::to_bytes`, in the tree with `root`.
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(proof: &[u8], root: &[u8], value: &[u8]) -> Result<bool, JsError> {
+    let proof = Proof::from_bytes(proof)?;
+    Ok(proof.validate::<_, Sha256>(root, &value.to_vec()))
+}
+
+/// Returns true if `proof`, as written by `ConsistencyProof::to_bytes`, links
+/// `old_root` to `new_root`.
+#[wasm_bindgen(js_name = verifyConsistency)]
+pub fn verify_consistency(proof: &[u8], old_root: &[u8], new_root: &[u8]) -> Result<bool, JsError> {
+    let proof = ConsistencyProof::from_bytes(proof)?;
+    Ok(proof.verify_with_hasher(old_root, new_root, Sha256::new()))
+}
+
+// `JsError` and `Uint8Array` call into JavaScript, so natively only the
+// paths that succeed without them can run.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> MerkleTree<Sha256> {
+        MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default())
+    }
+
+    #[test]
+    fn leaf_hash_matches_the_tree() {
+        assert_eq!(&leaf_hash(b"b"), tree().leaves().nth(1).unwrap());
+    }
+
+    #[test]
+    fn proofs_verify_from_bytes() {
+        let tree = tree();
+        let proof = tree.gen_proof(2).to_bytes();
+        assert!(verify_proof(&proof, tree.root_hash(), b"c").unwrap());
+        assert!(!verify_proof(&proof, tree.root_hash(), b"a").unwrap());
+    }
+
+    #[test]
+    fn consistency_verifies_from_bytes() {
+        let mut tree = tree();
+        let old_root = tree.root_hash().clone();
+        tree.push(&"d");
+        let proof = tree.consistency_proof(3).to_bytes();
+        assert!(verify_consistency(&proof, &old_root, tree.root_hash()).unwrap());
+        assert!(!verify_consistency(&proof, tree.root_hash(), &old_root).unwrap());
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use digest::Digest;
+use zeroize::Zeroize;
+
+use crate::mutation::LeafMutation;
+use crate::MerkleTree;
+
+/// Overwrites what the hasher buffered of the last value it hashed.
+///
+/// Finalizing resets a hasher without clearing its block buffer, which keeps
+/// the tail of the last input. One byte followed by 255 more refills the
+/// buffers of block sizes up to 256 bytes with zeros before the reset.
+fn scrub_hasher<H: Digest>(hasher: &mut H) {
+    hasher.update([0u8]);
+    hasher.update([0u8; 255]);
+    hasher.reset();
+}
+
+pub(crate) fn wipe_value(value: &mut Option<Vec<u8>>) {
+    if let Some(value) = value {
+        value.zeroize();
+    }
+}
+
+pub(crate) fn wipe_mutations(mutations: &mut [LeafMutation]) {
+    for m in mutations {
+        if let LeafMutation::SetValue(_, value) = m {
+            wipe_value(value);
+        }
+    }
+}
+
+impl<H: Digest> MerkleTree<H> {
+    /// Zeroes the retained values, the leaf hashes, the values kept for undo
+    /// and the hasher state when the tree is dropped, and retained values as
+    /// soon as a mutation replaces or removes them, for trees over secret
+    /// data.
+    ///
+    /// Nodes still shared with a snapshot are left to the snapshot, and
+    /// values the caller passed in are the caller's to wipe.
+    pub fn set_zeroize_on_drop(&mut self, enabled: bool) {
+        self.wipe = if enabled {
+            Some(scrub_hasher::<H>)
+        } else {
+            None
+        };
+        self.undo_log.set_wipes(enabled);
+    }
+
+    pub fn zeroizes_on_drop(&self) -> bool {
+        self.wipe.is_some()
+    }
+}
+
+impl<H> Drop for MerkleTree<H> {
+    fn drop(&mut self) {
+        let scrub = match self.wipe {
+            Some(scrub) => scrub,
+            None => return,
+        };
+
+        scrub(&mut self.hasher);
+        for value in &mut self.values {
+            wipe_value(value);
+        }
+        for stored in self.metadata.iter_mut().flatten() {
+            stored.plain.zeroize();
+        }
+        if let Some(levels) = Arc::get_mut(&mut self.levels) {
+            for node in levels.iter_mut().flatten() {
+                node.zeroize();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+
+    #[test]
+    fn wipe_value_zeroes_and_clears() {
+        let mut value = Some(b"secret".to_vec());
+        wipe_value(&mut value);
+        assert_eq!(value, Some(Vec::new()));
+
+        let mut none = None;
+        wipe_value(&mut none);
+        assert_eq!(none, None);
+    }
+
+    #[test]
+    fn wipe_mutations_only_touches_values() {
+        let mut mutations = vec![
+            LeafMutation::Push(vec![1, 2]),
+            LeafMutation::SetValue(0, Some(b"ab".to_vec())),
+        ];
+        wipe_mutations(&mut mutations);
+        assert!(matches!(&mutations[0], LeafMutation::Push(leaf) if leaf == &[1, 2]));
+        assert!(matches!(
+            &mutations[1],
+            LeafMutation::SetValue(0, Some(value)) if value.is_empty()
+        ));
+    }
+
+    #[test]
+    fn zeroizing_tree_keeps_working() {
+        let mut tree = MerkleTree::build_retaining_values(&["a", "b", "c"], Sha256::default());
+        assert!(!tree.zeroizes_on_drop());
+        tree.set_undo_limit(1);
+        tree.set_zeroize_on_drop(true);
+        assert!(tree.zeroizes_on_drop());
+
+        tree.update(1, &"x");
+        assert_eq!(tree.value(1), Some(&b"x"[..]));
+        tree.undo(1);
+        assert_eq!(tree.value(1), Some(&b"b"[..]));
+
+        let expected = MerkleTree::build_with_hasher(&["a", "b", "c"], Sha256::default());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+
+        tree.set_zeroize_on_drop(false);
+        assert!(!tree.zeroizes_on_drop());
+    }
+}